@@ -0,0 +1,11 @@
+//! Runs the Ethereum state test fixtures against the Conflux executor.
+//!
+//! The `evm-spec-tester` binary is a thin wrapper over `statetest`. Other
+//! tools can load the fixtures with `statetest::load_units`, or build a
+//! `statetest::UnitTester` from a `StateTestUnit`, and run them with
+//! `statetest::run_units` to get the results back as a `RunReport`.
+
+#[macro_use]
+extern crate log;
+
+pub mod statetest;
@@ -1,12 +1,10 @@
-#[macro_use]
-extern crate log;
-
-mod statetest;
-
-use statetest::command::StateTestCmd;
+use evm_spec_tester::statetest::{
+    current_test, progress_shown, StateTestCmd, CLEAR_LINE,
+};
+use std::io::Write;
 use structopt::StructOpt;
 
-fn init_logger(verbosity: u8) {
+fn init_logger(verbosity: u8, log_level: Option<&str>, summary_only: bool) {
     use log::LevelFilter;
 
     const BASE_LEVEL: u8 = 2;
@@ -18,20 +16,52 @@ fn init_logger(verbosity: u8) {
         3 => LevelFilter::Debug,
         _ => LevelFilter::Trace,
     };
+    // The failures and the counts are printed apart from the logs
+    let level = if summary_only {
+        LevelFilter::Warn
+    } else {
+        level
+    };
 
-    env_logger::Builder::new()
-        .target(env_logger::Target::Stdout)
-        .filter(None, LevelFilter::Off)
-        .filter_module("evm_spec_tester", level)
-        .format_timestamp(None) // Optional: add timestamp
-        // .format_level(true)     // show log level
-        // .format_module_path(true)  // show module path
+    let mut builder = env_logger::Builder::new();
+    builder.target(env_logger::Target::Stdout);
+    match log_level {
+        Some(filters) => builder.parse_filters(filters),
+        None => builder
+            .filter(None, LevelFilter::Off)
+            .filter_module("evm_spec_tester", level),
+    };
+    // The records emitted while a test runs are tagged with its identifier,
+    // and replace the progress line, which is redrawn under them
+    builder
+        .format(|buf, record| {
+            if progress_shown() {
+                write!(buf, "{}", CLEAR_LINE)?;
+            }
+            match current_test() {
+                Some(test) => writeln!(
+                    buf,
+                    "[{} {}] [{}] {}",
+                    record.level(),
+                    record.target(),
+                    test,
+                    record.args()
+                ),
+                None => writeln!(
+                    buf,
+                    "[{} {}] {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ),
+            }
+        })
         .init();
 }
 
 fn main() {
     let cmd = StateTestCmd::from_args();
-    init_logger(cmd.verbose);
+    init_logger(cmd.verbose, cmd.log_level.as_deref(), cmd.summary_only);
     let success = cmd.run();
     if !success {
         std::process::exit(1);
@@ -0,0 +1,126 @@
+//! Repeated runs of the same tests for benchmarking the executor, see
+//! `--repeat`. Every run builds the states from the fixtures again, so that a
+//! repetition does not start from the state left by the previous one.
+
+use super::{
+    report::{per_second, TestRecord},
+    runner::{run_units, RunReport},
+    unit_tester::{UnitOptions, UnitTester},
+};
+use cfx_executor::machine::Machine;
+use cfxcore::verification::VerificationConfig;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Runs `units` `warmup` times, discarding the results, then `repeat` times,
+/// timing every test. Returns the report of the last run.
+pub fn run_repeated(
+    units: &[UnitTester], machine: &Arc<Machine>,
+    verification: &VerificationConfig, options: &UnitOptions, warmup: usize,
+    repeat: usize,
+) -> (RunReport, BenchTimings) {
+    for run in 1..=warmup {
+        debug!("Warmup run {}/{}", run, warmup);
+        run_units(units, machine, verification, options);
+    }
+
+    let mut timings = BenchTimings::default();
+    let mut report = RunReport::default();
+    for run in 1..=repeat {
+        debug!("Measured run {}/{}", run, repeat);
+        report = run_units(units, machine, verification, options);
+        timings.add_run(&report.records);
+    }
+    (report, timings)
+}
+
+/// The durations of the executed tests over the measured runs.
+#[derive(Debug, Default)]
+pub struct BenchTimings {
+    /// `path::name::fork::index` of a test to its timings.
+    tests: BTreeMap<String, TestTimings>,
+    /// Gas per second of every run, over the time spent in its tests.
+    throughputs: Vec<f64>,
+}
+
+#[derive(Debug, Default)]
+struct TestTimings {
+    gas_used: u64,
+    durations_ms: Vec<f64>,
+}
+
+impl BenchTimings {
+    fn add_run(&mut self, records: &[TestRecord]) {
+        let mut gas_used = 0;
+        let mut duration_ms = 0.0;
+        for record in records.iter().filter(|r| r.status.is_executed()) {
+            let (Some(spec), Some(index)) = (record.spec, record.index) else {
+                continue;
+            };
+            let id = format!(
+                "{}::{}::{:?}::{}",
+                record.path, record.name, spec, index
+            );
+            let timings = self.tests.entry(id).or_default();
+            timings.gas_used = record.gas_used.unwrap_or_default();
+            timings.durations_ms.push(record.duration_ms);
+            gas_used += timings.gas_used;
+            duration_ms += record.duration_ms;
+        }
+        self.throughputs
+            .push(per_second(gas_used as f64, duration_ms / 1000.0));
+    }
+
+    /// Prints the min, median and max duration of every test, then of the
+    /// throughput of the runs.
+    pub fn print(&self) {
+        println!(
+            "\nBenchmark: {} tests, {} runs",
+            self.tests.len(),
+            self.throughputs.len()
+        );
+        println!(
+            "{:<96} {:>12} {:>10} {:>10} {:>10}",
+            "Test", "Gas Used", "Min (ms)", "Med (ms)", "Max (ms)"
+        );
+        for (id, timings) in &self.tests {
+            let (min, median, max) = min_median_max(&timings.durations_ms);
+            println!(
+                "{:<96} {:>12} {:>10.3} {:>10.3} {:>10.3}",
+                id, timings.gas_used, min, median, max
+            );
+        }
+        let (min, median, max) = min_median_max(&self.throughputs);
+        println!(
+            "Throughput: min {:.0} gas/s, median {:.0} gas/s, max {:.0} gas/s",
+            min, median, max
+        );
+    }
+}
+
+/// The min, median and max of `values`, zero if empty.
+fn min_median_max(values: &[f64]) -> (f64, f64, f64) {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let (Some(min), Some(max)) = (sorted.first(), sorted.last()) else {
+        return (0.0, 0.0, 0.0);
+    };
+    let mid = sorted.len() / 2;
+    let median = if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    };
+    (*min, median, *max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::min_median_max;
+
+    #[test]
+    fn median_of_an_even_count_is_the_middle_mean() {
+        assert_eq!(min_median_max(&[3.0, 1.0, 2.0]), (1.0, 2.0, 3.0));
+        assert_eq!(min_median_max(&[4.0, 1.0, 3.0, 2.0]), (1.0, 2.5, 4.0));
+        assert_eq!(min_median_max(&[]), (0.0, 0.0, 0.0));
+    }
+}
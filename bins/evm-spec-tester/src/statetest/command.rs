@@ -1,5 +1,17 @@
+use super::{
+    failing::FailingList,
+    loader::glob_to_regex,
+    reference::RefEvm,
+    report::ReportTarget,
+    shard::Shard,
+    unit_tester::{Checks, ForkRange, TestId},
+    xfail::XfailList,
+};
 use cfx_config::{Configuration, RawConfiguration};
+use cfx_types::{Address, Space};
+use eest_types::{SpecId, SpecName};
 use primitives::block_header::CIP112_TRANSITION_HEIGHT;
+use regex::Regex;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -8,23 +20,341 @@ use structopt::StructOpt;
 #[structopt(name = "statetest", about = "State test command")]
 pub struct StateTestCmd {
     /// Paths to test files or directories
-    #[structopt(parse(from_os_str), required = true)]
+    #[structopt(parse(from_os_str), required_unless_one = &["stdin", "compare_runs"])]
     pub(super) paths: Vec<PathBuf>,
 
+    /// Compare the JSON reports of two runs, see `--report`, and list the
+    /// tests newly failing, passing or skipped in the second one and the gas
+    /// changes, instead of running tests. Fails on a new failure or a gas
+    /// change
+    #[structopt(long, parse(from_os_str), number_of_values = 2, value_names = &["A", "B"], conflicts_with_all = &["paths", "stdin", "list", "fuzz", "bless"])]
+    pub(super) compare_runs: Vec<PathBuf>,
+
+    /// Read a single fixture from stdin instead of the paths, either a suite
+    /// or a bare unit. Its tests are reported under the `<stdin>` path, and
+    /// a bare unit is named `stdin`
+    #[structopt(long, conflicts_with_all = &["paths", "bless", "diff_ref", "list", "fuzz", "test", "only_failing_from"])]
+    pub(super) stdin: bool,
+
     /// Configuration
     #[structopt(short, long, parse(try_from_str = make_configuration), required = true, default_value = "", help = "Path to the configuration file")]
-    pub(super) config: Configuration,
+    pub(super) config: ConfigFile,
 
-    /// Only run tests matching this string
+    /// Only run tests whose `path::name` matches this regex
     #[structopt(short, long)]
     pub(super) matches: Option<String>,
 
+    /// Match `--matches` as a plain substring instead of a regex
+    #[structopt(long, requires = "matches")]
+    pub(super) literal: bool,
+
+    /// Only load the fixture files matching one of these globs, relative to
+    /// the given path (can be used multiple times)
+    #[structopt(long, parse(try_from_str = glob_to_regex))]
+    pub(super) include: Vec<Regex>,
+
+    /// Do not load the fixture files matching these globs (can be used
+    /// multiple times)
+    #[structopt(long, parse(try_from_str = glob_to_regex))]
+    pub(super) exclude: Vec<Regex>,
+
+    /// Only run the test of this `path::name::fork::index` identifier, even
+    /// if its fork is after `--max-fork`. The path is matched as a suffix of
+    /// the fixture path
+    #[structopt(long, conflicts_with_all = &["matches", "forks", "fork_range", "max_fork", "shard"])]
+    pub(super) test: Option<TestId>,
+
+    /// Only run the tests failed in this JSON report of a previous run, see
+    /// `--report`. Those no longer in the fixtures are warned about
+    #[structopt(long, parse(try_from_str = parse_failing), conflicts_with = "test")]
+    pub(super) only_failing_from: Option<FailingList>,
+
+    /// Only run the post states of these forks (can be used multiple times)
+    #[structopt(long = "fork", parse(try_from_str = parse_spec_name))]
+    pub(super) forks: Vec<SpecName>,
+
+    /// Only run the post states of the forks from `Start` to `End` included,
+    /// given as `Start..End`, e.g. `Shanghai..Prague`
+    #[structopt(long)]
+    pub(super) fork_range: Option<ForkRange>,
+
+    /// Skip the post states of the forks after this one, defaults to the
+    /// newest fork supported by the executor
+    #[structopt(long, parse(try_from_str = parse_max_fork))]
+    pub(super) max_fork: Option<SpecId>,
+
+    /// Emit an EIP-3155 trace for every executed transaction
+    #[structopt(long)]
+    pub(super) trace: bool,
+
+    /// Write the traces to this file instead of stdout
+    #[structopt(
+        long,
+        parse(from_os_str),
+        requires = "trace",
+        conflicts_with = "output_dir"
+    )]
+    pub(super) trace_output: Option<PathBuf>,
+
+    /// Write the artifacts of the run to this directory, one file per test
+    /// named after its identifier: the traces of `--trace` in `traces`, the
+    /// post states of `--dump-state` in `states`, and the fixtures of
+    /// `--bless` in `blessed` instead of in place
+    #[structopt(long, parse(from_os_str))]
+    pub(super) output_dir: Option<PathBuf>,
+
+    /// Write a report of every test outcome, as `json=<path>` or
+    /// `junit=<path>` (can be used multiple times)
+    #[structopt(long = "report")]
+    pub(super) reports: Vec<ReportTarget>,
+
+    /// Print a per-fork summary table at the end of the run
+    #[structopt(long)]
+    pub(super) summary: bool,
+
+    /// Print the gas and test throughput of every fork at the end of the
+    /// run, besides the overall one
+    #[structopt(long)]
+    pub(super) metrics: bool,
+
+    /// Print the executor time spent in the interpreter, the storage accesses
+    /// and the account loads at the end of the run. Requires the `perf`
+    /// feature
+    #[structopt(long, conflicts_with_all = &["list", "fuzz"])]
+    pub(super) perf: bool,
+
+    /// Stop starting tests once this many have been executed, not counting
+    /// the skipped ones. The tests already running are completed
+    #[structopt(long, conflicts_with_all = &["list", "fuzz", "repeat"])]
+    pub(super) count: Option<usize>,
+
+    /// Run the selected tests this many times before the measured runs of
+    /// `--repeat`, discarding their results
+    #[structopt(long, requires = "repeat")]
+    pub(super) warmup: Option<usize>,
+
+    /// Benchmark the executor: run the selected tests this many times, each
+    /// on freshly built states, and print the min, median and max duration
+    /// of every test and the gas throughput of the runs. The report and the
+    /// outcome come from the last run
+    #[structopt(long, conflicts_with_all = &["list", "fuzz", "bless", "trace", "dump_state"])]
+    pub(super) repeat: Option<usize>,
+
+    /// Space to run the tests in, `ethereum` or `native`. The fixtures with
+    /// no native counterpart are reported as skipped in `native`
+    #[structopt(long, default_value = "ethereum", parse(try_from_str = parse_space))]
+    pub(super) space: Space,
+
+    /// Abandon a test running longer than this many milliseconds and report
+    /// it as timed out
+    #[structopt(long)]
+    pub(super) timeout_ms: Option<u64>,
+
+    /// Run a test failing on a database error again, on a freshly built
+    /// pre-state, up to this many times before reporting the failure
+    #[structopt(long, default_value = "0")]
+    pub(super) retries: usize,
+
+    /// Run every failing test this many more times, each on a freshly built
+    /// pre-state (and twice with `--double-run`), and report how many of the
+    /// repeats failed too, to tell a deterministic failure from a flaky one.
+    /// The failures expected by `--xfail` are not repeated
+    #[structopt(long, default_value = "0", conflicts_with_all = &["bless", "list", "fuzz"])]
+    pub(super) repeat_failures: usize,
+
+    /// Skip the tests whose transaction gas limit is above this, before
+    /// executing them, as a cheap guard against the fixtures that would run
+    /// for too long
+    #[structopt(long)]
+    pub(super) max_gas: Option<u64>,
+
+    /// Write the opcodes never executed by the tests, per fork, to this JSON
+    /// file
+    #[structopt(long, parse(from_os_str))]
+    pub(super) coverage: Option<PathBuf>,
+
+    /// Only run the tests of shard `K` out of `N`, given as `K/N`. Tests are
+    /// assigned by a stable hash of their `path::name::fork::index`
+    #[structopt(long)]
+    pub(super) shard: Option<Shard>,
+
+    /// Write the computed post states back to the fixture files instead of
+    /// checking them. Only for Ethereum space runs, the tests expecting an
+    /// exception are still checked
+    #[structopt(long)]
+    pub(super) bless: bool,
+
+    /// Write the post state of every passing test to the `states` directory
+    /// of `--output-dir`, or to `--dump-state-dir`, as a genesis allocation
+    /// JSON in a file named after the test identifier. Only for Ethereum
+    /// space runs
+    #[structopt(long, conflicts_with = "bless")]
+    pub(super) dump_state: bool,
+
+    /// Write the post states of `--dump-state` to this directory instead of
+    /// the one of `--output-dir`
+    #[structopt(long, parse(from_os_str), requires = "dump_state")]
+    pub(super) dump_state_dir: Option<PathBuf>,
+
+    /// Use this address as the block author instead of the fixture coinbase.
+    /// The fee expected on the fixture coinbase is checked on this address,
+    /// and the state root is not
+    #[structopt(long, parse(try_from_str = parse_address), conflicts_with = "bless")]
+    pub(super) coinbase: Option<Address>,
+
+    /// Run every test at this block number instead of the one of the
+    /// fixture, e.g. on both sides of a transition height of the
+    /// configuration
+    #[structopt(long, conflicts_with = "bless")]
+    pub(super) block_number: Option<u64>,
+
+    /// Run every test at this block timestamp instead of the one of the
+    /// fixture
+    #[structopt(long, conflicts_with = "bless")]
+    pub(super) timestamp: Option<u64>,
+
+    /// Shell command of a reference EVM to compare the state root and gas
+    /// used of every test with. It reads a fixture with the single test on
+    /// stdin and prints its `stateRoot` and `gasUsed` as JSON. Only for
+    /// Ethereum space runs
+    #[structopt(long, conflicts_with_all = &["bless", "coinbase"])]
+    pub(super) diff_ref: Option<RefEvm>,
+
+    /// Print the `path::name::fork::index` identifiers of the tests that
+    /// would run, without executing them
+    #[structopt(long)]
+    pub(super) list: bool,
+
+    /// Instead of checking the post states, run this many executions of every
+    /// selected unit with random mutations of the transaction and the
+    /// pre-state balances. Only panics and broken invariants are reported,
+    /// with the smallest set of mutations still failing
+    #[structopt(long, conflicts_with_all = &["bless", "list"])]
+    pub(super) fuzz: Option<usize>,
+
+    /// Seed of every random choice of the run, so far the `--fuzz`
+    /// mutations. If not given, a random one is picked and printed. It is
+    /// written in the reports, so that a run can be reproduced
+    #[structopt(long, alias = "fuzz-seed")]
+    pub(super) seed: Option<u64>,
+
+    /// JSON file mapping the `path::name::fork` of the tests known to fail to
+    /// the reason. Their failures are reported as xfail and do not fail the
+    /// run, while their passes are reported as xpass and do. The path is
+    /// matched as a suffix of the fixture path
+    #[structopt(long, parse(try_from_str = parse_xfail))]
+    pub(super) xfail: Option<XfailList>,
+
+    /// Number of worker threads, one per CPU if 0
+    #[structopt(short, long, default_value = "0")]
+    pub(super) jobs: usize,
+
+    /// Keep running after a failure and report every failing test, instead
+    /// of stopping at the first one
+    #[structopt(long)]
+    pub(super) keep_going: bool,
+
+    /// Run every post state of a unit even if one of them fails, to see all
+    /// the failing index combinations of a fixture at once. The units not
+    /// started yet are still cancelled without `--keep-going`
+    #[structopt(long)]
+    pub(super) within_unit_keep_going: bool,
+
+    /// Fail the run if a fixture file does not parse. By default it is only
+    /// reported, and the other files still run
+    #[structopt(long)]
+    pub(super) strict_parse: bool,
+
+    /// Do not draw the progress of the run, which is only drawn when stdout
+    /// is a terminal
+    #[structopt(short, long)]
+    pub(super) quiet: bool,
+
+    #[structopt(flatten)]
+    pub(super) checks: Checks,
+
+    /// Build the spec and the precompiles of every test from its fork instead
+    /// of from its block number and the transition heights of the
+    /// configuration. The features of the Ethereum forks are enabled from
+    /// the fork of the test on, the CIP-645 bundle is kept as configured.
+    /// Always done for the fixtures with the post states of several forks
+    #[structopt(long)]
+    pub(super) spec_from_fork: bool,
+
+    /// After the failures of a path are printed, open a prompt to step
+    /// through them, show their post state diff and trace, and run one again
+    /// with a trace. Ignored when stdin or stdout is not a terminal
+    #[structopt(long, conflicts_with_all = &["list", "fuzz", "bless", "repeat", "compare_runs", "stdin"])]
+    pub(super) interactive: bool,
+
     /// Verbosity level (can be used multiple times)
     #[structopt(short, long, parse(from_occurrences))]
     pub verbose: u8,
+
+    /// Log filter replacing `--verbose`, as a level or `module=level`
+    /// directives separated by commas, e.g. `debug,cfx_executor=trace`
+    #[structopt(long)]
+    pub log_level: Option<String>,
+
+    /// Only print the failures and the counts at the end of the run, without
+    /// the progress and the log records under warnings, whatever the
+    /// filters. The reports are still written
+    #[structopt(long, conflicts_with_all = &["verbose", "log_level", "list"])]
+    pub summary_only: bool,
+}
+
+fn parse_spec_name(name: &str) -> Result<SpecName, String> {
+    match serde_json::from_value(serde_json::Value::String(name.into())) {
+        Ok(SpecName::Unknown) | Err(_) => {
+            Err(format!("Unknown fork name: {}", name))
+        }
+        Ok(spec) => Ok(spec),
+    }
+}
+
+fn parse_max_fork(name: &str) -> Result<SpecId, String> {
+    match parse_spec_name(name)? {
+        SpecName::ByzantiumToConstantinopleAt5 | SpecName::Constantinople => {
+            Err(format!("Unsupported fork: {}", name))
+        }
+        spec => Ok(spec.to_spec_id()),
+    }
+}
+
+fn parse_space(name: &str) -> Result<Space, String> {
+    match name {
+        "ethereum" => Ok(Space::Ethereum),
+        "native" => Ok(Space::Native),
+        _ => Err(format!("Unknown space: {}", name)),
+    }
+}
+
+fn parse_address(s: &str) -> Result<Address, String> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    hex.parse().map_err(|_| format!("Invalid address: {}", s))
+}
+
+fn parse_xfail(path: &str) -> Result<XfailList, String> {
+    XfailList::load(path.as_ref())
+        .map_err(|e| format!("Failed to load xfail list {}: {}", path, e))
+}
+
+fn parse_failing(path: &str) -> Result<FailingList, String> {
+    FailingList::load(path.as_ref())
+        .map_err(|e| format!("Failed to load report {}: {}", path, e))
+}
+
+/// The configuration, with the file it was read from to pass it back in the
+/// reproduction commands.
+pub(super) struct ConfigFile {
+    /// Empty for the default configuration.
+    pub(super) path: String,
+    pub(super) parsed: Configuration,
 }
 
-fn make_configuration(config_file: &str) -> Result<Configuration, String> {
+pub(super) fn make_configuration(
+    config_file: &str,
+) -> Result<ConfigFile, String> {
     let mut config = Configuration::default();
     config.raw_conf = if config_file.is_empty() {
         default_raw_configuration()
@@ -46,7 +376,10 @@ fn make_configuration(config_file: &str) -> Result<Configuration, String> {
         _ => {}
     }
 
-    Ok(config)
+    Ok(ConfigFile {
+        path: config_file.to_string(),
+        parsed: config,
+    })
 }
 
 fn default_raw_configuration() -> RawConfiguration {
@@ -0,0 +1,193 @@
+//! Differences between the JSON reports of two runs, see `--compare-runs`.
+//! The tests are matched by their `path::name::spec::index` identifier,
+//! the records of a whole unit are left out.
+
+use super::{
+    failing::{read_records, RecordEntry},
+    report::TestStatus,
+};
+use std::{collections::BTreeMap, path::Path};
+
+/// The tests of the second run whose result differs from the first one.
+#[derive(Debug, Default)]
+pub struct RunDiff {
+    /// Failed in the second run, but not in the first, or not run in it. An
+    /// unexpected pass fails a run as a failure does.
+    pub newly_failing: Vec<StatusChange>,
+    /// Passed in the second run, after another status in the first.
+    pub newly_passing: Vec<StatusChange>,
+    /// Skipped in the second run, after another status in the first.
+    pub newly_skipped: Vec<StatusChange>,
+    /// The tests whose gas used was recorded by both runs and differs.
+    pub gas_changed: Vec<GasChange>,
+    /// Number of tests only in the first run.
+    pub removed: usize,
+}
+
+#[derive(Debug)]
+pub struct StatusChange {
+    pub id: String,
+    /// `None` if the test was not in the first run.
+    pub before: Option<TestStatus>,
+    pub after: TestStatus,
+}
+
+#[derive(Debug)]
+pub struct GasChange {
+    pub id: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl RunDiff {
+    /// Compares the tests of the reports at `before` and `after`.
+    pub fn load(before: &Path, after: &Path) -> Result<Self, String> {
+        let read = |path: &Path| {
+            read_records(path).map_err(|e| {
+                format!("Failed to load report {}: {}", path.display(), e)
+            })
+        };
+        Ok(Self::from_records(read(before)?, read(after)?))
+    }
+
+    fn from_records(before: Vec<RecordEntry>, after: Vec<RecordEntry>) -> Self {
+        let mut before = by_id(before);
+        let mut diff = RunDiff::default();
+        for (id, record) in by_id(after) {
+            let previous = before.remove(&id);
+            if let Some((before, after)) = previous
+                .as_ref()
+                .and_then(|p| p.gas_used)
+                .zip(record.gas_used)
+                .filter(|(before, after)| before != after)
+            {
+                diff.gas_changed.push(GasChange {
+                    id: id.clone(),
+                    before,
+                    after,
+                });
+            }
+
+            let before = previous.map(|p| p.status);
+            let after = record.status;
+            let unchanged = match before {
+                Some(before) => {
+                    before == after || (is_failure(before) && is_failure(after))
+                }
+                // A new test is only reported if it fails
+                None => !is_failure(after),
+            };
+            if unchanged {
+                continue;
+            }
+            let changes = match after {
+                TestStatus::Failed | TestStatus::Xpass => {
+                    &mut diff.newly_failing
+                }
+                TestStatus::Passed => &mut diff.newly_passing,
+                TestStatus::Skipped => &mut diff.newly_skipped,
+                TestStatus::Xfail => continue,
+            };
+            changes.push(StatusChange { id, before, after });
+        }
+        diff.removed = before.len();
+        diff
+    }
+
+    /// Whether the second run has no new failure and no gas change.
+    pub fn is_clean(&self) -> bool {
+        self.newly_failing.is_empty() && self.gas_changed.is_empty()
+    }
+
+    pub fn print(&self) {
+        let print_changes = |title: &str, changes: &[StatusChange]| {
+            println!("{}: {}", title, changes.len());
+            for change in changes {
+                match change.before {
+                    Some(before) => println!(
+                        "\t{} ({:?} -> {:?})",
+                        change.id, before, change.after
+                    ),
+                    None => {
+                        println!("\t{} (new, {:?})", change.id, change.after)
+                    }
+                }
+            }
+        };
+        print_changes("Newly Failing", &self.newly_failing);
+        print_changes("Newly Passing", &self.newly_passing);
+        print_changes("Newly Skipped", &self.newly_skipped);
+        println!("Gas Changed: {}", self.gas_changed.len());
+        for change in &self.gas_changed {
+            println!(
+                "\t{}: {} -> {} ({:+})",
+                change.id,
+                change.before,
+                change.after,
+                change.after as i128 - change.before as i128
+            );
+        }
+        println!("Removed Tests: {}", self.removed);
+    }
+}
+
+fn is_failure(status: TestStatus) -> bool {
+    matches!(status, TestStatus::Failed | TestStatus::Xpass)
+}
+
+/// The records of the post states, by identifier.
+fn by_id(records: Vec<RecordEntry>) -> BTreeMap<String, RecordEntry> {
+    records
+        .into_iter()
+        .filter_map(|record| Some((record.id()?.to_string(), record)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunDiff, StatusChange};
+    use crate::statetest::report::TestStatus;
+
+    #[test]
+    fn changes_are_keyed_by_test_id() {
+        let records = |json: &str| serde_json::from_str(json).unwrap();
+        let before = records(
+            r#"[
+                { "path": "a.json", "name": "a", "spec": "Prague", "index": 0, "status": "passed", "gasUsed": 21000 },
+                { "path": "a.json", "name": "a", "spec": "Prague", "index": 1, "status": "failed" },
+                { "path": "a.json", "name": "a", "spec": "Cancun", "index": 0, "status": "passed", "gasUsed": 50000 },
+                { "path": "b.json", "name": "b", "spec": "Prague", "index": 0, "status": "xpass" },
+                { "path": "c.json", "name": "c", "spec": "Prague", "index": 0, "status": "passed" }
+            ]"#,
+        );
+        let after = records(
+            r#"[
+                { "path": "a.json", "name": "a", "spec": "Prague", "index": 0, "status": "failed", "gasUsed": 21000 },
+                { "path": "a.json", "name": "a", "spec": "Prague", "index": 1, "status": "passed", "gasUsed": 30000 },
+                { "path": "a.json", "name": "a", "spec": "Cancun", "index": 0, "status": "passed", "gasUsed": 50100 },
+                { "path": "b.json", "name": "b", "spec": "Prague", "index": 0, "status": "failed" },
+                { "path": "d.json", "name": "d", "spec": "Prague", "index": 0, "status": "skipped" },
+                { "path": "e.json", "name": "e", "spec": null, "index": null, "status": "failed" }
+            ]"#,
+        );
+        let diff = RunDiff::from_records(before, after);
+
+        let ids = |changes: &[StatusChange]| {
+            changes.iter().map(|c| c.id.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&diff.newly_failing), ["a.json::a::Prague::0"]);
+        assert_eq!(ids(&diff.newly_passing), ["a.json::a::Prague::1"]);
+        assert_eq!(diff.newly_passing[0].before, Some(TestStatus::Failed));
+        // A new test is only reported if it fails
+        assert!(diff.newly_skipped.is_empty());
+        // Only compared if recorded by both runs
+        let gas: Vec<_> = diff
+            .gas_changed
+            .iter()
+            .map(|c| (c.id.as_str(), c.before, c.after))
+            .collect();
+        assert_eq!(gas, [("a.json::a::Cancun::0", 50000, 50100)]);
+        assert_eq!(diff.removed, 1);
+        assert!(!diff.is_clean());
+    }
+}
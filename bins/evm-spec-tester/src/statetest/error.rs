@@ -1,8 +1,17 @@
+use super::{
+    observer::{
+        CoinbaseAccessMismatch, CreateAddressMismatch, GasForwardingViolation,
+        PrecompileCalls,
+    },
+    report::OutcomeKind,
+};
 use cfx_bytes::Bytes;
 use cfx_executor::executive::ExecutionOutcome;
 use cfx_types::{H256, U256};
 use cfxkey::Address;
+use eest_types::{ExpectGasUsed, SpecName};
 use primitives::transaction::TransactionError;
+use std::{collections::BTreeMap, fmt};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -10,7 +19,44 @@ use thiserror::Error;
 pub struct TestError {
     pub name: String,
     pub path: String,
+    pub spec: Option<SpecName>,
+    /// Position of the post state among the ones of `spec`, if known.
+    pub index: Option<usize>,
     pub kind: TestErrorKind,
+    /// How the executor handled the transaction, if it got that far.
+    pub outcome: Option<OutcomeKind>,
+    /// How the repeats of the test went, if it was repeated.
+    pub repeats: Option<Repeats>,
+}
+
+/// The repeats of a failing test on a fresh state, see
+/// `UnitOptions::repeat_failures`.
+#[derive(Clone, Copy, Debug)]
+pub struct Repeats {
+    pub runs: usize,
+    /// Number of the repeats that failed too, whatever their error.
+    pub failed: usize,
+}
+
+impl Repeats {
+    /// Whether every repeat failed as well.
+    pub fn is_deterministic(&self) -> bool { self.failed == self.runs }
+}
+
+impl fmt::Display for Repeats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed {} of {} repeats ({})",
+            self.failed,
+            self.runs,
+            if self.is_deterministic() {
+                "deterministic"
+            } else {
+                "flaky"
+            }
+        )
+    }
 }
 
 #[allow(dead_code)]
@@ -18,30 +64,47 @@ pub struct TestError {
 pub enum TestErrorKind {
     #[error("state mismatch: {0}")]
     StateMismatch(#[from] StateMismatch),
-    #[error("consensus check fail: {0}")]
+    #[error("no exception expected, consensus check failed: {0}")]
     ConsensusCheckFail(#[from] TransactionError),
+    /// Checked by the tester, as the consensus checks have no such rule.
+    #[error("sender {addr:?} is not an EOA (EIP-3607)")]
+    SenderNotEoa { addr: Address },
     #[error("unknown private key: {0:?}")]
     UnknownPrivateKey(H256),
     #[error("execution error: {outcome:?}")]
     ExecutionError { outcome: ExecutionOutcome },
+    #[error("preceding transaction {index} failed: {error}")]
+    PrecedingTxFailed {
+        index: usize,
+        error: Box<TestErrorKind>,
+    },
     #[error("common check error: {tx_error:?}")]
     CommonCheckError { tx_error: TransactionError },
-    #[error("should fail but success: {fail_reason}")]
+    #[error(
+        "expected exception \"{fail_reason}\", but the transaction succeeded"
+    )]
     ShouldFail { fail_reason: String },
     #[error(
-        "inconsistent fail_reason (execution): expect: {fail_reason}, actual: {outcome:?}"
+        "inconsistent fail_reason (execution): expected \"{fail_reason}\", got {outcome:?}"
     )]
     InconsistentError {
         outcome: ExecutionOutcome,
         fail_reason: String,
     },
     #[error(
-        "inconsistent fail_reason (consensus): expect: {fail_reason}, actual: {error:?}"
+        "inconsistent fail_reason (consensus): expected \"{fail_reason}\", got {error:?}"
     )]
     InconsistentErrorConsensus {
         error: TransactionError,
         fail_reason: String,
     },
+    #[error(
+        "inconsistent fail_reason (tester): expected \"{fail_reason}\", got {error}"
+    )]
+    InconsistentErrorTester {
+        error: Box<TestErrorKind>,
+        fail_reason: String,
+    },
     #[error(
         "unexpected output: got {got_output:?}, expected {expected_output:?}"
     )]
@@ -49,16 +112,142 @@ pub enum TestErrorKind {
         expected_output: Option<Bytes>,
         got_output: Option<Bytes>,
     },
+    #[error(
+        "authority mismatch at authorization {index}: got {got:?}, expected {expected}"
+    )]
+    AuthorityMismatch {
+        index: usize,
+        expected: Address,
+        got: Option<Address>,
+    },
+    /// A MODEXP or ECPAIRING call charged another gas than the formula of
+    /// the fork.
+    #[error(
+        "gas mismatch of precompile {addr:?}: got {got}, expected {expected}"
+    )]
+    PrecompileGasMismatch {
+        addr: Address,
+        expected: U256,
+        got: U256,
+    },
+    /// The code of an EIP-7702 authority does not match the authorizations
+    /// replayed on the pre-state, `None` standing for no delegation.
+    #[error(
+        "delegation mismatch of authority {authority:?}: got {got:?}, expected {expected:?}"
+    )]
+    DelegationMismatch {
+        authority: Address,
+        expected: Option<Address>,
+        got: Option<Address>,
+    },
+    /// An account committed by the executor, which the fixture does not
+    /// expect to change.
+    #[error(
+        "spurious touch of {addr:?}: committed by the transaction, but not changed by the fixture"
+    )]
+    SpuriousTouch { addr: Address },
+    /// The balances of the Ethereum space accounts changed by more or less
+    /// than the withdrawals and the burnt fees account for.
+    #[error("value not conserved: {delta}")]
+    ValueNotConserved { delta: ValueDelta },
+    #[error(
+        "inconsistent fail_reason (blob tx): expect: {fail_reason:?}, actual: {got}"
+    )]
+    InconsistentBlobTxError {
+        got: &'static str,
+        fail_reason: Option<String>,
+    },
     #[error(transparent)]
     SerdeDeserialize(#[from] serde_json::Error),
     #[error("thread panicked")]
     Panic,
+    #[error("timed out after {timeout_ms} ms")]
+    Timeout { timeout_ms: u64 },
+    /// Not run, as too many workers of timed out tests are left running.
+    #[error("not run: {running} timed out tests are still running")]
+    TooManyTimedOut { running: usize },
     #[error("path does not exist")]
     InvalidPath,
     #[error("no JSON test files found in path")]
     NoJsonFiles,
+    #[error(
+        "chain id mismatch: txbytes are signed for {txbytes}, the config \
+         chain id is {config}"
+    )]
+    ChainIdMismatch { txbytes: u64, config: u64 },
+    #[error("txbytes mismatch: {0}")]
+    TxBytesMismatch(TxBytesDiff),
     #[error("internal error: {0}")]
     Internal(String),
+    #[error("db error: {0}")]
+    DbError(String),
+    #[error("bad index: {0}")]
+    BadIndex(String),
+    #[error("missing expected exception: {0}")]
+    MissingExpectException(String),
+    #[error("unexpected pass, listed as failing: {reason}")]
+    UnexpectedPass { reason: String },
+    #[error("gas forwarding violation: {0}")]
+    GasForwardingViolation(GasForwardingViolation),
+    #[error(
+        "access list initialization mismatch: {missing:?} not warm at the \
+         start of the execution"
+    )]
+    AccessListInitMismatch { missing: Vec<Address> },
+    #[error("coinbase access cost mismatch: {0}")]
+    CoinbaseAccessMismatch(CoinbaseAccessMismatch),
+    #[error("contract address mismatch: {0}")]
+    CreateAddressMismatch(CreateAddressMismatch),
+    #[error(
+        "selfdestruct mismatch: {contract:?} deleted: {deleted}, expected: \
+         {expected}, created in the transaction: {created_in_tx}"
+    )]
+    SelfdestructMismatch {
+        contract: Address,
+        created_in_tx: bool,
+        deleted: bool,
+        expected: bool,
+    },
+    #[error(
+        "code hash mismatch of {addr:?}: expected {expected:?} for \
+         {expected_len} bytes of code, got {got:?} for {got_len}"
+    )]
+    CodeMismatch {
+        addr: Address,
+        expected: H256,
+        got: H256,
+        expected_len: usize,
+        got_len: usize,
+    },
+    #[error(
+        "nonce mismatch of {addr:?}: expected {expected} from its \
+         creations, got {got}"
+    )]
+    NonceMismatch {
+        addr: Address,
+        expected: U256,
+        got: U256,
+    },
+    #[error("revert rollback incomplete: {0}")]
+    RevertRollbackIncomplete(RollbackLeak),
+    #[error("transient storage leak: {0}")]
+    TransientStorageLeak(TransientLeak),
+    #[error(
+        "nondeterministic execution: first run {first}, second run {second}"
+    )]
+    Nondeterministic { first: String, second: String },
+    #[error("post check {name} failed: {message}")]
+    PostCheckFailed { name: String, message: String },
+    #[error(
+        "reference mismatch: state root {got_root:?}, reference {ref_root:?}; \
+         gas used {got_gas}, reference {ref_gas:?}"
+    )]
+    RefMismatch {
+        got_root: H256,
+        ref_root: H256,
+        got_gas: U256,
+        ref_gas: Option<U256>,
+    },
 }
 
 #[allow(dead_code)]
@@ -68,30 +257,303 @@ pub enum StateMismatch {
     LogsRootMismatch { got: H256, expected: H256 },
     #[error("state root mismatch: got {got}, expected {expected}")]
     StateRootMismatch { got: H256, expected: H256 },
+    #[error("post state mismatch:\n{0}")]
+    PostState(StateDiff),
     #[error(
-        "balance mismatch: address {address}, got {got}, expected {expected}"
+        "gas used mismatch: got {got}, {side} the expected {expected} \
+         (gross {gross}, refund counter {refund_counter}, refund applied {refund_applied})\n\
+         {precompiles}"
     )]
-    BalanceMismatch {
-        address: Address,
+    GasUsedMismatch {
         got: U256,
-        expected: U256,
+        expected: ExpectGasUsed,
+        side: GasSide,
+        gross: U256,
+        refund_counter: U256,
+        refund_applied: U256,
+        precompiles: PrecompileCalls,
     },
-    #[error("gas mismatch: got {got}, expected {expected}")]
-    GasMismatch { got: U256, expected: U256 },
-    #[error(
-        "nonce mismatch: address {address}, got {got}, expected {expected}"
-    )]
-    NonceMismatch {
-        address: Address,
+}
+
+/// What a reverted transaction left behind.
+#[derive(Debug)]
+pub enum RollbackLeak {
+    /// A slot written by the transaction does not hold its value from
+    /// before it.
+    Storage {
+        contract: Address,
+        key: U256,
         got: U256,
         expected: U256,
     },
-    #[error("code mismatch: got {got}, expected {expected}")]
-    CodeMismatch { got: String, expected: String },
-    #[error("storage mismatch (key {key}): got {got}, expected {expected}")]
-    StorageMismatch {
+    /// The refund of the reverted work was kept.
+    Refund { counter: U256, allowed: U256 },
+}
+
+/// Transient storage visible where EIP-1153 says it is gone.
+#[derive(Debug)]
+pub enum TransientLeak {
+    /// A TLOAD read a value other than the one of the TSTOREs whose frames
+    /// were not reverted.
+    StaleLoad {
+        contract: Address,
         key: U256,
         got: U256,
         expected: U256,
     },
+    /// A slot written by the transaction is still set once it is committed.
+    Committed {
+        contract: Address,
+        key: U256,
+        value: U256,
+    },
+}
+
+impl fmt::Display for TransientLeak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransientLeak::StaleLoad {
+                contract,
+                key,
+                got,
+                expected,
+            } => write!(
+                f,
+                "TLOAD of slot {:#x} of {:?} got {:#x}, expected {:#x}",
+                key, contract, got, expected
+            ),
+            TransientLeak::Committed {
+                contract,
+                key,
+                value,
+            } => write!(
+                f,
+                "slot {:#x} of {:?} still holds {:#x} after the transaction",
+                key, contract, value
+            ),
+        }
+    }
+}
+
+impl fmt::Display for RollbackLeak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RollbackLeak::Storage {
+                contract,
+                key,
+                got,
+                expected,
+            } => write!(
+                f,
+                "storage {:?}[{:#x}] is {:#x}, expected {:#x} from before the \
+                 transaction",
+                contract, key, got, expected
+            ),
+            RollbackLeak::Refund { counter, allowed } => write!(
+                f,
+                "refund counter {} above the {} of the authorizations",
+                counter, allowed
+            ),
+        }
+    }
+}
+
+/// Whether the gas used is under or over the expected one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GasSide {
+    Under,
+    Over,
+}
+
+impl fmt::Display for GasSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GasSide::Under => write!(f, "under"),
+            GasSide::Over => write!(f, "over"),
+        }
+    }
+}
+
+/// The value a test created or destroyed, see `--check-conservation`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueDelta {
+    Created(U256),
+    Destroyed(U256),
+}
+
+impl fmt::Display for ValueDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueDelta::Created(value) => write!(f, "{} wei created", value),
+            ValueDelta::Destroyed(value) => {
+                write!(f, "{} wei destroyed", value)
+            }
+        }
+    }
+}
+
+/// The first difference between the `txbytes` of the fixture and the
+/// encoding of the transaction built from its parts.
+#[derive(Debug)]
+pub struct TxBytesDiff {
+    /// The differing field, or why the encodings could not be decoded.
+    pub field: String,
+    pub expected: Bytes,
+    pub got: Bytes,
+}
+
+impl fmt::Display for TxBytesDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.field)?;
+        writeln!(f, "  expected: 0x{}", hex::encode(&self.expected))?;
+        write!(f, "  got:      0x{}", hex::encode(&self.got))
+    }
+}
+
+/// Every divergence of the post state from the fixture, by account.
+#[derive(Debug, Default)]
+pub struct StateDiff {
+    pub accounts: BTreeMap<Address, AccountDiff>,
+    /// Gas used by the transaction, if it explains a sender balance mismatch.
+    pub gas_used: Option<ValueDiff<U256>>,
+    /// Printed along a gas used mismatch.
+    pub precompiles: PrecompileCalls,
+}
+
+#[derive(Debug)]
+pub enum AccountDiff {
+    /// Expected in the post state but does not exist.
+    Missing,
+    /// Exists but is not in the post state.
+    Extra,
+    Fields(AccountFields),
+}
+
+#[derive(Debug, Default)]
+pub struct AccountFields {
+    pub balance: Option<ValueDiff<U256>>,
+    pub nonce: Option<ValueDiff<U256>>,
+    pub code: Option<ValueDiff<Bytes>>,
+    pub storage: BTreeMap<U256, ValueDiff<U256>>,
+}
+
+#[derive(Debug)]
+pub struct ValueDiff<T> {
+    pub expected: T,
+    pub got: T,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.gas_used.is_none()
+    }
+}
+
+impl AccountFields {
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.nonce.is_none()
+            && self.code.is_none()
+            && self.storage.is_empty()
+    }
+
+    fn rows(&self) -> Vec<[String; 3]> {
+        let mut rows = vec![];
+        if let Some(d) = &self.balance {
+            rows.push([
+                "balance".into(),
+                d.expected.to_string(),
+                d.got.to_string(),
+            ]);
+        }
+        if let Some(d) = &self.nonce {
+            rows.push([
+                "nonce".into(),
+                d.expected.to_string(),
+                d.got.to_string(),
+            ]);
+        }
+        if let Some(d) = &self.code {
+            rows.push([
+                "code".into(),
+                short_hex(&d.expected),
+                short_hex(&d.got),
+            ]);
+            if d.expected.len() != d.got.len() {
+                rows.push([
+                    "code size".into(),
+                    d.expected.len().to_string(),
+                    d.got.len().to_string(),
+                ]);
+            }
+        }
+        for (key, d) in &self.storage {
+            rows.push([
+                format!("storage[{:#x}]", key),
+                format!("{:#x}", d.expected),
+                format!("{:#x}", d.got),
+            ]);
+        }
+        rows
+    }
+}
+
+/// Cuts long code, its length still tells it apart.
+fn short_hex(code: &[u8]) -> String {
+    const MAX_BYTES: usize = 16;
+    if code.len() <= MAX_BYTES {
+        format!("0x{}", hex::encode(code))
+    } else {
+        format!(
+            "0x{}.. ({} bytes)",
+            hex::encode(&code[..MAX_BYTES]),
+            code.len()
+        )
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(d) = &self.gas_used {
+            writeln!(f, "gas used: expected {}, got {}", d.expected, d.got)?;
+            write!(f, "{}", self.precompiles)?;
+        }
+        for (address, diff) in &self.accounts {
+            let fields = match diff {
+                AccountDiff::Missing => {
+                    writeln!(f, "{:?}: missing", address)?;
+                    continue;
+                }
+                AccountDiff::Extra => {
+                    writeln!(f, "{:?}: not expected", address)?;
+                    continue;
+                }
+                AccountDiff::Fields(fields) => fields,
+            };
+
+            writeln!(f, "{:?}:", address)?;
+            let mut rows =
+                vec![[String::new(), "expected".into(), "got".into()]];
+            rows.extend(fields.rows());
+            let mut widths = [0; 3];
+            for row in &rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+            for [name, expected, got] in rows {
+                writeln!(
+                    f,
+                    "  {:<w0$}  {:>w1$}  {:>w2$}",
+                    name,
+                    expected,
+                    got,
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2],
+                )?;
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,155 @@
+use super::{report::TestStatus, unit_tester::TestId, utils::path_has_suffix};
+use eest_types::SpecName;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// The fields of a `TestRecord` read back from a report, to tell a failed
+/// test or compare two runs.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RecordEntry {
+    pub(super) path: String,
+    pub(super) name: String,
+    pub(super) spec: Option<SpecName>,
+    #[serde(default)]
+    pub(super) index: Option<usize>,
+    pub(super) status: TestStatus,
+    #[serde(default)]
+    pub(super) gas_used: Option<u64>,
+}
+
+impl RecordEntry {
+    /// The identifier of the post state of the record, `None` for the
+    /// record of a whole unit.
+    pub(super) fn id(&self) -> Option<TestId> {
+        Some(TestId {
+            path: self.path.clone(),
+            name: self.name.clone(),
+            spec: self.spec?,
+            index: self.index?,
+        })
+    }
+}
+
+/// The records of the JSON report at `path`.
+pub(super) fn read_records(path: &Path) -> Result<Vec<RecordEntry>, String> {
+    let s = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match serde_json::from_str(&s).map_err(|e| e.to_string())? {
+        ReportFile::Report { records } | ReportFile::Records(records) => {
+            Ok(records)
+        }
+    }
+}
+
+/// A report, or only its records as written before the seed was added.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReportFile {
+    Report { records: Vec<RecordEntry> },
+    Records(Vec<RecordEntry>),
+}
+
+/// Tests failed in a previous run, read from its JSON report. The failures
+/// include the unexpected passes, which also fail a run. The path is matched
+/// as a suffix of the fixture path, as the one of `--test`.
+#[derive(Debug, Default)]
+pub struct FailingList {
+    /// Name of the unit to its failed tests, ordered so that they are
+    /// warned about in the same order on every run.
+    tests: BTreeMap<String, Vec<TestId>>,
+}
+
+impl FailingList {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        read_records(path).map(Self::from_records)
+    }
+
+    fn from_records(records: impl IntoIterator<Item = RecordEntry>) -> Self {
+        let mut list = FailingList::default();
+        for record in records {
+            if !matches!(record.status, TestStatus::Failed | TestStatus::Xpass)
+            {
+                continue;
+            }
+            let Some(id) = record.id() else {
+                continue;
+            };
+            list.tests.entry(id.name.clone()).or_default().push(id);
+        }
+        list
+    }
+
+    pub fn is_empty(&self) -> bool { self.tests.is_empty() }
+
+    /// Every failed test.
+    pub fn ids(&self) -> impl Iterator<Item = &TestId> {
+        self.tests.values().flatten()
+    }
+
+    /// Whether a test of the fixture `path` failed.
+    pub fn has_path(&self, path: &str) -> bool {
+        self.ids().any(|id| id.matches_path(path))
+    }
+
+    /// Whether a test of the unit `name` of the fixture `path` failed.
+    pub fn has_unit(&self, path: &str, name: &str) -> bool {
+        self.tests.get(name).map_or(false, |ids| {
+            ids.iter().any(|id| path_has_suffix(path, &id.path))
+        })
+    }
+
+    pub fn contains(&self, test: &TestId) -> bool {
+        self.tests
+            .get(&test.name)
+            .map_or(false, |ids| ids.iter().any(|id| id.matches(test)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FailingList, RecordEntry};
+    use crate::statetest::unit_tester::TestId;
+    use eest_types::SpecName;
+
+    #[test]
+    fn only_failed_post_states_are_kept() {
+        let records: Vec<RecordEntry> = serde_json::from_str(
+            r#"[
+                { "path": "fixtures/a.json", "name": "a", "spec": "Prague", "index": 1, "status": "failed" },
+                { "path": "fixtures/a.json", "name": "a", "spec": "Prague", "index": 0, "status": "passed" },
+                { "path": "fixtures/b.json", "name": "b", "spec": "Cancun", "index": 0, "status": "xpass" },
+                { "path": "fixtures/c.json", "name": "c", "spec": null, "index": null, "status": "failed" }
+            ]"#,
+        )
+        .unwrap();
+        let list = FailingList::from_records(records);
+
+        let id = |path: &str, name: &str, spec, index| TestId {
+            path: path.to_string(),
+            name: name.to_string(),
+            spec,
+            index,
+        };
+        assert!(list.contains(&id(
+            "/data/fixtures/a.json",
+            "a",
+            SpecName::Prague,
+            1
+        )));
+        assert!(!list.contains(&id(
+            "/data/fixtures/a.json",
+            "a",
+            SpecName::Prague,
+            0
+        )));
+        assert!(list.contains(&id(
+            "/data/fixtures/b.json",
+            "b",
+            SpecName::Cancun,
+            0
+        )));
+        assert!(list.has_unit("/data/fixtures/a.json", "a"));
+        assert!(!list.has_unit("/data/fixtures/c.json", "c"));
+        assert!(!list.has_path("/data/fixtures/c.json"));
+    }
+}
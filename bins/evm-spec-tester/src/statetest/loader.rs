@@ -0,0 +1,343 @@
+use super::{
+    unit_tester::{BlessedPost, UnitTester},
+    utils::skip_test,
+};
+use eest_types::{SpecName, StateTestSuite};
+use flate2::read::GzDecoder;
+use rayon::{prelude::*, ThreadPool};
+use regex::Regex;
+use serde_json::{json, ser::PrettyFormatter, Map, Serializer, Value};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+use walkdir::WalkDir;
+
+/// A fixture file, or a directory entry, that could not be loaded.
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Include and exclude globs on the fixture paths, relative to the path
+/// given on the command line.
+pub struct PathFilter<'a> {
+    pub include: &'a [Regex],
+    pub exclude: &'a [Regex],
+}
+
+impl PathFilter<'_> {
+    fn allows(&self, root: &Path, path: &Path) -> bool {
+        let relative = match path.strip_prefix(root) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel,
+            _ => path.file_name().map_or(path, Path::new),
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        (self.include.is_empty()
+            || self.include.iter().any(|re| re.is_match(&relative)))
+            && !self.exclude.iter().any(|re| re.is_match(&relative))
+    }
+}
+
+/// Translates a glob into a regex matching the whole path. `*` and `?` do
+/// not cross directories, `**` matches any number of them.
+pub fn glob_to_regex(glob: &str) -> Result<Regex, String> {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| format!("Invalid glob {}: {}", glob, e))
+}
+
+/// The fixture files found under a path.
+#[derive(Default)]
+pub struct FixtureFiles {
+    pub files: Vec<PathBuf>,
+    /// Files skipped by `skip_test`.
+    pub skipped: usize,
+    pub errors: Vec<LoadError>,
+}
+
+/// Compression of a fixture file, told by its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// `None` if the file is not a fixture.
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".json") {
+            Some(Compression::None)
+        } else if name.ends_with(".json.gz") {
+            Some(Compression::Gzip)
+        } else if name.ends_with(".json.zst") {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Recursively collects the `*.json` files, plain or compressed as
+/// `*.json.gz` and `*.json.zst`, under `root`, sorted by file name.
+/// Symbolic links are followed, a link loop is reported as an error instead
+/// of being walked again.
+pub fn find_fixtures(root: &Path, filter: &PathFilter) -> FixtureFiles {
+    let mut fixtures = FixtureFiles::default();
+    for entry in WalkDir::new(root).follow_links(true).sort_by_file_name() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                fixtures.errors.push(LoadError {
+                    path: e.path().unwrap_or(root).to_path_buf(),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        if !entry.file_type().is_file()
+            || Compression::of(path).is_none()
+            || !filter.allows(root, path)
+        {
+            continue;
+        }
+
+        if skip_test(path) {
+            fixtures.skipped += 1;
+        } else {
+            fixtures.files.push(entry.into_path());
+        }
+    }
+    fixtures
+}
+
+/// Parses the fixture files on `pool`. The units keep the order of `files`,
+/// a file that fails to parse is reported instead.
+pub fn load_units(
+    files: &[PathBuf], pool: &ThreadPool,
+) -> (Vec<UnitTester>, Vec<LoadError>) {
+    let suites: Vec<Result<SuiteTester, String>> =
+        pool.install(|| files.par_iter().map(SuiteTester::load).collect());
+
+    let mut units = vec![];
+    let mut errors = vec![];
+    for (path, suite) in files.iter().zip(suites) {
+        match suite {
+            Ok(tester) => units.extend(tester.into_units()),
+            Err(error) => {
+                warn!(
+                    "TestSuite load failed. path: {:?}, error: {}",
+                    path, error
+                );
+                errors.push(LoadError {
+                    path: path.clone(),
+                    error,
+                });
+            }
+        }
+    }
+    (units, errors)
+}
+
+/// The path of the units read by `load_stdin`.
+pub const STDIN_PATH: &str = "<stdin>";
+
+/// Reads a fixture from stdin, either a suite of named units or a single
+/// unit, which is named `stdin`. The units have `STDIN_PATH` as path.
+pub fn load_stdin() -> Result<Vec<UnitTester>, String> {
+    let mut s = String::new();
+    io::stdin()
+        .read_to_string(&mut s)
+        .map_err(|e| e.to_string())?;
+
+    let path = STDIN_PATH.to_string();
+    let value: Value = serde_json::from_str(&s).map_err(|e| e.to_string())?;
+    // The units of a suite are under their names
+    if value.get("env").is_some() {
+        let unit = serde_json::from_str(&s).map_err(|e| e.to_string())?;
+        return Ok(vec![UnitTester::new(&path, "stdin".into(), unit)]);
+    }
+    let suite = serde_json::from_str(&s).map_err(|e| e.to_string())?;
+    Ok(SuiteTester { path, suite }.into_units().collect())
+}
+
+struct SuiteTester {
+    path: String,
+    suite: StateTestSuite,
+}
+
+impl SuiteTester {
+    fn load(path: &PathBuf) -> Result<Self, String> {
+        let s = read_fixture(path)?;
+        let suite: StateTestSuite =
+            serde_json::from_str(&s).map_err(|e| e.to_string())?;
+
+        let path = path.to_string_lossy().into_owned();
+        Ok(Self { path, suite })
+    }
+
+    fn into_units(self) -> impl Iterator<Item = UnitTester> {
+        debug!("Loaded TestSuite: {}", self.path);
+        let path = self.path;
+        self.suite
+            .0
+            .into_iter()
+            .map(move |(name, unit)| UnitTester::new(&path, name, unit))
+    }
+}
+
+/// Reads a fixture file, decompressing it according to its extension.
+fn read_fixture(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut s = String::new();
+    let compression = Compression::of(path);
+    match compression {
+        Some(Compression::Gzip) => GzDecoder::new(file).read_to_string(&mut s),
+        Some(Compression::Zstd) => zstd::Decoder::new(file)
+            .and_then(|mut decoder| decoder.read_to_string(&mut s)),
+        _ => file.read_to_string(&mut s),
+    }
+    .map_err(|e| match compression {
+        Some(Compression::Gzip | Compression::Zstd) => {
+            format!("failed to decompress: {}", e)
+        }
+        _ => e.to_string(),
+    })?;
+    Ok(s)
+}
+
+/// The JSON of the units of the fixture files, indexed by their names. A
+/// file is read and parsed once, by the first of its units asked for, and
+/// kept for the rest of the run, whose units may be run again by `--repeat`.
+#[derive(Debug, Default)]
+pub struct UnitIndex {
+    files: Mutex<
+        HashMap<PathBuf, Arc<OnceLock<Result<Map<String, Value>, String>>>>,
+    >,
+}
+
+impl UnitIndex {
+    /// The JSON of the unit `name` in the fixture at `path`.
+    pub fn unit(&self, path: &Path, name: &str) -> Result<Value, String> {
+        // Parsed out of the lock, which the units of the other files take
+        let file = self
+            .files
+            .lock()
+            .expect("unit index poisoned")
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone();
+        let units = file
+            .get_or_init(|| {
+                let s = read_fixture(path)?;
+                serde_json::from_str(&s).map_err(|e| e.to_string())
+            })
+            .as_ref()
+            .map_err(Clone::clone)?;
+        units
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no unit {} in {}", name, path.display()))
+    }
+}
+
+/// Writes the fixture at `path` to `output`, which may be `path` itself, with
+/// its post states replaced by `posts`. The other fields of the fixture are
+/// kept, but not their order.
+pub fn bless_fixture(
+    path: &Path, output: &Path, posts: &[BlessedPost],
+) -> Result<(), String> {
+    if Compression::of(path) != Some(Compression::None) {
+        return Err("can not bless a compressed fixture".into());
+    }
+    let s = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut suite: Value =
+        serde_json::from_str(&s).map_err(|e| e.to_string())?;
+
+    for post in posts {
+        let test = suite
+            .get_mut(&post.name)
+            .and_then(|unit| unit.get_mut("post"))
+            .and_then(Value::as_object_mut)
+            .and_then(|specs| {
+                specs.iter_mut().find(|(key, _)| {
+                    serde_json::from_value::<SpecName>(Value::String(
+                        key.to_string(),
+                    ))
+                    .map_or(false, |spec| spec == post.spec)
+                })
+            })
+            .and_then(|(_, tests)| tests.get_mut(post.index))
+            .ok_or_else(|| {
+                format!(
+                    "no post state {:?} #{} in {}",
+                    post.spec, post.index, post.name
+                )
+            })?;
+        test["state"] = post.state.state.clone();
+        test["hash"] = json!(post.state.hash);
+        test["logs"] = json!(post.state.logs);
+    }
+
+    let mut out = Vec::new();
+    let mut serializer = Serializer::with_formatter(
+        &mut out,
+        PrettyFormatter::with_indent(b"    "),
+    );
+    serde::Serialize::serialize(&suite, &mut serializer)
+        .map_err(|e| e.to_string())?;
+    out.push(b'\n');
+    File::create(output)
+        .and_then(|mut file| file.write_all(&out))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnitIndex;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn units_are_read_once_per_file() {
+        let path = std::env::temp_dir()
+            .join(format!("evm-spec-tester-units-{}.json", std::process::id()));
+        let suite = json!({ "a": { "env": 1 }, "b": { "env": 2 } });
+        fs::write(&path, suite.to_string()).unwrap();
+
+        let index = UnitIndex::default();
+        assert_eq!(index.unit(&path, "a").unwrap(), json!({ "env": 1 }));
+        // Served from the index once the file is gone, and again
+        fs::remove_file(&path).unwrap();
+        assert_eq!(index.unit(&path, "b").unwrap(), json!({ "env": 2 }));
+        assert_eq!(index.unit(&path, "a").unwrap(), json!({ "env": 1 }));
+        assert!(index.unit(&path, "c").unwrap_err().starts_with("no unit c"));
+        assert!(index.unit(&path.with_extension("gz"), "a").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Per-test allocation counters. The counting allocator is only installed
+//! with the `profile-mem` feature, without it `measure` just runs the closure.
+
+use serde::Serialize;
+
+/// Allocations made by the current thread while running a test.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    /// Peak of the bytes allocated and not yet freed.
+    pub peak_bytes: u64,
+    /// Number of allocations, reallocations included.
+    pub allocations: u64,
+}
+
+/// Runs `f`, returning the allocations it made on this thread if the
+/// counting allocator is installed.
+#[cfg(feature = "profile-mem")]
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Option<MemoryStats>) {
+    counting::reset();
+    let res = f();
+    (res, Some(counting::stats()))
+}
+
+#[cfg(not(feature = "profile-mem"))]
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Option<MemoryStats>) {
+    (f(), None)
+}
+
+#[cfg(feature = "profile-mem")]
+mod counting {
+    use super::MemoryStats;
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    // Const initialized and without destructor, so they can be used from
+    // the allocator without allocating.
+    thread_local! {
+        static CURRENT: Cell<i64> = const { Cell::new(0) };
+        static PEAK: Cell<i64> = const { Cell::new(0) };
+        static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    pub fn reset() {
+        CURRENT.with(|c| c.set(0));
+        PEAK.with(|p| p.set(0));
+        ALLOCATIONS.with(|a| a.set(0));
+    }
+
+    pub fn stats() -> MemoryStats {
+        MemoryStats {
+            peak_bytes: PEAK.with(Cell::get) as u64,
+            allocations: ALLOCATIONS.with(Cell::get),
+        }
+    }
+
+    fn on_alloc(size: usize) {
+        let _ = CURRENT.try_with(|current| {
+            let now = current.get() + size as i64;
+            current.set(now);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+        });
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+    }
+
+    // Memory allocated before the reset, or on another thread, may be freed
+    // here, so the current bytes can go negative.
+    fn on_dealloc(size: usize) {
+        let _ = CURRENT.try_with(|c| c.set(c.get() - size as i64));
+    }
+
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                on_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                on_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            on_dealloc(layout.size());
+        }
+
+        unsafe fn realloc(
+            &self, ptr: *mut u8, layout: Layout, new_size: usize,
+        ) -> *mut u8 {
+            let new_ptr = System.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                on_dealloc(layout.size());
+                on_alloc(new_size);
+            }
+            new_ptr
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+}
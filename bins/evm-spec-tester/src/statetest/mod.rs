@@ -1,40 +1,286 @@
-pub(crate) mod command;
+mod bench;
+mod command;
+mod compare;
 mod error;
+mod failing;
+mod loader;
+mod memory;
+mod observer;
+mod output_dir;
+mod perf;
+mod post_check;
+mod progress;
+mod reference;
+mod report;
+mod runner;
+mod shard;
+mod span;
+mod triage;
 mod unit_tester;
 mod utils;
+mod xfail;
 
-pub use error::TestError;
+use self::utils::{closest, random_seed, shell_quote};
+pub use command::StateTestCmd;
+pub use compare::{GasChange, RunDiff, StatusChange};
+pub use error::{Repeats, TestError, TestErrorKind};
+pub use failing::FailingList;
+pub use loader::{
+    bless_fixture, find_fixtures, glob_to_regex, load_stdin, load_units,
+    FixtureFiles, LoadError, PathFilter, STDIN_PATH,
+};
+pub use memory::MemoryStats;
+pub use observer::{OpcodeCoverage, Sinks, TraceSink};
+pub use output_dir::{Artifact, OutputDir};
+pub use perf::PerfTotals;
+pub use post_check::{PostCheck, PostChecks};
+pub use progress::{progress_shown, Progress, CLEAR_LINE};
+pub use reference::RefEvm;
+pub use report::{
+    print_metrics, print_summary, FixtureInfo, ReportTarget, TestRecord,
+    TestStatus,
+};
+pub use runner::{run_units, RunReport, TestBudget};
+pub use shard::Shard;
+pub use span::{current_test, TestSpan};
+pub use triage::Triage;
+pub use unit_tester::{
+    transact_dry, BlessedPost, BlessedState, BlockOverrides, Checks,
+    ForkMachines, ForkRange, FuzzFailure, FuzzOptions, Mutation, SkipReason,
+    SpecTally, StateSurface, TestId, UnitOptions, UnitResult, UnitStatus,
+    UnitTester, LATEST_SUPPORTED_SPEC,
+};
+pub use xfail::XfailList;
 
 use cfx_executor::machine::{Machine, VmFactory};
+use cfx_types::{Space, U256};
 use cfxcore::verification::VerificationConfig;
-use eest_types::StateTestSuite;
 use itertools::Itertools;
-use std::{path::PathBuf, sync::Arc};
-
-use command::StateTestCmd;
-use unit_tester::UnitTester;
-use utils::{find_all_json_tests, skip_test};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io, panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 impl StateTestCmd {
     /// Runs `statetest` command.
     pub fn run(&self) -> bool {
+        if !self.compare_runs.is_empty() {
+            return self.compare_runs();
+        }
+
+        let start = Instant::now();
         let mut success = true;
+
+        let matches = match self.matches_regex() {
+            Ok(matches) => matches,
+            Err(e) => {
+                error!("Invalid matches pattern: {}", e);
+                return false;
+            }
+        };
+
+        if self.bless && self.space != Space::Ethereum {
+            error!("--bless only supports the Ethereum space");
+            return false;
+        }
+
+        if self.repeat == Some(0) {
+            error!("--repeat must be at least 1");
+            return false;
+        }
+
+        if self.perf && !perf::SUPPORTED {
+            error!("--perf requires building with the perf feature");
+            return false;
+        }
+
+        if self.diff_ref.is_some() && self.space != Space::Ethereum {
+            error!("--diff-ref only supports the Ethereum space");
+            return false;
+        }
+
+        let output_dir = match &self.output_dir {
+            Some(dir) => match OutputDir::create(dir) {
+                Ok(output) => Some(Arc::new(output)),
+                Err(e) => {
+                    error!(
+                        "Failed to create output directory {:?}: {}",
+                        dir, e
+                    );
+                    return false;
+                }
+            },
+            None => None,
+        };
+
+        let state_dumps = match (self.dump_state, &self.dump_state_dir) {
+            (false, _) => None,
+            _ if self.space != Space::Ethereum => {
+                error!("--dump-state only supports the Ethereum space");
+                return false;
+            }
+            (true, None) => {
+                match &output_dir {
+                    Some(output) => Some(output.clone()),
+                    None => {
+                        error!("--dump-state needs --dump-state-dir or --output-dir");
+                        return false;
+                    }
+                }
+            }
+            (true, Some(dir)) => match OutputDir::flat(dir) {
+                Ok(output) => Some(Arc::new(output)),
+                Err(e) => {
+                    error!("Failed to create dump directory {:?}: {}", dir, e);
+                    return false;
+                }
+            },
+        };
+
+        let trace_sink = match (self.trace, &self.trace_output, &output_dir) {
+            (false, ..) => None,
+            (true, None, None) => Some(Arc::new(TraceSink::stdout())),
+            (true, None, Some(output)) => {
+                Some(Arc::new(TraceSink::dir(output.clone())))
+            }
+            (true, Some(trace_path), _) => match TraceSink::file(trace_path) {
+                Ok(sink) => Some(Arc::new(sink)),
+                Err(e) => {
+                    error!(
+                        "Failed to create trace file {:?}: {}",
+                        trace_path, e
+                    );
+                    return false;
+                }
+            },
+        };
+
+        let sinks = Sinks {
+            trace: trace_sink,
+            coverage: self
+                .coverage
+                .is_some()
+                .then(|| Arc::new(OpcodeCoverage::default())),
+            perf: self.perf.then(|| Arc::new(PerfTotals::default())),
+            state_dumps,
+            blessed: output_dir.filter(|_| self.bless),
+        };
+
+        let pool = match ThreadPoolBuilder::new().num_threads(self.jobs).build()
+        {
+            Ok(pool) => pool,
+            Err(e) => {
+                error!("Failed to build thread pool: {}", e);
+                return false;
+            }
+        };
+
+        let filter = PathFilter {
+            include: &self.include,
+            exclude: &self.exclude,
+        };
+
+        let seed = self.seed.unwrap_or_else(random_seed);
+        info!("Seed {}, pass --seed {} to reproduce the run", seed, seed);
+
+        // Warned, as the results can no longer be compared with the fixtures
+        if let Some(number) = self.block_number {
+            warn!("Overriding the block number of every test with {}", number);
+        }
+        if let Some(timestamp) = self.timestamp {
+            warn!("Overriding the timestamp of every test with {}", timestamp);
+        }
+
+        if let Some(id) = &self.test {
+            if let Err(e) = self.check_test_id(id, &filter, &pool) {
+                error!("{}", e);
+                return false;
+            }
+        }
+
+        if let Some(list) = &self.only_failing_from {
+            self.warn_missing_failures(list, &filter, &pool);
+        }
+
+        if self.list {
+            return self.list_tests(&filter, &pool, matches.as_ref());
+        }
+
+        // The machine is only read by the tests, so one is shared by every
+        // path and every thread of the pool
+        let machine = self.make_machine();
+        let verification =
+            self.config.parsed.verification_config(machine.clone());
+        let fork_machines = ForkMachines::new(machine.clone());
+        let engine = Engine {
+            machine: &machine,
+            verification: &verification,
+            fork_machines: &fork_machines,
+        };
+
+        if let Some(iterations) = self.fuzz {
+            let fuzz = FuzzOptions { iterations, seed };
+            return self.fuzz_tests(
+                &filter,
+                &pool,
+                matches.as_ref(),
+                &sinks,
+                engine,
+                &fuzz,
+            );
+        }
+
+        let mut records = vec![];
+
+        if self.stdin
+            && !self.run_stdin_tests(
+                &pool,
+                matches.as_ref(),
+                &sinks,
+                engine,
+                &mut records,
+            )
+        {
+            success = false;
+        }
+
         for path in &self.paths {
             if !path.exists() {
                 panic!("Path not exists: {:?}", path);
             }
 
-            let test_files = find_all_json_tests(path);
+            let fixtures = self.find_fixtures(path, &filter);
+            let pinned =
+                self.test.is_some() || self.only_failing_from.is_some();
+            if pinned && fixtures.files.is_empty() {
+                continue;
+            }
 
-            if test_files.is_empty() {
+            if fixtures.files.is_empty() && fixtures.errors.is_empty() {
                 error!("No fixtures found in directory: {:?}", path);
                 continue;
             }
 
-            match self.run_file_tests(test_files, path) {
+            match self.run_file_tests(
+                fixtures,
+                path,
+                &pool,
+                matches.as_ref(),
+                &sinks,
+                engine,
+                &mut records,
+            ) {
                 Ok(true) => {}
                 Ok(false) => {
                     success = false;
+                    if !self.keep_going {
+                        break;
+                    }
                 }
                 Err(_) => {
                     success = false;
@@ -43,135 +289,677 @@ impl StateTestCmd {
                 }
             }
         }
+
+        if let (Some(shard), true) = (self.shard, records.is_empty()) {
+            info!("No tests in shard {}", shard);
+        }
+
+        for report in &self.reports {
+            if let Err(e) = report.write(seed, &records) {
+                error!("Failed to write report {:?}: {}", report, e);
+                success = false;
+            }
+        }
+
+        if let (Some(path), Some(coverage)) = (&self.coverage, &sinks.coverage)
+        {
+            let max_spec = self.max_fork.unwrap_or(LATEST_SUPPORTED_SPEC);
+            if let Err(e) = coverage.write(path, max_spec) {
+                error!("Failed to write coverage {:?}: {}", path, e);
+                success = false;
+            }
+        }
+
+        if self.summary {
+            print_summary(&records);
+        }
+
+        print_metrics(&records, start.elapsed(), self.metrics);
+
+        if let Some(perf) = &sinks.perf {
+            perf.print();
+        }
+
         success
     }
 
+    fn compare_runs(&self) -> bool {
+        let [before, after] = &self.compare_runs[..] else {
+            error!("--compare-runs takes the reports of two runs");
+            return false;
+        };
+        match RunDiff::load(before, after) {
+            Ok(diff) => {
+                diff.print();
+                diff.is_clean()
+            }
+            Err(e) => {
+                error!("{}", e);
+                false
+            }
+        }
+    }
+
+    fn matches_regex(&self) -> Result<Option<Regex>, regex::Error> {
+        let Some(pattern) = &self.matches else {
+            return Ok(None);
+        };
+        if self.literal {
+            Regex::new(&regex::escape(pattern)).map(Some)
+        } else {
+            Regex::new(pattern).map(Some)
+        }
+    }
+
+    fn unit_options<'a>(
+        &'a self, matches: Option<&'a Regex>, sinks: &'a Sinks,
+        fork_machines: Option<&'a ForkMachines>,
+    ) -> UnitOptions<'a> {
+        UnitOptions {
+            matches,
+            forks: &self.forks,
+            max_spec: self.max_fork.unwrap_or(LATEST_SUPPORTED_SPEC),
+            fork_range: self.fork_range,
+            test: self.test.as_ref(),
+            only_failing: self.only_failing_from.as_ref(),
+            sinks,
+            timeout: self.timeout_ms.map(Duration::from_millis),
+            retries: self.retries,
+            repeat_failures: self.repeat_failures,
+            max_gas: self.max_gas.map(U256::from),
+            space: self.space,
+            shard: self.shard,
+            keep_going: self.keep_going,
+            keep_going_in_unit: self.within_unit_keep_going,
+            bless: self.bless,
+            coinbase: self.coinbase,
+            block: BlockOverrides {
+                number: self.block_number,
+                timestamp: self.timestamp,
+            },
+            xfail: self.xfail.as_ref(),
+            dump_state: sinks.state_dumps.as_deref(),
+            reference: self.diff_ref.as_ref(),
+            checks: self.checks,
+            spec_from_fork: self.spec_from_fork,
+            fork_machines,
+            post_checks: None,
+            progress: None,
+            budget: None,
+        }
+    }
+
+    /// A command running only the test of `err` with a trace, with the
+    /// flags of this run that change how it executes. `None` if the test is
+    /// not known down to its post state, or was read from stdin.
+    fn repro_command(&self, err: &TestError) -> Option<String> {
+        let (Some(spec), Some(index)) = (err.spec, err.index) else {
+            return None;
+        };
+        if self.stdin {
+            return None;
+        }
+        let id = TestId {
+            path: err.path.clone(),
+            name: err.name.clone(),
+            spec,
+            index,
+        };
+        let mut args = vec!["evm-spec-tester".to_string()];
+        if !self.config.path.is_empty() {
+            args.extend(["-c".to_string(), shell_quote(&self.config.path)]);
+        }
+        args.push(shell_quote(&err.path));
+        args.extend(["--test".to_string(), shell_quote(&id.to_string())]);
+        args.push("--trace".to_string());
+        if let Some(dir) = &self.output_dir {
+            let dir = dir.to_string_lossy();
+            args.extend(["--output-dir".to_string(), shell_quote(&dir)]);
+        }
+        if self.space == Space::Native {
+            args.extend(["--space".to_string(), "native".to_string()]);
+        }
+        if let Some(coinbase) = &self.coinbase {
+            args.extend(["--coinbase".to_string(), format!("{:?}", coinbase)]);
+        }
+        if let Some(number) = self.block_number {
+            args.extend(["--block-number".to_string(), number.to_string()]);
+        }
+        if let Some(timestamp) = self.timestamp {
+            args.extend(["--timestamp".to_string(), timestamp.to_string()]);
+        }
+        args.extend(self.checks.flags().into_iter().map(str::to_string));
+        if self.spec_from_fork {
+            args.push("--spec-from-fork".to_string());
+        }
+        Some(args.join(" "))
+    }
+
+    /// The fixtures under `path`, only the file of `--test` or the ones of
+    /// `--only-failing-from` if set.
+    fn find_fixtures(&self, path: &Path, filter: &PathFilter) -> FixtureFiles {
+        let mut fixtures = find_fixtures(path, filter);
+        if let Some(id) = &self.test {
+            fixtures
+                .files
+                .retain(|file| id.matches_path(&file.to_string_lossy()));
+        }
+        if let Some(list) = &self.only_failing_from {
+            fixtures
+                .files
+                .retain(|file| list.has_path(&file.to_string_lossy()));
+        }
+        fixtures
+    }
+
+    /// Warns about the tests of `list` no longer in the fixtures under the
+    /// paths.
+    fn warn_missing_failures(
+        &self, list: &FailingList, filter: &PathFilter, pool: &ThreadPool,
+    ) {
+        if list.is_empty() {
+            warn!("No failed test in the report");
+            return;
+        }
+        let files: Vec<_> = self
+            .paths
+            .iter()
+            .flat_map(|path| self.find_fixtures(path, filter).files)
+            .collect();
+        let (units, _) = load_units(&files, pool);
+        let mut ids: HashMap<String, Vec<TestId>> = HashMap::new();
+        for id in units.iter().flat_map(UnitTester::ids) {
+            ids.entry(id.name.clone()).or_default().push(id);
+        }
+        for id in list.ids() {
+            let found = ids.get(&id.name).map_or(false, |others| {
+                others.iter().any(|other| id.matches(other))
+            });
+            if !found {
+                warn!("Failed test {} is no longer in the fixtures", id);
+            }
+        }
+    }
+
+    /// Checks that `id` is a post state of the fixtures under the paths, or
+    /// returns an error listing the closest identifiers.
+    fn check_test_id(
+        &self, id: &TestId, filter: &PathFilter, pool: &ThreadPool,
+    ) -> Result<(), String> {
+        let (mut files, mut others) = (vec![], vec![]);
+        for path in &self.paths {
+            let (found, rest): (Vec<_>, Vec<_>) = find_fixtures(path, filter)
+                .files
+                .into_iter()
+                .partition(|file| id.matches_path(&file.to_string_lossy()));
+            files.extend(found);
+            others.extend(rest);
+        }
+
+        let (error, near) = if files.is_empty() {
+            // Compare as many trailing components as the path of `id` has
+            let depth = id.path.split('/').count();
+            let suffixes = others.iter().map(|file| {
+                let components: Vec<_> = file
+                    .iter()
+                    .map(|c| c.to_string_lossy().into_owned())
+                    .collect();
+                components[components.len().saturating_sub(depth)..].join("/")
+            });
+            (
+                format!("No fixture file matches {}", id.path),
+                closest(&id.path, suffixes, 5),
+            )
+        } else {
+            let (units, _) = load_units(&files, pool);
+            let ids: Vec<TestId> =
+                units.iter().flat_map(UnitTester::ids).collect();
+            if ids.iter().any(|other| id.matches(other)) {
+                return Ok(());
+            }
+            // Printed with the path of `id`, so that they can be pasted back
+            let ids = ids.into_iter().map(|other| {
+                TestId {
+                    path: id.path.clone(),
+                    ..other
+                }
+                .to_string()
+            });
+            (
+                format!("No test {} in the fixtures", id),
+                closest(&id.to_string(), ids, 5),
+            )
+        };
+
+        if near.is_empty() {
+            return Err(error);
+        }
+        Err(format!("{}, near matches:\n\t{}", error, near.join("\n\t")))
+    }
+
+    /// Prints the identifiers of the tests a run would execute, one per line,
+    /// without executing them.
+    fn list_tests(
+        &self, filter: &PathFilter, pool: &ThreadPool, matches: Option<&Regex>,
+    ) -> bool {
+        let mut success = true;
+        let sinks = Sinks::default();
+        let opts = self.unit_options(matches, &sinks, None);
+
+        for path in &self.paths {
+            if !path.exists() {
+                panic!("Path not exists: {:?}", path);
+            }
+
+            let fixtures = self.find_fixtures(path, filter);
+            let (units, parse_errors) = load_units(&fixtures.files, pool);
+            for LoadError { path, error } in
+                fixtures.errors.iter().chain(&parse_errors)
+            {
+                error!("Failed to load {}: {}", path.display(), error);
+            }
+            if self.load_failed(&fixtures.errors, &parse_errors) {
+                success = false;
+            }
+
+            for id in units.iter().flat_map(|unit| unit.list(&opts)) {
+                println!("{}", id);
+            }
+        }
+        success
+    }
+
+    /// Fuzzes the selected units, see `UnitTester::fuzz`, and prints the
+    /// failures.
+    fn fuzz_tests(
+        &self, filter: &PathFilter, pool: &ThreadPool, matches: Option<&Regex>,
+        sinks: &Sinks, engine: Engine, fuzz: &FuzzOptions,
+    ) -> bool {
+        let mut success = true;
+        let Engine {
+            machine,
+            verification,
+            fork_machines,
+        } = engine;
+        let opts = self.unit_options(matches, sinks, Some(fork_machines));
+
+        for path in &self.paths {
+            if !path.exists() {
+                panic!("Path not exists: {:?}", path);
+            }
+
+            let fixtures = self.find_fixtures(path, filter);
+            let (units, parse_errors) = load_units(&fixtures.files, pool);
+            for LoadError { path, error } in
+                fixtures.errors.iter().chain(&parse_errors)
+            {
+                error!("Failed to load {}: {}", path.display(), error);
+            }
+            if self.load_failed(&fixtures.errors, &parse_errors) {
+                success = false;
+            }
+
+            info!("Fuzzing {} TestUnits in {}", units.len(), path.display());
+            // The panics are reported with the mutations, the default hook
+            // would also print every one hit while minimizing
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(|_| {}));
+            let failures: Vec<FuzzFailure> = pool.install(|| {
+                units
+                    .par_iter()
+                    .filter_map(|unit| {
+                        unit.fuzz(machine, verification, &opts, fuzz)
+                    })
+                    .collect()
+            });
+            panic::set_hook(hook);
+
+            for failure in &failures {
+                println!("\nFuzz failure: {}", failure);
+                success = false;
+            }
+            println!(
+                "\nFuzz failures in {}: {}",
+                path.display(),
+                failures.len()
+            );
+        }
+
+        success
+    }
+
+    /// Whether the errors of loading the fixtures fail the run. A fixture
+    /// that does not parse is only reported, unless `--strict-parse` is set.
+    fn load_failed(
+        &self, walk_errors: &[LoadError], parse_errors: &[LoadError],
+    ) -> bool {
+        !walk_errors.is_empty()
+            || (self.strict_parse && !parse_errors.is_empty())
+    }
+
+    fn make_machine(&self) -> Arc<Machine> {
+        let vm_factory = VmFactory::new(1024 * 32);
+        Arc::new(Machine::new_with_builtin(
+            self.config.parsed.common_params(),
+            vm_factory,
+        ))
+    }
+
+    /// Runs the units of the fixture read from stdin, see `load_stdin`.
+    fn run_stdin_tests(
+        &self, pool: &ThreadPool, matches: Option<&Regex>, sinks: &Sinks,
+        engine: Engine, records: &mut Vec<TestRecord>,
+    ) -> bool {
+        let units = match load_stdin() {
+            Ok(units) => units,
+            Err(e) => {
+                error!("Failed to load the fixture from stdin: {}", e);
+                return false;
+            }
+        };
+        info!("Running {} TestUnits from {}", units.len(), STDIN_PATH);
+        let loaded = LoadedUnits {
+            units,
+            skipped: 0,
+            load_errors: vec![],
+            parse_errors: vec![],
+        };
+        self.run_loaded_units(loaded, pool, matches, sinks, engine, records)
+    }
+
     fn run_file_tests(
-        &self, test_files: Vec<PathBuf>, path: &PathBuf,
+        &self, fixtures: FixtureFiles, path: &Path, pool: &ThreadPool,
+        matches: Option<&Regex>, sinks: &Sinks, engine: Engine,
+        records: &mut Vec<TestRecord>,
     ) -> Result<bool, String> {
-        let mut success = true;
         info!(
             "Running {} TestSuites in {}",
-            test_files.len(),
+            fixtures.files.len(),
             path.display()
         );
 
-        let machine = {
-            let vm_factory = VmFactory::new(1024 * 32);
-            Arc::new(Machine::new_with_builtin(
-                self.config.common_params(),
-                vm_factory,
-            ))
+        let (units, parse_errors) = load_units(&fixtures.files, pool);
+        let loaded = LoadedUnits {
+            units,
+            skipped: fixtures.skipped,
+            load_errors: fixtures.errors,
+            parse_errors,
         };
+        Ok(
+            self.run_loaded_units(
+                loaded, pool, matches, sinks, engine, records,
+            ),
+        )
+    }
 
-        let verification = self.config.verification_config(machine.clone());
-
-        let mut skipped_suite = 0;
-        let mut load_err_suite = 0;
+    /// Runs `loaded` and prints its failures and counts. Returns whether the
+    /// run succeeded.
+    fn run_loaded_units(
+        &self, loaded: LoadedUnits, pool: &ThreadPool, matches: Option<&Regex>,
+        sinks: &Sinks, engine: Engine, records: &mut Vec<TestRecord>,
+    ) -> bool {
+        let mut success = true;
+        let LoadedUnits {
+            units,
+            skipped: skipped_suite,
+            load_errors,
+            parse_errors,
+        } = loaded;
+        let Engine {
+            machine,
+            verification,
+            fork_machines,
+        } = engine;
 
-        let mut success_units = 0;
-        let mut skipped_units = 0;
-        let mut total_executions = 0;
+        let fail_fast = !self.keep_going;
 
-        let mut error_list = vec![];
+        let opts = self.unit_options(matches, sinks, Some(fork_machines));
+        // Only counts a single run
+        let progress = (!self.quiet
+            && !self.summary_only
+            && self.repeat.is_none()
+            && Progress::is_supported())
+        .then(|| {
+            let total = pool.install(|| {
+                units.par_iter().map(|unit| unit.list(&opts).len()).sum()
+            });
+            Progress::new(self.count.map_or(total, |count| count.min(total)))
+        });
+        let budget = self.count.map(TestBudget::new);
+        let opts = UnitOptions {
+            progress: progress.as_ref(),
+            budget: budget.as_ref(),
+            ..opts
+        };
+        let load_err_suite = load_errors.len() + parse_errors.len();
+        let load_failed = self.load_failed(&load_errors, &parse_errors);
 
-        for path in test_files {
-            if skip_test(&path) {
-                skipped_suite += 1;
-                continue;
+        let mut timings = None;
+        let report = if load_failed && fail_fast {
+            RunReport {
+                cancelled_units: units.len(),
+                ..Default::default()
             }
+        } else if let Some(repeat) = self.repeat {
+            let (report, bench) = pool.install(|| {
+                bench::run_repeated(
+                    &units,
+                    machine,
+                    verification,
+                    &opts,
+                    self.warmup.unwrap_or_default(),
+                    repeat,
+                )
+            });
+            timings = Some(bench);
+            report
+        } else {
+            pool.install(|| run_units(&units, machine, verification, &opts))
+        };
+        if let Some(progress) = &progress {
+            progress.finish();
+        }
+        if load_failed || !report.is_success() {
+            success = false;
+        }
 
-            let (success_cnt, skipped_cnt, transact_cnt, errors) =
-                match SuiteTester::load(&path) {
-                    Ok(tester) => tester.run(
-                        &machine,
-                        &verification,
-                        self.matches.as_deref(),
-                    ),
-                    Err(err_msg) => {
-                        warn!(
-                            "TestSuite load failed. path: {:?}, error: {}",
-                            path, err_msg
-                        );
-                        success = false;
-                        load_err_suite += 1;
-                        continue;
-                    }
-                };
-
-            success_units += success_cnt;
-            skipped_units += skipped_cnt;
-            total_executions += transact_cnt;
+        let total_executions = report.executed();
+        let failed_executions = report.failed();
+        let filtered_units = report.filtered_units();
+        let RunReport {
+            records: unit_records,
+            specs: spec_tally,
+            errors: error_list,
+            blessed: blessed_posts,
+            success_units,
+            skipped_units,
+            above_cap_units,
+            above_cap_tests,
+            empty_units,
+            error_units,
+            cancelled_units,
+            unscheduled_units,
+            surface,
+            unsupported_tx_types,
+        } = report;
+        records.extend(unit_records);
 
-            error_list.extend(errors);
+        let mut blessed: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        for post in blessed_posts {
+            blessed.entry(post.path.clone()).or_default().push(post);
         }
 
-        let error_units = error_list.len();
+        for (path, posts) in &blessed {
+            let out = match &sinks.blessed {
+                Some(output) => output.path(Artifact::Blessed, path),
+                None => Ok((PathBuf::from(path), false)),
+            };
+            let res = out.map_err(|e| e.to_string()).and_then(|(out, _)| {
+                bless_fixture(Path::new(path), &out, posts)?;
+                Ok(out)
+            });
+            match res {
+                Ok(out) => info!(
+                    "Blessed {} post states of {} in {}",
+                    posts.len(),
+                    path,
+                    out.display()
+                ),
+                Err(e) => {
+                    error!("Failed to bless {}: {}", path, e);
+                    success = false;
+                }
+            }
+        }
 
-        for (path, units) in
-            &error_list.into_iter().chunk_by(|err| err.path.clone())
+        for (path, units) in &error_list.iter().chunk_by(|err| err.path.clone())
         {
-            success = false;
             println!("\nPath {path} fails:");
-            for TestError { name, kind, .. } in units {
-                println!("\t{name}: {kind}");
+            for err in units {
+                let TestError {
+                    name,
+                    spec,
+                    kind,
+                    repeats,
+                    ..
+                } = err;
+                match spec {
+                    Some(spec) => println!("\t{name} ({spec:?}): {kind}"),
+                    None => println!("\t{name}: {kind}"),
+                }
+                if let Some(repeats) = repeats {
+                    println!("\t\t{repeats}");
+                }
+                if let Some(command) = self.repro_command(err) {
+                    println!("\t\treproduce with: {command}");
+                }
+            }
+        }
+
+        if self.interactive && !error_list.is_empty() {
+            if Triage::is_supported() {
+                let traces =
+                    sinks.trace.as_deref().and_then(TraceSink::output_dir);
+                let rerun = |id: &TestId| {
+                    let sinks = Sinks {
+                        trace: Some(Arc::new(TraceSink::stdout())),
+                        ..Default::default()
+                    };
+                    let opts = UnitOptions {
+                        test: Some(id),
+                        keep_going: true,
+                        ..self.unit_options(None, &sinks, Some(fork_machines))
+                    };
+                    pool.install(|| {
+                        run_units(&units, machine, verification, &opts)
+                    })
+                };
+                let mut triage = Triage::new(&error_list, traces, rerun);
+                let stdin = io::stdin();
+                if let Err(e) = triage.run(stdin.lock(), &mut io::stdout()) {
+                    warn!("Interactive triage stopped: {}", e);
+                }
+            } else {
+                warn!("--interactive needs stdin and stdout to be terminals");
+            }
+        }
+
+        if !load_errors.is_empty() {
+            println!("\nCollection errors:");
+            for LoadError { path, error } in &load_errors {
+                println!("\t{}: {}", path.display(), error);
+            }
+        }
+
+        if !parse_errors.is_empty() {
+            println!("\nParse errors:");
+            for LoadError { path, error } in &parse_errors {
+                println!("\t{}: {}", path.display(), error);
             }
         }
 
         println!("\n\nSkipped TestSuites: {}", skipped_suite);
         println!("Load Failed TestSuites: {}", load_err_suite);
+        println!("Parse Failed TestSuites: {}", parse_errors.len());
         println!("Success Units: {}", success_units);
-        println!("Skipped Units: {}", skipped_units);
+        println!(
+            "Skipped Units: {} ({} filtered out, {} after the max fork, {} \
+             without post states)",
+            skipped_units, filtered_units, above_cap_units, empty_units
+        );
+        if !above_cap_tests.is_empty() {
+            println!(
+                "Post States After The Max Fork: {}",
+                above_cap_tests.values().sum::<usize>()
+            );
+        }
+        for (spec, count) in &above_cap_tests {
+            println!("\t{spec:?}: {count}");
+        }
         println!("Error Units  : {}", error_units);
+        if fail_fast {
+            println!("Cancelled Units: {}", cancelled_units);
+        }
+        if let Some(budget) = budget.as_ref().filter(|b| b.is_used_up()) {
+            println!(
+                "Stopped after {} executed tests (--count {}), {} units not \
+                 started",
+                total_executions,
+                budget.count(),
+                unscheduled_units
+            );
+        }
         println!("Total Executions: {}", total_executions);
+        println!("Failed Executions: {}", failed_executions);
+        println!(
+            "State Changed: {} accounts, {} storage slots",
+            surface.accounts.len(),
+            surface.slots.len()
+        );
+        println!(
+            "Unsupported Tx Types: {}",
+            unsupported_tx_types.values().sum::<usize>()
+        );
+        for (tx_type, count) in &unsupported_tx_types {
+            println!("\ttype {tx_type}: {count}");
+        }
 
-        Ok(success)
+        if !spec_tally.is_empty() {
+            println!("\nPer-fork Executions:");
+            for (spec, tally) in &spec_tally {
+                println!(
+                    "\t{spec:?}: {} passed, {} skipped, {} failed, {} xfail, {} xpass",
+                    tally.passed,
+                    tally.skipped,
+                    tally.failed,
+                    tally.xfailed,
+                    tally.xpassed
+                );
+            }
+        }
+
+        if let Some(timings) = &timings {
+            timings.print();
+        }
+
+        success
     }
 }
 
-struct SuiteTester {
-    path: String,
-    suite: StateTestSuite,
+/// The machine of the tests of a run, its verification config and its
+/// fork machines, built once by `StateTestCmd::run`.
+#[derive(Clone, Copy)]
+struct Engine<'a> {
+    machine: &'a Arc<Machine>,
+    verification: &'a VerificationConfig,
+    fork_machines: &'a ForkMachines,
 }
 
-impl SuiteTester {
-    pub fn load(path: &PathBuf) -> Result<Self, String> {
-        let s = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        let suite: StateTestSuite =
-            serde_json::from_str(&s).map_err(|e| e.to_string())?;
-
-        let path = path.to_string_lossy().into_owned();
-        Ok(Self { path, suite })
-    }
-
-    fn run(
-        self, machine: &Machine, verification: &VerificationConfig,
-        matches: Option<&str>,
-    ) -> (usize, usize, usize, Vec<TestError>) {
-        if matches.is_some() {
-            trace!("Running TestUnit: {}", self.path);
-        } else {
-            debug!("Running TestUnit: {}", self.path);
-        }
-
-        let mut error_list = vec![];
-        let mut success_cnt = 0;
-        let mut skipped_cnt = 0;
-        let mut transact_cnt = 0;
-        for (name, unit) in self.suite.0 {
-            let unit_tester = UnitTester::new(&self.path, name, unit);
-            match unit_tester.run(&machine, verification, matches) {
-                Ok(cnt) => {
-                    transact_cnt += cnt;
-                    if cnt > 0 {
-                        success_cnt += 1;
-                    } else {
-                        skipped_cnt += 1;
-                    }
-                }
-                Err(e) => error_list.push(e),
-            }
-        }
-        (success_cnt, skipped_cnt, transact_cnt, error_list)
-    }
+/// The units of a run, with the errors of loading them.
+struct LoadedUnits {
+    units: Vec<UnitTester>,
+    /// Fixture files skipped by `skip_test`.
+    skipped: usize,
+    load_errors: Vec<LoadError>,
+    parse_errors: Vec<LoadError>,
 }
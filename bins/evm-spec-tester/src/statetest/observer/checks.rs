@@ -0,0 +1,183 @@
+use super::{
+    CoinbaseAccessTracer, CreationTracer, GasForwardingTracer,
+    PrecompileTracer, SelfdestructTracer, StorageWritesTracer, TouchTracer,
+    TransientStorageTracer, WarmAccountsTracer,
+};
+use cfx_executor::{
+    observer::{
+        AddressPocket, CallTracer, CheckpointTracer, DrainTrace,
+        InternalTransferTracer, OpcodeTracer, StorageTracer, TracerTrait,
+    },
+    stack::FrameResult,
+};
+use cfx_types::{Address, AddressWithSpace, H256, U256};
+use cfx_vm_tracer_derive::DrainTrace;
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+
+/// The tracers of the checks enabled for a test, one field of `Observer`.
+/// The tracer derive matches every combination of the fields of the
+/// observer, so the checks are not fields of their own: the set of enabled
+/// checks only grows this struct, and every event is forwarded to the
+/// tracers that are set.
+#[derive(Default, DrainTrace)]
+pub struct CheckTracers {
+    /// Enabled by `Checks::trace_precompiles` and
+    /// `Checks::check_precompile_gas`.
+    pub precompiles: Option<PrecompileTracer>,
+    /// Enabled by `Checks::check_gas_forwarding`.
+    pub gas_forwarding: Option<GasForwardingTracer>,
+    /// Enabled for the tests of the Ethereum space, whose addresses the
+    /// fixtures are derived for.
+    pub creation: Option<CreationTracer>,
+    /// Copies the warm accounts once per transaction.
+    pub warm_accounts: Option<WarmAccountsTracer>,
+    /// Enabled for the tests of the Ethereum space, whose forks give the
+    /// SELFDESTRUCT rule.
+    pub selfdestruct: Option<SelfdestructTracer>,
+    /// Enabled by `Checks::check_revert_rollback`.
+    pub storage_writes: Option<StorageWritesTracer>,
+    /// Enabled for the tests of the Ethereum space from Cancun, which
+    /// introduces the transient storage.
+    pub transient_storage: Option<TransientStorageTracer>,
+    /// Enabled by `Checks::check_coinbase_access` for the tests of the
+    /// Ethereum space whose spec prices the account accesses by their
+    /// warmth (EIP-2929).
+    pub coinbase_access: Option<CoinbaseAccessTracer>,
+    /// Enabled for the tests of the Ethereum space from Spurious Dragon,
+    /// which clears the touched empty accounts (EIP-161).
+    pub touches: Option<TouchTracer>,
+}
+
+/// The tracers of `$checks` that are set, as `$ty`.
+macro_rules! enabled_tracers {
+    ($checks:expr, $as:ident, $ty:ty) => {{
+        let CheckTracers {
+            precompiles,
+            gas_forwarding,
+            creation,
+            warm_accounts,
+            selfdestruct,
+            storage_writes,
+            transient_storage,
+            coinbase_access,
+            touches,
+        } = $checks;
+        [
+            precompiles.$as().map(|t| t as $ty),
+            gas_forwarding.$as().map(|t| t as $ty),
+            creation.$as().map(|t| t as $ty),
+            warm_accounts.$as().map(|t| t as $ty),
+            selfdestruct.$as().map(|t| t as $ty),
+            storage_writes.$as().map(|t| t as $ty),
+            transient_storage.$as().map(|t| t as $ty),
+            coinbase_access.$as().map(|t| t as $ty),
+            touches.$as().map(|t| t as $ty),
+        ]
+        .into_iter()
+        .flatten()
+    }};
+}
+
+impl CheckTracers {
+    fn for_each(&mut self, f: impl FnMut(&mut dyn TracerTrait)) {
+        enabled_tracers!(self, as_mut, &mut dyn TracerTrait).for_each(f);
+    }
+}
+
+impl CallTracer for CheckTracers {
+    fn record_call(&mut self, params: &ActionParams) {
+        self.for_each(|t| t.record_call(params));
+    }
+
+    fn record_call_result(&mut self, result: &FrameResult) {
+        self.for_each(|t| t.record_call_result(result));
+    }
+
+    fn record_builtin_call(&mut self, address: &Address) {
+        self.for_each(|t| t.record_builtin_call(address));
+    }
+
+    fn do_record_warm_accounts(&self, enabled: &mut bool) {
+        enabled_tracers!(self, as_ref, &dyn TracerTrait)
+            .for_each(|t| t.do_record_warm_accounts(enabled));
+    }
+
+    fn record_warm_accounts(&mut self, accounts: &[AddressWithSpace]) {
+        self.for_each(|t| t.record_warm_accounts(accounts));
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        self.for_each(|t| t.record_create(params));
+    }
+
+    fn record_create_result(&mut self, result: &FrameResult) {
+        self.for_each(|t| t.record_create_result(result));
+    }
+}
+
+impl CheckpointTracer for CheckTracers {
+    fn trace_checkpoint(&mut self) { self.for_each(|t| t.trace_checkpoint()); }
+
+    fn trace_checkpoint_discard(&mut self) {
+        self.for_each(|t| t.trace_checkpoint_discard());
+    }
+
+    fn trace_checkpoint_revert(&mut self) {
+        self.for_each(|t| t.trace_checkpoint_revert());
+    }
+}
+
+impl InternalTransferTracer for CheckTracers {
+    fn trace_internal_transfer(
+        &mut self, from: AddressPocket, to: AddressPocket, value: U256,
+    ) {
+        self.for_each(|t| t.trace_internal_transfer(from, to, value));
+    }
+
+    fn trace_convert_storage_points(
+        &mut self, addr: Address, from_balance: U256, from_collateral: U256,
+    ) {
+        self.for_each(|t| {
+            t.trace_convert_storage_points(addr, from_balance, from_collateral)
+        });
+    }
+
+    fn trace_refund_collateral(&mut self, addr: Address, by: U256) {
+        self.for_each(|t| t.trace_refund_collateral(addr, by));
+    }
+
+    fn trace_occupy_collateral(&mut self, addr: Address, by: U256) {
+        self.for_each(|t| t.trace_occupy_collateral(addr, by));
+    }
+}
+
+impl StorageTracer for CheckTracers {}
+
+impl OpcodeTracer for CheckTracers {
+    fn do_trace_opcode(&self, enabled: &mut bool) {
+        enabled_tracers!(self, as_ref, &dyn TracerTrait)
+            .for_each(|t| t.do_trace_opcode(enabled));
+    }
+
+    fn initialize_interp(&mut self, gas_limit: U256) {
+        self.for_each(|t| t.initialize_interp(gas_limit));
+    }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        self.for_each(|t| t.step(interp));
+    }
+
+    fn step_end(&mut self, interp: &dyn InterpreterInfo) {
+        self.for_each(|t| t.step_end(interp));
+    }
+
+    fn log(&mut self, address: &Address, topics: &Vec<H256>, data: &[u8]) {
+        self.for_each(|t| t.log(address, topics, data));
+    }
+
+    fn selfdestruct(
+        &mut self, contract: &Address, target: &Address, value: U256,
+    ) {
+        self.for_each(|t| t.selfdestruct(contract, target, value));
+    }
+}
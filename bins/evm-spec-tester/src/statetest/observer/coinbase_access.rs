@@ -0,0 +1,233 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+};
+use cfx_types::{Address, BigEndianHash, H256, U256};
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use std::fmt;
+use typemap::ShareDebugMap;
+
+const BALANCE: u8 = 0x31;
+const EXTCODESIZE: u8 = 0x3b;
+const EXTCODECOPY: u8 = 0x3c;
+const EXTCODEHASH: u8 = 0x3f;
+const CALL: u8 = 0xf1;
+const CALLCODE: u8 = 0xf2;
+const DELEGATECALL: u8 = 0xf4;
+const STATICCALL: u8 = 0xfa;
+const SELFDESTRUCT: u8 = 0xff;
+
+/// EIP-2929 costs of an account access.
+const WARM_ACCESS: u64 = 100;
+const COLD_ACCESS: u64 = 2600;
+
+/// The first access to the block author charged another cost than the one
+/// of its warmth at the start of the execution.
+#[derive(Clone, Debug)]
+pub struct CoinbaseAccessMismatch {
+    pub opcode: &'static str,
+    pub coinbase: Address,
+    /// Whether the coinbase was expected warm, by EIP-3651 or by the
+    /// transaction itself.
+    pub warm: bool,
+    pub expected: U256,
+    pub got: U256,
+}
+
+impl CoinbaseAccessMismatch {
+    pub fn of(executed: &Executed) -> Option<Self> {
+        executed.ext_result.get::<CoinbaseAccessKey>().cloned()
+    }
+}
+
+impl fmt::Display for CoinbaseAccessMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of the coinbase {:?} charged {}, expected {} for a {} account",
+            self.opcode,
+            self.coinbase,
+            self.got,
+            self.expected,
+            if self.warm { "warm" } else { "cold" }
+        )
+    }
+}
+
+/// Checks the gas charged by the first BALANCE, EXTCODESIZE or EXTCODEHASH
+/// of the coinbase in the transaction, the warm cost if it is warm from the
+/// start of the execution and the cold one otherwise. Only the first access
+/// is checked, since the warmth it gives is dropped if its frame reverts.
+pub struct CoinbaseAccessTracer {
+    coinbase: Address,
+    warm: bool,
+    /// Whether the coinbase was accessed already.
+    accessed: bool,
+    /// The access being executed and the gas left before it.
+    pending: Option<(&'static str, U256)>,
+    mismatch: Option<CoinbaseAccessMismatch>,
+}
+
+impl CoinbaseAccessTracer {
+    pub fn new(coinbase: Address, warm: bool) -> Self {
+        CoinbaseAccessTracer {
+            coinbase,
+            warm,
+            accessed: false,
+            pending: None,
+            mismatch: None,
+        }
+    }
+
+    fn is_coinbase(&self, word: Option<&U256>) -> bool {
+        word.map_or(false, |word| {
+            Address::from(H256::from_uint(word)) == self.coinbase
+        })
+    }
+}
+
+impl DrainTrace for CoinbaseAccessTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        if let Some(mismatch) = self.mismatch {
+            map.insert::<CoinbaseAccessKey>(mismatch);
+        }
+    }
+}
+
+pub struct CoinbaseAccessKey;
+
+impl typemap::Key for CoinbaseAccessKey {
+    type Value = CoinbaseAccessMismatch;
+}
+
+impl CallTracer for CoinbaseAccessTracer {
+    fn record_call(&mut self, params: &ActionParams) {
+        if params.address == self.coinbase
+            || params.code_address == self.coinbase
+        {
+            self.accessed = true;
+        }
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        if params.address == self.coinbase {
+            self.accessed = true;
+        }
+    }
+}
+
+impl CheckpointTracer for CoinbaseAccessTracer {}
+
+impl InternalTransferTracer for CoinbaseAccessTracer {}
+
+impl StorageTracer for CoinbaseAccessTracer {}
+
+impl OpcodeTracer for CoinbaseAccessTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        if self.accessed {
+            return;
+        }
+        let stack = interp.stack();
+        let arg = |i: usize| stack.len().checked_sub(i + 1).map(|i| &stack[i]);
+        let opcode = interp.current_opcode();
+        let name = match opcode {
+            BALANCE => "BALANCE",
+            EXTCODESIZE => "EXTCODESIZE",
+            EXTCODEHASH => "EXTCODEHASH",
+            EXTCODECOPY | SELFDESTRUCT => {
+                self.accessed = self.is_coinbase(arg(0));
+                return;
+            }
+            CALL | CALLCODE | DELEGATECALL | STATICCALL => {
+                self.accessed = self.is_coinbase(arg(1));
+                return;
+            }
+            _ => return,
+        };
+        if self.is_coinbase(arg(0)) {
+            self.pending = Some((name, interp.gas_remainning()));
+        }
+    }
+
+    fn step_end(&mut self, interp: &dyn InterpreterInfo) {
+        let Some((opcode, gas)) = self.pending.take() else {
+            return;
+        };
+        // Not charged, the instruction failed
+        if interp.gas_remainning() == gas {
+            return;
+        }
+        self.accessed = true;
+        let expected =
+            U256::from(if self.warm { WARM_ACCESS } else { COLD_ACCESS });
+        let got = gas.saturating_sub(interp.gas_remainning());
+        if got != expected {
+            self.mismatch = Some(CoinbaseAccessMismatch {
+                opcode,
+                coinbase: self.coinbase,
+                warm: self.warm,
+                expected,
+                got,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::test_interp::Step, CoinbaseAccessTracer, BALANCE, CALL,
+    };
+    use cfx_executor::observer::OpcodeTracer;
+    use cfx_types::{Address, BigEndianHash, H256, U256};
+
+    /// Runs `opcode` on `stack`, with the top last, charging `cost`.
+    fn exec(
+        tracer: &mut CoinbaseAccessTracer, opcode: u8, stack: Vec<U256>,
+        cost: u64,
+    ) {
+        let gas = U256::from(10_000);
+        let step = Step {
+            opcode,
+            stack,
+            gas,
+            ..Default::default()
+        };
+        tracer.step(&step);
+        tracer.step_end(&Step {
+            gas: gas - cost,
+            ..step
+        });
+    }
+
+    #[test]
+    fn first_coinbase_access_costs_its_warmth() {
+        let coinbase = Address::from_low_u64_be(0xc0ffee);
+        let word = H256::from(coinbase).into_uint();
+
+        let mut tracer = CoinbaseAccessTracer::new(coinbase, true);
+        exec(&mut tracer, BALANCE, vec![word], 100);
+        assert!(tracer.mismatch.is_none());
+
+        let mut tracer = CoinbaseAccessTracer::new(coinbase, true);
+        exec(&mut tracer, BALANCE, vec![word], 2600);
+        let mismatch = tracer.mismatch.unwrap();
+        assert_eq!(mismatch.expected, U256::from(100));
+        assert_eq!(mismatch.got, U256::from(2600));
+
+        // Warmed by a call, whose frame may revert
+        let mut tracer = CoinbaseAccessTracer::new(coinbase, false);
+        let call = vec![U256::zero(); 5]
+            .into_iter()
+            .chain([word, U256::from(50_000)])
+            .collect();
+        exec(&mut tracer, CALL, call, 2600);
+        exec(&mut tracer, BALANCE, vec![word], 100);
+        assert!(tracer.mismatch.is_none());
+    }
+}
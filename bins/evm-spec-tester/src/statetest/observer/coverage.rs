@@ -0,0 +1,165 @@
+use cfx_executor::{
+    executive::ExecutionOutcome,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+};
+use cfx_vm_interpreter::INSTRUCTIONS_CANCUN;
+use cfx_vm_types::InterpreterInfo;
+use eest_types::SpecId;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap, fs::File, io::BufWriter, path::Path, sync::Mutex,
+};
+use typemap::ShareDebugMap;
+
+/// Forks the fixtures are run under, see `SpecName::to_spec_id`.
+const COVERAGE_FORKS: [SpecId; 13] = [
+    SpecId::FRONTIER,
+    SpecId::HOMESTEAD,
+    SpecId::TANGERINE,
+    SpecId::SPURIOUS_DRAGON,
+    SpecId::BYZANTIUM,
+    SpecId::PETERSBURG,
+    SpecId::ISTANBUL,
+    SpecId::BERLIN,
+    SpecId::LONDON,
+    SpecId::MERGE,
+    SpecId::SHANGHAI,
+    SpecId::CANCUN,
+    SpecId::PRAGUE,
+];
+
+/// A set of opcodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpcodeSet([u64; 4]);
+
+impl OpcodeSet {
+    pub fn insert(&mut self, op: u8) {
+        self.0[(op >> 6) as usize] |= 1 << (op & 0x3f);
+    }
+
+    pub fn contains(&self, op: u8) -> bool {
+        self.0[(op >> 6) as usize] & (1 << (op & 0x3f)) != 0
+    }
+
+    pub fn union(&mut self, other: &OpcodeSet) {
+        for (word, other) in self.0.iter_mut().zip(other.0) {
+            *word |= other;
+        }
+    }
+}
+
+/// Records the opcodes executed by a transaction.
+#[derive(Default)]
+pub struct OpcodeCoverageTracer {
+    executed: OpcodeSet,
+}
+
+impl DrainTrace for OpcodeCoverageTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        map.insert::<OpcodeCoverageKey>(self.executed);
+    }
+}
+
+pub struct OpcodeCoverageKey;
+
+impl typemap::Key for OpcodeCoverageKey {
+    type Value = OpcodeSet;
+}
+
+impl CheckpointTracer for OpcodeCoverageTracer {}
+
+impl InternalTransferTracer for OpcodeCoverageTracer {}
+
+impl StorageTracer for OpcodeCoverageTracer {}
+
+impl CallTracer for OpcodeCoverageTracer {}
+
+impl OpcodeTracer for OpcodeCoverageTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        self.executed.insert(interp.current_opcode());
+    }
+}
+
+/// Opcodes executed across a run, by spec.
+#[derive(Default)]
+pub struct OpcodeCoverage(Mutex<BTreeMap<SpecId, OpcodeSet>>);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ForkCoverage {
+    fork: String,
+    executed: usize,
+    never_executed: Vec<&'static str>,
+}
+
+impl OpcodeCoverage {
+    /// Adds the opcodes carried by `outcome`.
+    pub fn record(&self, spec: SpecId, outcome: &ExecutionOutcome) {
+        let Some(executed) = outcome
+            .try_as_executed()
+            .and_then(|e| e.ext_result.get::<OpcodeCoverageKey>())
+        else {
+            return;
+        };
+        let mut coverage = self.0.lock().expect("coverage poisoned");
+        coverage.entry(spec).or_default().union(executed);
+    }
+
+    /// Writes, for every fork up to `max_spec`, the opcodes available in the
+    /// fork that no test executed.
+    pub fn write(&self, path: &Path, max_spec: SpecId) -> Result<(), String> {
+        let coverage = self.0.lock().expect("coverage poisoned");
+        let forks: Vec<_> = COVERAGE_FORKS
+            .into_iter()
+            .filter(|spec| *spec <= max_spec)
+            .map(|spec| {
+                let executed = coverage.get(&spec).copied().unwrap_or_default();
+                let never_executed = (0..=u8::MAX)
+                    .filter(|op| introduced_in(*op) <= spec)
+                    .filter(|op| !executed.contains(*op))
+                    .filter_map(|op| INSTRUCTIONS_CANCUN[op as usize])
+                    .map(|info| info.name)
+                    .collect();
+                ForkCoverage {
+                    fork: format!("{:?}", spec),
+                    executed: executed
+                        .0
+                        .iter()
+                        .map(|w| w.count_ones() as usize)
+                        .sum(),
+                    never_executed,
+                }
+            })
+            .collect();
+
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &forks)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The fork an opcode is introduced in.
+fn introduced_in(op: u8) -> SpecId {
+    match op {
+        // DELEGATECALL
+        0xf4 => SpecId::HOMESTEAD,
+        // RETURNDATASIZE, RETURNDATACOPY, STATICCALL, REVERT
+        0x3d | 0x3e | 0xfa | 0xfd => SpecId::BYZANTIUM,
+        // SHL, SHR, SAR, EXTCODEHASH, CREATE2
+        0x1b | 0x1c | 0x1d | 0x3f | 0xf5 => SpecId::PETERSBURG,
+        // CHAINID, SELFBALANCE
+        0x46 | 0x47 => SpecId::ISTANBUL,
+        // BASEFEE
+        0x48 => SpecId::LONDON,
+        // PUSH0
+        0x5f => SpecId::SHANGHAI,
+        // BLOBHASH, BLOBBASEFEE, TLOAD, TSTORE, MCOPY
+        0x49 | 0x4a | 0x5c | 0x5d | 0x5e => SpecId::CANCUN,
+        _ => SpecId::FRONTIER,
+    }
+}
@@ -0,0 +1,396 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::{
+    cal_contract_address, Address, BigEndianHash, CreateContractAddressType,
+    H256, U256,
+};
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use typemap::ShareDebugMap;
+
+const CREATE: u8 = 0xf0;
+const CREATE2: u8 = 0xf5;
+
+/// A contract created at another address than derived from its creator.
+#[derive(Clone, Debug)]
+pub struct CreateAddressMismatch {
+    /// Depth of the creating frame, 0 for a creation transaction.
+    pub depth: usize,
+    pub expected: Address,
+    pub got: Address,
+}
+
+impl CreateAddressMismatch {
+    pub fn of(executed: &Executed) -> Option<Self> {
+        executed.ext_result.get::<CreateAddressKey>().cloned()
+    }
+}
+
+impl fmt::Display for CreateAddressMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let by = match self.depth {
+            0 => "transaction".to_string(),
+            depth => format!("CREATE2 at depth {}", depth),
+        };
+        write!(
+            f,
+            "created by {} at {:?}, expected {:?}",
+            by, self.got, self.expected
+        )
+    }
+}
+
+/// The CREATE and CREATE2 of a transaction that bumped the nonce of their
+/// creator, in the frames that were not reverted.
+#[derive(Clone, Debug, Default)]
+pub struct Creations {
+    /// The number of frames opened by every creator.
+    pub counts: BTreeMap<Address, u64>,
+    /// The creators of a CREATE or CREATE2 that failed without opening a
+    /// frame, which consumes a nonce on an address collision but not on a
+    /// balance or depth failure.
+    pub unsure: BTreeSet<Address>,
+}
+
+/// The creations of the transaction, if they were traced.
+pub fn creations(executed: &Executed) -> Option<&Creations> {
+    executed.ext_result.get::<CreationsKey>()
+}
+
+/// Checks the address of the contracts created in the Ethereum space: the
+/// one of a creation transaction, from its sender and nonce, and the ones of
+/// CREATE2, from the creator, the salt and the init code. The nonce of a
+/// contract running CREATE is not known to the tracer, so CREATE is only
+/// covered by the post state. Also counts the creations of every creator,
+/// see `Creations`.
+pub struct CreationTracer {
+    depth: usize,
+    /// The address expected for the frame opened next.
+    pending: Option<Address>,
+    /// The first mismatch, a wrong address is likely to break the next ones.
+    mismatch: Option<CreateAddressMismatch>,
+    /// The contract running a CREATE or CREATE2 that did not open its frame
+    /// yet.
+    creating: Option<Address>,
+    /// The creations of every open frame, dropped if it reverts.
+    frames: Vec<BTreeMap<Address, u64>>,
+    creations: Creations,
+}
+
+impl CreationTracer {
+    /// `sender` and `nonce` are the ones of the transaction if it creates a
+    /// contract.
+    pub fn new(creation: Option<(Address, U256)>) -> Self {
+        CreationTracer {
+            depth: 0,
+            pending: creation.map(|(sender, nonce)| {
+                derive_address(
+                    CreateContractAddressType::FromSenderNonce,
+                    &sender,
+                    &nonce,
+                    &[],
+                )
+            }),
+            mismatch: None,
+            creating: None,
+            frames: vec![],
+            creations: Creations::default(),
+        }
+    }
+
+    /// Remembers a creator whose CREATE or CREATE2 did not open a frame.
+    fn settle_creating(&mut self) {
+        if let Some(creator) = self.creating.take() {
+            self.creations.unsure.insert(creator);
+        }
+    }
+
+    fn close_frame(&mut self, result: &FrameResult) {
+        self.settle_creating();
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if !result.as_ref().map_or(false, |r| r.apply_state) {
+            return;
+        }
+        let kept = match self.frames.last_mut() {
+            Some(parent) => parent,
+            None => &mut self.creations.counts,
+        };
+        for (creator, count) in frame {
+            *kept.entry(creator).or_default() += count;
+        }
+    }
+}
+
+fn derive_address(
+    scheme: CreateContractAddressType, sender: &Address, nonce: &U256,
+    code: &[u8],
+) -> Address {
+    cal_contract_address(scheme, 0, sender, nonce, code).0
+}
+
+/// The `size` bytes of `mem` from `offset`, the memory being zero past its
+/// end. `None` if the range can not be allocated.
+fn read_memory(mem: &[u8], offset: U256, size: U256) -> Option<Vec<u8>> {
+    if size.is_zero() {
+        return Some(vec![]);
+    }
+    let offset = usize::try_from(offset).ok()?;
+    let size = usize::try_from(size).ok()?;
+    // Far more than any gas limit can pay the memory of
+    if size > u32::MAX as usize {
+        return None;
+    }
+    let mut data = vec![0u8; size];
+    if let Some(src) = mem.get(offset..) {
+        let len = src.len().min(size);
+        data[..len].copy_from_slice(&src[..len]);
+    }
+    Some(data)
+}
+
+impl DrainTrace for CreationTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        if let Some(mismatch) = self.mismatch {
+            map.insert::<CreateAddressKey>(mismatch);
+        }
+        map.insert::<CreationsKey>(self.creations);
+    }
+}
+
+pub struct CreationsKey;
+
+impl typemap::Key for CreationsKey {
+    type Value = Creations;
+}
+
+pub struct CreateAddressKey;
+
+impl typemap::Key for CreateAddressKey {
+    type Value = CreateAddressMismatch;
+}
+
+impl CallTracer for CreationTracer {
+    fn record_call(&mut self, _params: &ActionParams) {
+        self.pending = None;
+        self.depth += 1;
+        self.frames.push(BTreeMap::new());
+    }
+
+    fn record_call_result(&mut self, result: &FrameResult) {
+        self.depth -= 1;
+        self.close_frame(result);
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        if let Some(expected) = self.pending.take() {
+            if expected != params.address && self.mismatch.is_none() {
+                self.mismatch = Some(CreateAddressMismatch {
+                    depth: self.depth,
+                    expected,
+                    got: params.address,
+                });
+            }
+        }
+        self.depth += 1;
+        // The frame of a creation transaction has no creator
+        if let (Some(creator), Some(frame)) =
+            (self.creating.take(), self.frames.last_mut())
+        {
+            *frame.entry(creator).or_default() += 1;
+        }
+        self.frames.push(BTreeMap::new());
+    }
+
+    fn record_create_result(&mut self, result: &FrameResult) {
+        self.depth -= 1;
+        self.close_frame(result);
+    }
+}
+
+impl CheckpointTracer for CreationTracer {}
+
+impl InternalTransferTracer for CreationTracer {}
+
+impl StorageTracer for CreationTracer {}
+
+impl OpcodeTracer for CreationTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        // A CREATE2 that failed before opening a frame is not checked
+        self.pending = None;
+        self.settle_creating();
+
+        let opcode = interp.current_opcode();
+        if opcode == CREATE || opcode == CREATE2 {
+            self.creating = Some(interp.contract_address());
+        }
+        if opcode != CREATE2 {
+            return;
+        }
+        let stack = interp.stack();
+        let arg = |i: usize| stack.len().checked_sub(i + 1).map(|i| stack[i]);
+        let (Some(offset), Some(size), Some(salt)) = (arg(1), arg(2), arg(3))
+        else {
+            return;
+        };
+        let Some(code) = read_memory(interp.mem(), offset, size) else {
+            return;
+        };
+        self.pending = Some(derive_address(
+            CreateContractAddressType::FromSenderSaltAndCodeHash(
+                H256::from_uint(&salt),
+            ),
+            &interp.contract_address(),
+            &U256::zero(),
+            &code,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::test_interp::{stack, Step},
+        derive_address, read_memory, CreationTracer, CREATE, CREATE2,
+    };
+    use cfx_executor::{
+        observer::{CallTracer, OpcodeTracer},
+        stack::FrameReturn,
+    };
+    use cfx_types::{Address, CreateContractAddressType, Space, H256, U256};
+    use cfx_vm_types::{ActionParams, Error, ReturnData};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        str::FromStr,
+    };
+
+    const STOP: u8 = 0x00;
+
+    /// Runs `opcode` in `contract`, with the arguments of a CREATE2 of an
+    /// empty init code.
+    fn exec(tracer: &mut CreationTracer, contract: Address, opcode: u8) {
+        tracer.step(&Step {
+            opcode,
+            stack: stack(&[0, 0, 0, 0]),
+            contract,
+            ..Default::default()
+        });
+    }
+
+    fn returned(apply_state: bool) -> FrameReturn {
+        FrameReturn {
+            space: Space::Ethereum,
+            gas_left: U256::zero(),
+            apply_state,
+            return_data: ReturnData::empty(),
+            create_address: None,
+            substate: None,
+        }
+    }
+
+    #[test]
+    fn creations_of_reverted_frames_are_not_counted() {
+        let outer = Address::from_low_u64_be(0x1000);
+        let callee = Address::from_low_u64_be(0x2000);
+        let created = Address::from_low_u64_be(0x3000);
+        let mut tracer = CreationTracer::new(None);
+
+        tracer.record_call(&ActionParams::default());
+        // A CREATE whose frame reverts still bumps the nonce of its creator,
+        // not the ones of the creations in it
+        exec(&mut tracer, outer, CREATE);
+        tracer.record_create(&ActionParams::default());
+        exec(&mut tracer, created, CREATE);
+        tracer.record_create(&ActionParams::default());
+        tracer.record_create_result(&Ok(returned(true)));
+        tracer.record_create_result(&Ok(returned(false)));
+        // Nor do the creations of a reverted sub-call
+        tracer.record_call(&ActionParams::default());
+        exec(&mut tracer, callee, CREATE);
+        tracer.record_create(&ActionParams::default());
+        tracer.record_create_result(&Ok(returned(true)));
+        tracer.record_call_result(&Err(Error::Reverted));
+        exec(&mut tracer, outer, CREATE2);
+        tracer.record_create(&ActionParams::default());
+        tracer.record_create_result(&Ok(returned(true)));
+        tracer.record_call_result(&Ok(returned(true)));
+
+        assert_eq!(tracer.creations.counts, BTreeMap::from([(outer, 2)]));
+        assert!(tracer.creations.unsure.is_empty());
+    }
+
+    #[test]
+    fn create_without_a_frame_is_unsure() {
+        let outer = Address::from_low_u64_be(0x1000);
+        let callee = Address::from_low_u64_be(0x2000);
+        let mut tracer = CreationTracer::new(None);
+
+        tracer.record_call(&ActionParams::default());
+        // A CREATE2 failing on an address collision opens no frame
+        exec(&mut tracer, outer, CREATE2);
+        exec(&mut tracer, outer, STOP);
+        // Neither does one at the end of its frame
+        tracer.record_call(&ActionParams::default());
+        exec(&mut tracer, callee, CREATE2);
+        tracer.record_call_result(&Ok(returned(true)));
+        tracer.record_call_result(&Ok(returned(true)));
+
+        assert!(tracer.creations.counts.is_empty());
+        assert_eq!(tracer.creations.unsure, BTreeSet::from([outer, callee]));
+        // Not checked against the address it would have created at
+        assert!(tracer.mismatch.is_none());
+    }
+
+    #[test]
+    fn addresses_are_derived_as_ethereum_does() {
+        // Examples of EIP-1014
+        let create2 = derive_address(
+            CreateContractAddressType::FromSenderSaltAndCodeHash(H256::zero()),
+            &Address::zero(),
+            &U256::zero(),
+            &[0x00],
+        );
+        assert_eq!(
+            create2,
+            Address::from_str("4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38")
+                .unwrap()
+        );
+
+        let create = derive_address(
+            CreateContractAddressType::FromSenderNonce,
+            &Address::from_str("a94f5374fce5edbc8e2a8697c15331677e6ebf0b")
+                .unwrap(),
+            &U256::zero(),
+            &[],
+        );
+        assert_eq!(
+            create,
+            Address::from_str("6295ee1b4f6dd65047762f924ecd367c17eabf8f")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn init_code_is_zero_past_the_memory() {
+        let mem = [1, 2, 3];
+        assert_eq!(
+            read_memory(&mem, 1.into(), 4.into()),
+            Some(vec![2, 3, 0, 0])
+        );
+        assert_eq!(read_memory(&mem, 5.into(), 2.into()), Some(vec![0, 0]));
+        assert_eq!(read_memory(&mem, U256::MAX, 0.into()), Some(vec![]));
+        assert_eq!(read_memory(&mem, 0.into(), U256::MAX), None);
+    }
+}
@@ -0,0 +1,237 @@
+use super::super::{
+    output_dir::{Artifact, OutputDir},
+    unit_tester::TestId,
+};
+use cfx_executor::{
+    executive::ExecutionOutcome,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::U256;
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use typemap::ShareDebugMap;
+
+/// Collects one EIP-3155 JSON line per executed opcode.
+///
+/// A line is emitted when the opcode finishes, so `gasCost` is the gas
+/// actually deducted by the opcode (including the gas forwarded by the
+/// CALL/CREATE family). The interpreter does not expose the refund counter,
+/// so the `refund` of a step is always 0. The refund of the transaction is
+/// in its summary line, see `summary`.
+#[derive(Default)]
+pub struct Eip3155Tracer {
+    depth: usize,
+    pending: Option<PendingStep>,
+    lines: Vec<String>,
+}
+
+struct PendingStep {
+    pc: u64,
+    op: u8,
+    gas: U256,
+    mem_size: usize,
+    stack: Vec<String>,
+    depth: usize,
+}
+
+impl Eip3155Tracer {
+    pub fn drain(self) -> Vec<String> { self.lines }
+}
+
+impl DrainTrace for Eip3155Tracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        map.insert::<Eip3155TraceKey>(self.drain());
+    }
+}
+
+pub struct Eip3155TraceKey;
+
+impl typemap::Key for Eip3155TraceKey {
+    type Value = Vec<String>;
+}
+
+impl CheckpointTracer for Eip3155Tracer {}
+
+impl InternalTransferTracer for Eip3155Tracer {}
+
+impl StorageTracer for Eip3155Tracer {}
+
+impl CallTracer for Eip3155Tracer {
+    fn record_call(&mut self, _params: &ActionParams) { self.depth += 1; }
+
+    fn record_call_result(&mut self, _result: &FrameResult) { self.depth -= 1; }
+
+    fn record_create(&mut self, _params: &ActionParams) { self.depth += 1; }
+
+    fn record_create_result(&mut self, _result: &FrameResult) {
+        self.depth -= 1;
+    }
+}
+
+impl OpcodeTracer for Eip3155Tracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        self.pending = Some(PendingStep {
+            pc: interp.program_counter(),
+            op: interp.current_opcode(),
+            gas: interp.gas_remainning(),
+            mem_size: interp.mem().len(),
+            stack: interp.stack().iter().map(|v| format!("{:#x}", v)).collect(),
+            depth: self.depth,
+        });
+    }
+
+    fn step_end(&mut self, interp: &dyn InterpreterInfo) {
+        let Some(step) = self.pending.take() else {
+            return;
+        };
+        let gas_cost = step.gas.saturating_sub(interp.gas_remainning());
+        let line = json!({
+            "pc": step.pc,
+            "op": step.op,
+            "gas": format!("{:#x}", step.gas),
+            "gasCost": format!("{:#x}", gas_cost),
+            "memSize": step.mem_size,
+            "stack": step.stack,
+            "depth": step.depth,
+            "refund": 0,
+        });
+        self.lines.push(line.to_string());
+    }
+}
+
+/// Destination of the EIP-3155 traces. Writes are serialized so that the
+/// lines of one transaction are never interleaved with another one.
+pub struct TraceSink(Target);
+
+enum Target {
+    Stream(Mutex<Box<dyn Write + Send>>),
+    /// One file per test, see `OutputDir`.
+    Dir(Arc<OutputDir>),
+}
+
+impl TraceSink {
+    pub fn stdout() -> Self {
+        TraceSink(Target::Stream(Mutex::new(Box::new(io::stdout()))))
+    }
+
+    pub fn file(path: &Path) -> io::Result<Self> {
+        Ok(TraceSink(Target::Stream(Mutex::new(Box::new(
+            File::create(path)?,
+        )))))
+    }
+
+    /// Writes the traces of every test to its own file of `output`.
+    pub fn dir(output: Arc<OutputDir>) -> Self {
+        TraceSink(Target::Dir(output))
+    }
+
+    /// The directory the traces are written to, if not a single stream.
+    pub fn output_dir(&self) -> Option<&OutputDir> {
+        match &self.0 {
+            Target::Stream(_) => None,
+            Target::Dir(output) => Some(output.as_ref()),
+        }
+    }
+
+    /// Writes the opcode lines carried by `outcome`, a transaction of the
+    /// test `id`, followed by the summary line. Outcomes that never reached
+    /// the interpreter write nothing.
+    pub fn write_outcome(&self, id: &TestId, outcome: &ExecutionOutcome) {
+        let (Some(executed), Some(summary)) =
+            (outcome.try_as_executed(), summary(outcome))
+        else {
+            return;
+        };
+
+        let write = |writer: &mut dyn Write| {
+            executed
+                .ext_result
+                .get::<Eip3155TraceKey>()
+                .into_iter()
+                .flatten()
+                .try_for_each(|line| writeln!(writer, "{}", line))
+                .and_then(|_| writeln!(writer, "{}", summary))
+                .and_then(|_| writer.flush())
+        };
+        let res = match &self.0 {
+            Target::Stream(writer) => {
+                write(&mut **writer.lock().expect("trace sink poisoned"))
+            }
+            Target::Dir(output) => output
+                .open(Artifact::Trace, &id.to_string())
+                .and_then(|file| write(&mut BufWriter::new(file))),
+        };
+        if let Err(e) = res {
+            warn!("Failed to write trace of {}: {}", id, e);
+        }
+    }
+}
+
+/// The last line of the trace of an executed transaction: its output, the
+/// gas used, the gas charged to the sender and the refund applied, with the
+/// error it failed with if any.
+fn summary(outcome: &ExecutionOutcome) -> Option<Value> {
+    let executed = outcome.try_as_executed()?;
+    let mut summary = json!({
+        "output": format!("0x{}", hex::encode(&executed.output)),
+        "gasUsed": format!("{:#x}", executed.gas_used),
+        "gasCharged": format!("{:#x}", executed.gas_charged),
+        "refund": format!("{:#x}", executed.refund_applied),
+    });
+    if let ExecutionOutcome::ExecutionErrorBumpNonce(error, _) = outcome {
+        summary["error"] = format!("{:?}", error).into();
+    }
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::summary;
+    use cfx_executor::executive::{Executed, ExecutionOutcome};
+    use cfx_types::U256;
+    use serde_json::json;
+
+    #[test]
+    fn summary_carries_the_refund_and_the_gas_charged() {
+        // 30000 gas before a refund of 4800, charged in full
+        let executed = Executed {
+            base_gas: 21000,
+            gas_used: U256::from(25200),
+            gross_gas_used: U256::from(30000),
+            refund_counter: U256::from(4800),
+            refund_applied: U256::from(4800),
+            fee: U256::from(30000),
+            burnt_fee: None,
+            gas_charged: U256::from(30000),
+            gas_sponsor_paid: false,
+            logs: vec![],
+            storage_sponsor_paid: false,
+            storage_collateralized: vec![],
+            storage_released: vec![],
+            contracts_created: vec![],
+            output: vec![0xab],
+            ext_result: typemap::ShareDebugMap::custom(),
+        };
+        assert_eq!(
+            summary(&ExecutionOutcome::Finished(executed)),
+            Some(json!({
+                "output": "0xab",
+                "gasUsed": "0x6270",
+                "gasCharged": "0x7530",
+                "refund": "0x12c0",
+            }))
+        );
+    }
+}
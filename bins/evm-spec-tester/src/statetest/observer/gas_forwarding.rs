@@ -0,0 +1,305 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::U256;
+use cfx_vm_interpreter::INSTRUCTIONS_CANCUN;
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use eest_types::SpecId;
+use std::fmt;
+use typemap::ShareDebugMap;
+
+const CREATE: u8 = 0xf0;
+const CALL: u8 = 0xf1;
+const CALLCODE: u8 = 0xf2;
+const DELEGATECALL: u8 = 0xf4;
+const CREATE2: u8 = 0xf5;
+const STATICCALL: u8 = 0xfa;
+
+/// Gas added to the callee of a CALL/CALLCODE transferring value, and not
+/// charged to the caller.
+const CALL_STIPEND: u64 = 2300;
+
+/// A CALL/CREATE whose callee did not get the gas of the EIP-150 rule, or
+/// the whole requested gas before Tangerine Whistle.
+#[derive(Clone, Debug)]
+pub struct GasForwardingViolation {
+    /// Depth of the calling frame, 1 for the frame of the transaction.
+    pub depth: usize,
+    pub opcode: u8,
+    pub pc: u64,
+    /// Gas of the callee, with the stipend.
+    pub got: U256,
+    /// `None` if the callee got more gas than charged to the caller.
+    pub expected: Option<U256>,
+}
+
+impl GasForwardingViolation {
+    pub fn of(executed: &Executed) -> Option<Self> {
+        executed.ext_result.get::<GasForwardingKey>().cloned()
+    }
+}
+
+impl fmt::Display for GasForwardingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = INSTRUCTIONS_CANCUN[self.opcode as usize]
+            .map_or("UNKNOWN", |info| info.name);
+        write!(
+            f,
+            "{} at pc {}, depth {}: callee got {} gas, ",
+            name, self.pc, self.depth, self.got
+        )?;
+        match self.expected {
+            Some(expected) => write!(f, "expected {}", expected),
+            None => write!(f, "more than charged to the caller"),
+        }
+    }
+}
+
+/// A CALL/CREATE between its step and the frame it opens.
+struct PendingCall {
+    opcode: u8,
+    pc: u64,
+    depth: usize,
+    gas: U256,
+    /// Gas argument of the CALL family.
+    requested: Option<U256>,
+    stipend: U256,
+    /// Gas deducted by the opcode, including the forwarded gas but not the
+    /// stipend, known once it is executed.
+    charged: Option<U256>,
+}
+
+/// Checks the gas given to the callee at every CALL/CREATE against the
+/// forwarding rule of the spec. The interpreter charges the opcode cost and
+/// the forwarded gas at once, so the cost is recovered from the gas the
+/// callee got.
+pub struct GasForwardingTracer {
+    spec: SpecId,
+    depth: usize,
+    pending: Option<PendingCall>,
+    /// The first violation, later ones may only follow from it.
+    violation: Option<GasForwardingViolation>,
+}
+
+impl GasForwardingTracer {
+    pub fn new(spec: SpecId) -> Self {
+        GasForwardingTracer {
+            spec,
+            depth: 0,
+            pending: None,
+            violation: None,
+        }
+    }
+
+    fn open_frame(&mut self, params: &ActionParams) {
+        if let Some(call) = self.pending.take() {
+            if self.violation.is_none() {
+                self.violation = self.check(call, params.gas);
+            }
+        }
+        self.depth += 1;
+    }
+
+    fn close_frame(&mut self) {
+        self.pending = None;
+        self.depth -= 1;
+    }
+
+    fn check(
+        &self, call: PendingCall, got: U256,
+    ) -> Option<GasForwardingViolation> {
+        let charged = call.charged?;
+        let expected = expected_gas(self.spec, &call, charged, got);
+        (expected != Some(got)).then_some(GasForwardingViolation {
+            depth: call.depth,
+            opcode: call.opcode,
+            pc: call.pc,
+            got,
+            expected,
+        })
+    }
+}
+
+/// The gas the callee of `call` should get, as the callee got `got`. `None`
+/// if `got`, without the stipend, is more than `charged`.
+fn expected_gas(
+    spec: SpecId, call: &PendingCall, charged: U256, got: U256,
+) -> Option<U256> {
+    let forwarded = got.checked_sub(call.stipend)?;
+    let cost = charged.checked_sub(forwarded)?;
+    let available = call.gas.saturating_sub(cost);
+    let provided = if spec >= SpecId::TANGERINE {
+        let cap = available - available / 64;
+        call.requested.map_or(cap, |requested| requested.min(cap))
+    } else {
+        call.requested.unwrap_or(available)
+    };
+    Some(provided + call.stipend)
+}
+
+impl DrainTrace for GasForwardingTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        if let Some(violation) = self.violation {
+            map.insert::<GasForwardingKey>(violation);
+        }
+    }
+}
+
+pub struct GasForwardingKey;
+
+impl typemap::Key for GasForwardingKey {
+    type Value = GasForwardingViolation;
+}
+
+impl CallTracer for GasForwardingTracer {
+    fn record_call(&mut self, params: &ActionParams) {
+        self.open_frame(params);
+    }
+
+    fn record_call_result(&mut self, _result: &FrameResult) {
+        self.close_frame();
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        self.open_frame(params);
+    }
+
+    fn record_create_result(&mut self, _result: &FrameResult) {
+        self.close_frame();
+    }
+}
+
+impl CheckpointTracer for GasForwardingTracer {}
+
+impl InternalTransferTracer for GasForwardingTracer {}
+
+impl StorageTracer for GasForwardingTracer {}
+
+impl OpcodeTracer for GasForwardingTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        // A call that failed before opening a frame is not checked
+        self.pending = None;
+
+        let opcode = interp.current_opcode();
+        let stack = interp.stack();
+        let arg = |i: usize| stack.len().checked_sub(i + 1).map(|i| stack[i]);
+        let (requested, value) = match opcode {
+            CALL | CALLCODE => (arg(0), arg(2)),
+            DELEGATECALL | STATICCALL => (arg(0), None),
+            CREATE | CREATE2 => (None, None),
+            _ => return,
+        };
+        let stipend = match value {
+            Some(value) if !value.is_zero() => U256::from(CALL_STIPEND),
+            _ => U256::zero(),
+        };
+        self.pending = Some(PendingCall {
+            opcode,
+            pc: interp.program_counter(),
+            depth: self.depth,
+            gas: interp.gas_remainning(),
+            requested,
+            stipend,
+            charged: None,
+        });
+    }
+
+    fn step_end(&mut self, interp: &dyn InterpreterInfo) {
+        if let Some(call) = &mut self.pending {
+            call.charged =
+                Some(call.gas.saturating_sub(interp.gas_remainning()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::test_interp::{stack, Step},
+        GasForwardingTracer, GasForwardingViolation, CALL, CREATE,
+    };
+    use cfx_executor::observer::{CallTracer, OpcodeTracer};
+    use cfx_types::U256;
+    use cfx_vm_types::ActionParams;
+    use eest_types::SpecId;
+
+    /// Runs `step` in the frame of the transaction, leaving `gas_left` to
+    /// the caller and `got` to the callee, and returns the violation found.
+    fn forward(
+        spec: SpecId, step: &Step, gas_left: u64, got: u64,
+    ) -> Option<GasForwardingViolation> {
+        let mut tracer = GasForwardingTracer::new(spec);
+        tracer.record_call(&ActionParams::default());
+        tracer.step(step);
+        tracer.step_end(&Step {
+            gas: gas_left.into(),
+            ..step.clone()
+        });
+        tracer.record_call(&ActionParams {
+            gas: got.into(),
+            ..Default::default()
+        });
+        tracer.violation
+    }
+
+    #[test]
+    fn call_forwards_all_but_one_64th_with_the_stipend() {
+        // CALL of 1 wei to a warm account asking for 1000000 gas out of
+        // 100000: 100 + 9000 of cost leave 90900, of which 89480 are
+        // forwarded, with 2300 of stipend
+        let call = Step {
+            opcode: CALL,
+            pc: 0x20,
+            stack: stack(&[0, 0, 0, 0, 1, 0x1000, 1_000_000]),
+            gas: U256::from(100_000),
+            ..Default::default()
+        };
+        assert!(forward(SpecId::CANCUN, &call, 1420, 91_780).is_none());
+
+        // All the gas left forwarded
+        let violation = forward(SpecId::CANCUN, &call, 0, 93_200).unwrap();
+        assert_eq!(
+            (violation.depth, violation.opcode, violation.pc),
+            (1, CALL, 0x20)
+        );
+        assert_eq!(violation.got, U256::from(93_200));
+        assert_eq!(violation.expected, Some(U256::from(91_780)));
+
+        // Asking for 5000 gets 5000, and 7300 with the stipend
+        let requested = Step {
+            stack: stack(&[0, 0, 0, 0, 1, 0x1000, 5000]),
+            ..call.clone()
+        };
+        assert!(forward(SpecId::CANCUN, &requested, 85_900, 7300).is_none());
+        let violation =
+            forward(SpecId::CANCUN, &requested, 85_899, 7301).unwrap();
+        assert_eq!(violation.expected, Some(U256::from(7300)));
+
+        // More than charged to the caller
+        let violation = forward(SpecId::CANCUN, &call, 1420, 101_000).unwrap();
+        assert_eq!(violation.expected, None);
+    }
+
+    #[test]
+    fn create_forwards_all_before_tangerine() {
+        // 32000 of cost leave 68000 out of 100000, 66938 of which are
+        // forwarded since Tangerine Whistle
+        let create = Step {
+            opcode: CREATE,
+            stack: stack(&[0, 0, 0]),
+            gas: U256::from(100_000),
+            ..Default::default()
+        };
+        assert!(forward(SpecId::HOMESTEAD, &create, 0, 68_000).is_none());
+        assert!(forward(SpecId::CANCUN, &create, 1062, 66_938).is_none());
+        let violation = forward(SpecId::CANCUN, &create, 0, 68_000).unwrap();
+        assert_eq!(violation.expected, Some(U256::from(66_938)));
+    }
+}
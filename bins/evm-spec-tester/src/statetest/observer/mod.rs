@@ -0,0 +1,102 @@
+mod checks;
+mod coinbase_access;
+mod coverage;
+mod creation;
+mod eip3155;
+mod gas_forwarding;
+mod precompile;
+mod selfdestruct;
+mod storage_writes;
+#[cfg(test)]
+mod test_interp;
+mod touch;
+mod transient_storage;
+mod warm;
+
+pub use checks::CheckTracers;
+pub use coinbase_access::{CoinbaseAccessMismatch, CoinbaseAccessTracer};
+pub use coverage::{OpcodeCoverage, OpcodeCoverageTracer};
+pub use creation::{
+    creations, CreateAddressMismatch, CreationTracer, Creations,
+};
+pub use eip3155::{Eip3155TraceKey, Eip3155Tracer, TraceSink};
+pub use gas_forwarding::{GasForwardingTracer, GasForwardingViolation};
+pub use precompile::{PrecompileCalls, PrecompileTracer};
+pub use selfdestruct::{selfdestructs, Selfdestruct, SelfdestructTracer};
+pub use storage_writes::{
+    storage_writes, StorageWritesKey, StorageWritesTracer,
+};
+pub use touch::{touches, TouchTracer};
+pub use transient_storage::{
+    transient_storage, StaleTransientLoad, TransientStorageKey,
+    TransientStorageTrace, TransientStorageTracer,
+};
+pub use warm::{warm_accounts, WarmAccountsTracer};
+
+use super::{output_dir::OutputDir, perf::PerfTotals, unit_tester::TestId};
+use cfx_executor::{
+    executive::ExecutionOutcome,
+    executive_observer::{AsTracer, DrainTrace, TracerTrait},
+};
+use cfx_vm_tracer_derive::{AsTracer, DrainTrace};
+use eest_types::SpecId;
+use std::sync::Arc;
+
+/// Observer attached to every transaction executed by the tester. Each field
+/// is an optional tracer that only costs something when enabled.
+#[derive(AsTracer, DrainTrace)]
+pub struct Observer {
+    pub eip3155: Option<Eip3155Tracer>,
+    pub coverage: Option<OpcodeCoverageTracer>,
+    /// The tracers of the checks of the test, see `Observer::checks`.
+    pub checks: Option<CheckTracers>,
+}
+
+impl Observer {
+    /// Enables the tracers whose output is collected by `sinks`.
+    pub fn new(sinks: &Sinks) -> Self {
+        Observer {
+            eip3155: sinks.trace.is_some().then(Eip3155Tracer::default),
+            coverage: sinks
+                .coverage
+                .is_some()
+                .then(OpcodeCoverageTracer::default),
+            checks: Some(CheckTracers {
+                warm_accounts: Some(WarmAccountsTracer::default()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The tracers of the checks, to enable one of them.
+    pub fn checks(&mut self) -> &mut CheckTracers {
+        self.checks.get_or_insert_with(Default::default)
+    }
+}
+
+/// Where the tracer outputs of a run go, shared by every test.
+#[derive(Clone, Default)]
+pub struct Sinks {
+    pub trace: Option<Arc<TraceSink>>,
+    pub coverage: Option<Arc<OpcodeCoverage>>,
+    /// The executor time of the transactions, see `PerfTotals::measure`.
+    pub perf: Option<Arc<PerfTotals>>,
+    /// Where the post states of the passing tests are dumped, see
+    /// `UnitOptions::dump_state`.
+    pub state_dumps: Option<Arc<OutputDir>>,
+    /// Where the blessed fixtures are written instead of in place.
+    pub blessed: Option<Arc<OutputDir>>,
+}
+
+impl Sinks {
+    pub fn write_outcome(
+        &self, id: &TestId, spec: SpecId, outcome: &ExecutionOutcome,
+    ) {
+        if let Some(sink) = &self.trace {
+            sink.write_outcome(id, outcome);
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.record(spec, outcome);
+        }
+    }
+}
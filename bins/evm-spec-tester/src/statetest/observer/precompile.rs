@@ -0,0 +1,158 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::{Address, U256};
+use cfx_vm_types::ActionParams;
+use std::fmt;
+use typemap::ShareDebugMap;
+
+/// A call to a precompile and the gas it charged.
+#[derive(Clone, Debug)]
+pub struct PrecompileCall {
+    pub address: Address,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+    /// The input of a successful call to a precompile whose gas depends on
+    /// it, see `priced_by_input`.
+    pub input: Option<Vec<u8>>,
+}
+
+/// The precompile calls of a transaction, in call order.
+#[derive(Clone, Debug, Default)]
+pub struct PrecompileCalls(pub Vec<PrecompileCall>);
+
+impl PrecompileCalls {
+    pub fn of(executed: Option<&Executed>) -> Self {
+        executed
+            .and_then(|e| e.ext_result.get::<PrecompileCallsKey>())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for PrecompileCalls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "precompile calls:")?;
+        writeln!(
+            f,
+            "  {:<42}  {:>12}  {:>12}",
+            "address", "gas limit", "gas used"
+        )?;
+        for call in &self.0 {
+            writeln!(
+                f,
+                "  {:<42}  {:>12}  {:>12}",
+                format!("{:?}", call.address),
+                call.gas_limit,
+                call.gas_used
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// MODEXP and ECPAIRING, whose gas is computed from their input.
+pub fn priced_by_input(address: &Address) -> bool {
+    [5, 8].map(Address::from_low_u64_be).contains(address)
+}
+
+/// Records the gas charged by every precompile call.
+#[derive(Default)]
+pub struct PrecompileTracer {
+    /// The open frames, see `Frame`.
+    frames: Vec<Frame>,
+    calls: Vec<PrecompileCall>,
+}
+
+struct Frame {
+    gas_limit: U256,
+    /// The address of the precompile, if the frame calls one.
+    precompile: Option<Address>,
+    /// The input, kept for the precompiles priced by it.
+    input: Option<Vec<u8>>,
+}
+
+impl PrecompileTracer {
+    fn open_frame(&mut self, params: &ActionParams) {
+        let input = priced_by_input(&params.code_address)
+            .then(|| params.data.clone().unwrap_or_default());
+        self.frames.push(Frame {
+            gas_limit: params.gas,
+            precompile: None,
+            input,
+        });
+    }
+
+    fn close_frame(&mut self, result: &FrameResult) {
+        let Some(Frame {
+            gas_limit,
+            precompile: Some(address),
+            input,
+        }) = self.frames.pop()
+        else {
+            return;
+        };
+        let gas_used = match result {
+            Ok(r) => gas_limit.saturating_sub(r.gas_left),
+            Err(_) => gas_limit,
+        };
+        self.calls.push(PrecompileCall {
+            address,
+            gas_limit,
+            gas_used,
+            input: input.filter(|_| result.is_ok()),
+        });
+    }
+}
+
+impl DrainTrace for PrecompileTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        map.insert::<PrecompileCallsKey>(PrecompileCalls(self.calls));
+    }
+}
+
+pub struct PrecompileCallsKey;
+
+impl typemap::Key for PrecompileCallsKey {
+    type Value = PrecompileCalls;
+}
+
+impl CallTracer for PrecompileTracer {
+    fn record_call(&mut self, params: &ActionParams) {
+        self.open_frame(params);
+    }
+
+    fn record_call_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        self.open_frame(params);
+    }
+
+    fn record_create_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+
+    fn record_builtin_call(&mut self, address: &Address) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.precompile = Some(*address);
+        }
+    }
+}
+
+impl CheckpointTracer for PrecompileTracer {}
+
+impl InternalTransferTracer for PrecompileTracer {}
+
+impl StorageTracer for PrecompileTracer {}
+
+impl OpcodeTracer for PrecompileTracer {}
@@ -0,0 +1,126 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::{Address, U256};
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use std::collections::HashSet;
+use typemap::ShareDebugMap;
+
+const SELFDESTRUCT: u8 = 0xff;
+
+/// A SELFDESTRUCT whose frame was not reverted.
+#[derive(Clone, Debug)]
+pub struct Selfdestruct {
+    pub contract: Address,
+    /// Whether the contract was created by the transaction, which EIP-6780
+    /// still lets delete itself.
+    pub created_in_tx: bool,
+    /// Whether the executor marked the contract for deletion, rather than
+    /// only sending its balance.
+    pub deleted: bool,
+}
+
+/// The SELFDESTRUCTs of a transaction, in their execution order.
+pub fn selfdestructs(executed: &Executed) -> &[Selfdestruct] {
+    executed
+        .ext_result
+        .get::<SelfdestructKey>()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Records the SELFDESTRUCTs of the transaction and whether they deleted
+/// their contract, so that they can be checked against the rule of the fork.
+#[derive(Default)]
+pub struct SelfdestructTracer {
+    created: HashSet<Address>,
+    /// The SELFDESTRUCTs of every open frame, dropped if it reverts.
+    frames: Vec<Vec<Selfdestruct>>,
+    kept: Vec<Selfdestruct>,
+}
+
+impl SelfdestructTracer {
+    fn close_frame(&mut self, result: &FrameResult) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if !result.as_ref().map_or(false, |r| r.apply_state) {
+            return;
+        }
+        match self.frames.last_mut() {
+            Some(parent) => parent.extend(frame),
+            None => self.kept.extend(frame),
+        }
+    }
+}
+
+impl DrainTrace for SelfdestructTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        if !self.kept.is_empty() {
+            map.insert::<SelfdestructKey>(self.kept);
+        }
+    }
+}
+
+pub struct SelfdestructKey;
+
+impl typemap::Key for SelfdestructKey {
+    type Value = Vec<Selfdestruct>;
+}
+
+impl CallTracer for SelfdestructTracer {
+    fn record_call(&mut self, _params: &ActionParams) {
+        self.frames.push(vec![]);
+    }
+
+    fn record_call_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        self.created.insert(params.address);
+        self.frames.push(vec![]);
+    }
+
+    fn record_create_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+}
+
+impl CheckpointTracer for SelfdestructTracer {}
+
+impl InternalTransferTracer for SelfdestructTracer {}
+
+impl StorageTracer for SelfdestructTracer {}
+
+impl OpcodeTracer for SelfdestructTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        if interp.current_opcode() != SELFDESTRUCT {
+            return;
+        }
+        let contract = interp.contract_address();
+        if let Some(frame) = self.frames.last_mut() {
+            frame.push(Selfdestruct {
+                contract,
+                created_in_tx: self.created.contains(&contract),
+                deleted: false,
+            });
+        }
+    }
+
+    fn selfdestruct(
+        &mut self, contract: &Address, _target: &Address, _value: U256,
+    ) {
+        let last = self.frames.last_mut().and_then(|frame| frame.last_mut());
+        if let Some(last) = last.filter(|last| last.contract == *contract) {
+            last.deleted = true;
+        }
+    }
+}
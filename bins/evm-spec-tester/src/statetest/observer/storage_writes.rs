@@ -0,0 +1,61 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+};
+use cfx_types::{Address, U256};
+use cfx_vm_types::InterpreterInfo;
+use std::collections::BTreeSet;
+use typemap::ShareDebugMap;
+
+const SSTORE: u8 = 0x55;
+
+/// The storage slots written by SSTORE during the transaction, in any frame,
+/// as `(contract, key)`.
+pub fn storage_writes(
+    executed: &Executed,
+) -> Option<&BTreeSet<(Address, U256)>> {
+    executed.ext_result.get::<StorageWritesKey>()
+}
+
+/// Records the slots written by the transaction, reverted or not, so that a
+/// reverted transaction can be checked to have rolled all of them back.
+#[derive(Default)]
+pub struct StorageWritesTracer {
+    slots: BTreeSet<(Address, U256)>,
+}
+
+impl DrainTrace for StorageWritesTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        map.insert::<StorageWritesKey>(self.slots);
+    }
+}
+
+pub struct StorageWritesKey;
+
+impl typemap::Key for StorageWritesKey {
+    type Value = BTreeSet<(Address, U256)>;
+}
+
+impl CallTracer for StorageWritesTracer {}
+
+impl CheckpointTracer for StorageWritesTracer {}
+
+impl InternalTransferTracer for StorageWritesTracer {}
+
+impl StorageTracer for StorageWritesTracer {}
+
+impl OpcodeTracer for StorageWritesTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        if interp.current_opcode() != SSTORE {
+            return;
+        }
+        if let Some(&key) = interp.stack().last() {
+            self.slots.insert((interp.contract_address(), key));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use cfx_types::{Address, U256};
+use cfx_vm_types::InterpreterInfo;
+
+/// The interpreter at one instruction, for the tests of the tracers.
+#[derive(Clone, Default)]
+pub struct Step {
+    pub opcode: u8,
+    pub pc: u64,
+    /// With the top last.
+    pub stack: Vec<U256>,
+    pub gas: U256,
+    pub contract: Address,
+    pub mem: Vec<u8>,
+    pub return_stack: Vec<usize>,
+}
+
+impl InterpreterInfo for Step {
+    fn gas_remainning(&self) -> U256 { self.gas }
+
+    fn program_counter(&self) -> u64 { self.pc }
+
+    fn current_opcode(&self) -> u8 { self.opcode }
+
+    fn opcode(&self, _pc: u64) -> Option<u8> { Some(self.opcode) }
+
+    fn mem(&self) -> &Vec<u8> { &self.mem }
+
+    fn stack(&self) -> &Vec<U256> { &self.stack }
+
+    fn return_stack(&self) -> &Vec<usize> { &self.return_stack }
+
+    fn contract_address(&self) -> Address { self.contract }
+}
+
+/// `words` as a stack, with the top last.
+pub fn stack(words: &[u64]) -> Vec<U256> {
+    words.iter().map(|&word| U256::from(word)).collect()
+}
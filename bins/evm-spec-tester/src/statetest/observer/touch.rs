@@ -0,0 +1,157 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::{Address, BigEndianHash, H256};
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use std::collections::BTreeSet;
+use typemap::ShareDebugMap;
+
+const SELFDESTRUCT: u8 = 0xff;
+
+/// The accounts touched by the frames of a transaction that were not
+/// reverted, if they were traced.
+pub fn touches(executed: &Executed) -> Option<&BTreeSet<Address>> {
+    executed.ext_result.get::<TouchesKey>()
+}
+
+/// Records the accounts a transaction touches in the sense of EIP-161, the
+/// ones cleared if they are left empty: the account of every frame it opens
+/// and the beneficiary of every SELFDESTRUCT. The executor only marks the
+/// accounts it writes, not the ones sent a zero value. A touch is reverted
+/// with its frame, as the other changes of the frame.
+#[derive(Default)]
+pub struct TouchTracer {
+    /// The touches of every open frame.
+    frames: Vec<BTreeSet<Address>>,
+    kept: BTreeSet<Address>,
+}
+
+impl TouchTracer {
+    fn open_frame(&mut self, params: &ActionParams) {
+        self.frames.push(BTreeSet::from([params.address]));
+    }
+
+    fn close_frame(&mut self, result: &FrameResult) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if !result.as_ref().map_or(false, |r| r.apply_state) {
+            return;
+        }
+        match self.frames.last_mut() {
+            Some(parent) => parent.extend(frame),
+            None => self.kept.extend(frame),
+        }
+    }
+}
+
+impl DrainTrace for TouchTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        map.insert::<TouchesKey>(self.kept);
+    }
+}
+
+pub struct TouchesKey;
+
+impl typemap::Key for TouchesKey {
+    type Value = BTreeSet<Address>;
+}
+
+impl CallTracer for TouchTracer {
+    fn record_call(&mut self, params: &ActionParams) {
+        self.open_frame(params);
+    }
+
+    fn record_call_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+
+    fn record_create(&mut self, params: &ActionParams) {
+        self.open_frame(params);
+    }
+
+    fn record_create_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+}
+
+impl CheckpointTracer for TouchTracer {}
+
+impl InternalTransferTracer for TouchTracer {}
+
+impl StorageTracer for TouchTracer {}
+
+impl OpcodeTracer for TouchTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        // Also a SELFDESTRUCT that does not delete its contract, which the
+        // executor does not report
+        if interp.current_opcode() != SELFDESTRUCT {
+            return;
+        }
+        let (Some(target), Some(frame)) =
+            (interp.stack().last(), self.frames.last_mut())
+        else {
+            return;
+        };
+        frame.insert(Address::from(H256::from_uint(target)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_interp::Step, TouchTracer, SELFDESTRUCT};
+    use cfx_executor::{
+        observer::{CallTracer, OpcodeTracer},
+        stack::FrameReturn,
+    };
+    use cfx_types::{Address, Space, U256};
+    use cfx_vm_types::{ActionParams, Error, ReturnData};
+    use std::collections::BTreeSet;
+
+    fn call(address: Address) -> ActionParams {
+        ActionParams {
+            address,
+            ..Default::default()
+        }
+    }
+
+    fn succeeded() -> FrameReturn {
+        FrameReturn {
+            space: Space::Ethereum,
+            gas_left: U256::zero(),
+            apply_state: true,
+            return_data: ReturnData::empty(),
+            create_address: None,
+            substate: None,
+        }
+    }
+
+    #[test]
+    fn touches_of_reverted_frames_are_dropped() {
+        let [outer, inner, reverted, beneficiary] =
+            [0x1000, 0x2000, 0x3000, 0x4000].map(Address::from_low_u64_be);
+        let mut tracer = TouchTracer::default();
+
+        tracer.record_call(&call(outer));
+        tracer.record_call(&call(inner));
+        tracer.step(&Step {
+            opcode: SELFDESTRUCT,
+            stack: vec![U256::from(0x4000)],
+            contract: inner,
+            ..Default::default()
+        });
+        tracer.record_call_result(&Ok(succeeded()));
+        tracer.record_call(&call(reverted));
+        tracer.record_call_result(&Err(Error::Reverted));
+        tracer.record_call_result(&Ok(succeeded()));
+
+        assert_eq!(tracer.kept, BTreeSet::from([outer, inner, beneficiary]));
+    }
+}
@@ -0,0 +1,243 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+    stack::FrameResult,
+};
+use cfx_types::{Address, U256};
+use cfx_vm_types::{ActionParams, InterpreterInfo};
+use std::collections::{BTreeSet, HashMap};
+use typemap::ShareDebugMap;
+
+const TLOAD: u8 = 0x5c;
+const TSTORE: u8 = 0x5d;
+
+/// What the transaction did with the transient storage.
+#[derive(Clone, Debug, Default)]
+pub struct TransientStorageTrace {
+    /// The slots written by TSTORE, in any frame, as `(contract, key)`.
+    pub slots: BTreeSet<(Address, U256)>,
+    /// The first TLOAD that did not read the value left by the TSTOREs of
+    /// the frames that were not reverted.
+    pub stale_load: Option<StaleTransientLoad>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StaleTransientLoad {
+    pub contract: Address,
+    pub key: U256,
+    pub got: U256,
+    pub expected: U256,
+}
+
+/// The transient storage of the transaction, if it was traced.
+pub fn transient_storage(
+    executed: &Executed,
+) -> Option<&TransientStorageTrace> {
+    executed.ext_result.get::<TransientStorageKey>()
+}
+
+/// Keeps its own copy of the transient storage of the transaction, rolled
+/// back with the frames that revert as EIP-1153 says, to check every TLOAD
+/// against it. Only enabled from Cancun, before which the opcodes are
+/// BEGINSUB and RETURNSUB.
+#[derive(Default)]
+pub struct TransientStorageTracer {
+    values: HashMap<(Address, U256), U256>,
+    /// The values overwritten in every open frame, restored if it reverts.
+    journals: Vec<Vec<((Address, U256), U256)>>,
+    /// The TLOAD or TSTORE being executed and the gas left before it, which
+    /// the instruction only charges if it does not fail.
+    pending: Option<(Pending, U256)>,
+    trace: TransientStorageTrace,
+}
+
+enum Pending {
+    Load((Address, U256)),
+    Store((Address, U256), U256),
+}
+
+impl TransientStorageTracer {
+    fn close_frame(&mut self, result: &FrameResult) {
+        let Some(journal) = self.journals.pop() else {
+            return;
+        };
+        if result.as_ref().map_or(false, |r| r.apply_state) {
+            if let Some(parent) = self.journals.last_mut() {
+                parent.extend(journal);
+            }
+            return;
+        }
+        for (slot, old) in journal.into_iter().rev() {
+            self.values.insert(slot, old);
+        }
+    }
+
+    fn store(&mut self, slot: (Address, U256), value: U256) {
+        let old = self.values.insert(slot, value).unwrap_or_default();
+        if let Some(journal) = self.journals.last_mut() {
+            journal.push((slot, old));
+        }
+        self.trace.slots.insert(slot);
+    }
+}
+
+impl DrainTrace for TransientStorageTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        map.insert::<TransientStorageKey>(self.trace);
+    }
+}
+
+pub struct TransientStorageKey;
+
+impl typemap::Key for TransientStorageKey {
+    type Value = TransientStorageTrace;
+}
+
+impl CallTracer for TransientStorageTracer {
+    fn record_call(&mut self, _params: &ActionParams) {
+        self.journals.push(vec![]);
+    }
+
+    fn record_call_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+
+    fn record_create(&mut self, _params: &ActionParams) {
+        self.journals.push(vec![]);
+    }
+
+    fn record_create_result(&mut self, result: &FrameResult) {
+        self.close_frame(result);
+    }
+}
+
+impl CheckpointTracer for TransientStorageTracer {}
+
+impl InternalTransferTracer for TransientStorageTracer {}
+
+impl StorageTracer for TransientStorageTracer {}
+
+impl OpcodeTracer for TransientStorageTracer {
+    fn do_trace_opcode(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn step(&mut self, interp: &dyn InterpreterInfo) {
+        let stack = interp.stack();
+        let Some(&key) = stack.last() else {
+            return;
+        };
+        let slot = (interp.contract_address(), key);
+        let pending = match interp.current_opcode() {
+            TLOAD => Pending::Load(slot),
+            TSTORE if stack.len() >= 2 => {
+                Pending::Store(slot, stack[stack.len() - 2])
+            }
+            _ => return,
+        };
+        self.pending = Some((pending, interp.gas_remainning()));
+    }
+
+    fn step_end(&mut self, interp: &dyn InterpreterInfo) {
+        let Some((pending, gas)) = self.pending.take() else {
+            return;
+        };
+        if interp.gas_remainning() == gas {
+            return;
+        }
+        match pending {
+            Pending::Load(slot) => {
+                let got = interp.stack().last().copied().unwrap_or_default();
+                let expected =
+                    self.values.get(&slot).copied().unwrap_or_default();
+                if got != expected && self.trace.stale_load.is_none() {
+                    self.trace.stale_load = Some(StaleTransientLoad {
+                        contract: slot.0,
+                        key: slot.1,
+                        got,
+                        expected,
+                    });
+                }
+            }
+            Pending::Store(slot, value) => self.store(slot, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::test_interp::{self, Step},
+        TransientStorageTracer, TLOAD, TSTORE,
+    };
+    use cfx_executor::{
+        observer::{CallTracer, OpcodeTracer},
+        stack::FrameReturn,
+    };
+    use cfx_types::{Address, Space, U256};
+    use cfx_vm_types::{ActionParams, Error, ReturnData};
+
+    /// Runs an instruction of `contract`, `stack` before and `result` after,
+    /// both with the top last.
+    fn exec(
+        tracer: &mut TransientStorageTracer, contract: Address, opcode: u8,
+        stack: &[u64], result: &[u64],
+    ) {
+        tracer.step(&Step {
+            opcode,
+            stack: test_interp::stack(stack),
+            gas: U256::from(1000),
+            contract,
+            ..Default::default()
+        });
+        tracer.step_end(&Step {
+            opcode,
+            stack: test_interp::stack(result),
+            gas: U256::from(900),
+            contract,
+            ..Default::default()
+        });
+    }
+
+    fn succeeded() -> FrameReturn {
+        FrameReturn {
+            space: Space::Ethereum,
+            gas_left: U256::zero(),
+            apply_state: true,
+            return_data: ReturnData::empty(),
+            create_address: None,
+            substate: None,
+        }
+    }
+
+    #[test]
+    fn stores_of_reverted_frames_are_not_visible() {
+        let outer = Address::from_low_u64_be(0x1000);
+        let inner = Address::from_low_u64_be(0x2000);
+        let mut tracer = TransientStorageTracer::default();
+
+        tracer.record_call(&ActionParams::default());
+        // TSTORE(1, 5)
+        exec(&mut tracer, outer, TSTORE, &[5, 1], &[]);
+        tracer.record_call(&ActionParams::default());
+        exec(&mut tracer, inner, TSTORE, &[7, 1], &[]);
+        tracer.record_call(&ActionParams::default());
+        // Reverted with its caller
+        exec(&mut tracer, outer, TSTORE, &[9, 1], &[]);
+        tracer.record_call_result(&Ok(succeeded()));
+        tracer.record_call_result(&Err(Error::Reverted));
+
+        exec(&mut tracer, outer, TLOAD, &[1], &[5]);
+        exec(&mut tracer, inner, TLOAD, &[1], &[0]);
+        assert!(tracer.trace.stale_load.is_none());
+
+        // Reads the store of the reverted sub-call
+        exec(&mut tracer, outer, TLOAD, &[1], &[9]);
+        tracer.record_call_result(&Ok(succeeded()));
+        let stale = tracer.trace.stale_load.unwrap();
+        assert_eq!((stale.contract, stale.got), (outer, U256::from(9)));
+        assert_eq!(stale.expected, U256::from(5));
+        assert_eq!(tracer.trace.slots.len(), 2);
+    }
+}
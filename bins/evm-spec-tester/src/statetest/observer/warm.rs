@@ -0,0 +1,53 @@
+use cfx_executor::{
+    executive::Executed,
+    observer::{
+        CallTracer, CheckpointTracer, DrainTrace, InternalTransferTracer,
+        OpcodeTracer, StorageTracer,
+    },
+};
+use cfx_types::AddressWithSpace;
+use typemap::ShareDebugMap;
+
+/// Records the accounts the executor warmed before the first frame of the
+/// transaction, so that they can be checked against the ones of the fork.
+#[derive(Default)]
+pub struct WarmAccountsTracer {
+    /// `None` if the transaction did not reach its execution.
+    accounts: Option<Vec<AddressWithSpace>>,
+}
+
+/// The accounts warm at the start of the execution of a transaction, `None`
+/// if it was not executed.
+pub fn warm_accounts(executed: &Executed) -> Option<&Vec<AddressWithSpace>> {
+    executed.ext_result.get::<WarmAccountsKey>()
+}
+
+impl DrainTrace for WarmAccountsTracer {
+    fn drain_trace(self, map: &mut ShareDebugMap) {
+        if let Some(accounts) = self.accounts {
+            map.insert::<WarmAccountsKey>(accounts);
+        }
+    }
+}
+
+pub struct WarmAccountsKey;
+
+impl typemap::Key for WarmAccountsKey {
+    type Value = Vec<AddressWithSpace>;
+}
+
+impl CallTracer for WarmAccountsTracer {
+    fn do_record_warm_accounts(&self, enabled: &mut bool) { *enabled |= true; }
+
+    fn record_warm_accounts(&mut self, accounts: &[AddressWithSpace]) {
+        self.accounts = Some(accounts.to_vec());
+    }
+}
+
+impl CheckpointTracer for WarmAccountsTracer {}
+
+impl InternalTransferTracer for WarmAccountsTracer {}
+
+impl StorageTracer for WarmAccountsTracer {}
+
+impl OpcodeTracer for WarmAccountsTracer {}
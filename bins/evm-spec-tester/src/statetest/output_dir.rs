@@ -0,0 +1,196 @@
+//! The directory the artifacts of a run are written to, see `--output-dir`.
+//! Every kind of artifact has its own subdirectory, holding one file per
+//! test named after its `path::name::spec::index` identifier.
+
+use super::shard::fnv1a;
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Longest file name stem, well below the 255 bytes most file systems allow
+/// to leave room for the hash and the extension.
+const MAX_NAME_LEN: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Artifact {
+    /// The EIP-3155 trace of the transactions of a test, see `--trace`.
+    Trace,
+    /// The post state of a passing test, see `--dump-state`.
+    StateDump,
+    /// A fixture with the post states computed by the run, see `--bless`.
+    Blessed,
+}
+
+impl Artifact {
+    fn dir(self) -> &'static str {
+        match self {
+            Artifact::Trace => "traces",
+            Artifact::StateDump => "states",
+            Artifact::Blessed => "blessed",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Artifact::Trace => "jsonl",
+            Artifact::StateDump | Artifact::Blessed => "json",
+        }
+    }
+}
+
+pub struct OutputDir {
+    root: PathBuf,
+    /// Whether every artifact is written to `root` itself, as
+    /// `--dump-state-dir` does.
+    flat: bool,
+    /// The keys a file was given out to in this run.
+    seen: Mutex<HashSet<(Artifact, String)>>,
+}
+
+impl OutputDir {
+    /// Creates `root` and its parents if missing.
+    pub fn create(root: &Path) -> io::Result<Self> { Self::new(root, false) }
+
+    /// An output directory without subdirectories.
+    pub fn flat(root: &Path) -> io::Result<Self> { Self::new(root, true) }
+
+    fn new(root: &Path, flat: bool) -> io::Result<Self> {
+        fs::create_dir_all(root)?;
+        Ok(OutputDir {
+            root: root.to_path_buf(),
+            flat,
+            seen: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn root(&self) -> &Path { &self.root }
+
+    /// The file of the `artifact` of `key`, a test identifier or a fixture
+    /// path, and whether it was already given out to `key` in this run.
+    /// The name is `key` without the extension of the artifact, with the
+    /// characters other than alphanumerics, `.`, `_` and `-` replaced by
+    /// `_`, truncated if too long, and suffixed with a hash of `key` if
+    /// changed. Two keys thus never share a file, whatever order they come
+    /// in.
+    pub fn path(
+        &self, artifact: Artifact, key: &str,
+    ) -> io::Result<(PathBuf, bool)> {
+        let dir = if self.flat {
+            self.root.clone()
+        } else {
+            self.root.join(artifact.dir())
+        };
+        fs::create_dir_all(&dir)?;
+
+        let extension = artifact.extension();
+        let stem = key
+            .strip_suffix(extension)
+            .and_then(|stem| stem.strip_suffix('.'))
+            .unwrap_or(key);
+        let name = file_name(stem, key);
+        let seen = !self
+            .seen
+            .lock()
+            .expect("output names poisoned")
+            .insert((artifact, key.to_string()));
+        Ok((dir.join(format!("{}.{}", name, extension)), seen))
+    }
+
+    /// Opens the file of the `artifact` of `key`, truncated the first time
+    /// in the run. The traces of a test run again are appended to it.
+    pub fn open(&self, artifact: Artifact, key: &str) -> io::Result<File> {
+        let (path, seen) = self.path(artifact, key)?;
+        let append = seen && artifact == Artifact::Trace;
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+    }
+}
+
+/// The file name of `stem`, suffixed with the hash of `key` if not kept as
+/// is.
+fn file_name(stem: &str, key: &str) -> String {
+    let mut name: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    name.truncate(MAX_NAME_LEN);
+    if name != stem {
+        name.push_str(&format!("-{:016x}", fnv1a(key.as_bytes())));
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Artifact, OutputDir, MAX_NAME_LEN};
+
+    #[test]
+    fn files_are_named_after_their_test() {
+        let root = std::env::temp_dir()
+            .join(format!("evm-spec-tester-output-{}", std::process::id()));
+        let output = OutputDir::create(&root.join("first")).unwrap();
+        let name = |output: &OutputDir, artifact, key: &str| {
+            let (path, seen) = output.path(artifact, key).unwrap();
+            let relative = path.strip_prefix(output.root()).unwrap();
+            (relative.to_str().unwrap().to_string(), seen)
+        };
+
+        // Kept as is
+        let clean = "test-Prague-0";
+        let trace = ("traces/test-Prague-0.jsonl".to_string(), false);
+        assert_eq!(name(&output, Artifact::Trace, clean), trace);
+        assert_eq!(name(&output, Artifact::Trace, clean), (trace.0, true));
+        assert_eq!(
+            name(&output, Artifact::StateDump, clean),
+            ("states/test-Prague-0.json".into(), false)
+        );
+        assert_eq!(
+            name(&output, Artifact::Blessed, "a.json"),
+            ("blessed/a.json".into(), false)
+        );
+
+        // Sanitized, and suffixed with a hash of the key
+        let id = "a/b.json::test[fork]::Prague::0";
+        let (trace, _) = name(&output, Artifact::Trace, id);
+        assert!(trace.starts_with("traces/a_b.json__test_fork___Prague__0-"));
+        let (blessed, _) = name(&output, Artifact::Blessed, "fixtures/a.json");
+        assert!(blessed.starts_with("blessed/fixtures_a-"));
+        assert!(!blessed.ends_with(".json.json"));
+        // Another spec or index is another file
+        assert_ne!(
+            name(&output, Artifact::Trace, "a/b.json::test[fork]::Prague::1").0,
+            trace
+        );
+
+        // Sanitized to the name of another key, in either order
+        let other = "a_b.json::test[fork]::Prague::0";
+        let (other_trace, seen) = name(&output, Artifact::Trace, other);
+        assert!(!seen);
+        assert_ne!(other_trace, trace);
+        let reversed = OutputDir::create(&root.join("second")).unwrap();
+        assert_eq!(name(&reversed, Artifact::Trace, other).0, other_trace);
+        assert_eq!(name(&reversed, Artifact::Trace, id).0, trace);
+
+        let long = format!("{}::test::Prague::0", "x".repeat(300));
+        let (path, _) = output.path(Artifact::Trace, &long).unwrap();
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        assert_eq!(stem.len(), MAX_NAME_LEN + 17);
+        assert!(output.root().join("traces").is_dir());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
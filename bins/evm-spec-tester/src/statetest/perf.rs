@@ -0,0 +1,79 @@
+//! Executor time of a run, split by `cfx_executor::perf`. The executor only
+//! counts it when built with the `perf` feature, so `--perf` is refused
+//! without it.
+
+use cfx_executor::perf::{self, PerfCounters};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Whether the executor counts its time.
+pub const SUPPORTED: bool = cfg!(feature = "perf");
+
+/// The counters of the transactions of a run, added once per transaction.
+#[derive(Debug, Default)]
+pub struct PerfTotals {
+    transactions: AtomicU64,
+    transact_ns: AtomicU64,
+    interpreter_ns: AtomicU64,
+    storage_ns: AtomicU64,
+    account_load_ns: AtomicU64,
+}
+
+impl PerfTotals {
+    /// Runs `f`, which executes a single transaction on this thread, and adds
+    /// its executor time to `totals` if any.
+    pub fn measure<T>(totals: Option<&Self>, f: impl FnOnce() -> T) -> T {
+        let Some(totals) = totals else {
+            return f();
+        };
+        // Drops the time of the state setup
+        perf::take();
+        let res = f();
+        totals.add(perf::take());
+        res
+    }
+
+    fn add(&self, counters: PerfCounters) {
+        self.transactions.fetch_add(1, Ordering::Relaxed);
+        for (total, ns) in [
+            (&self.transact_ns, counters.transact_ns),
+            (&self.interpreter_ns, counters.interpreter_ns),
+            (&self.storage_ns, counters.storage_ns),
+            (&self.account_load_ns, counters.account_load_ns),
+        ] {
+            total.fetch_add(ns, Ordering::Relaxed);
+        }
+    }
+
+    /// Prints the time of every kind, as the share of the executor time.
+    pub fn print(&self) {
+        let load = |total: &AtomicU64| total.load(Ordering::Relaxed);
+        let rows = [
+            ("Interpreter", load(&self.interpreter_ns)),
+            ("Storage", load(&self.storage_ns)),
+            ("Account load", load(&self.account_load_ns)),
+            ("Other", load(&self.transact_ns)),
+        ];
+        let total: u64 = rows.iter().map(|(_, ns)| ns).sum();
+        println!(
+            "\nPerf: {} transactions, {:.1} ms in the executor",
+            load(&self.transactions),
+            total as f64 / 1e6
+        );
+        for (name, ns) in rows {
+            println!(
+                "{:<16} {:>12.1} ms {:>6.1}%",
+                name,
+                ns as f64 / 1e6,
+                share(ns, total)
+            );
+        }
+    }
+}
+
+fn share(ns: u64, total: u64) -> f64 {
+    if total > 0 {
+        ns as f64 * 100.0 / total as f64
+    } else {
+        0.0
+    }
+}
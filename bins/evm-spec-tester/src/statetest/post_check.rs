@@ -0,0 +1,48 @@
+//! Invariants checked after every test besides the post state of the
+//! fixture, registered by the embedders of the tester through
+//! `UnitOptions::post_checks`.
+
+use super::error::TestErrorKind;
+use cfx_executor::{executive::Executed, state::State};
+use cfx_types::Space;
+use eest_types::SpecId;
+use primitives::SignedTransaction;
+use std::sync::Arc;
+
+/// A check run after the built-in ones on every passing test, in the final
+/// state of the test. A check that has no error kind of its own returns
+/// `TestErrorKind::PostCheckFailed`.
+pub trait PostCheck: Send + Sync {
+    /// Names the check in its failures.
+    fn name(&self) -> &str;
+
+    /// `executed` is `None` if the transaction was rejected or dropped as
+    /// the test expects.
+    fn check(
+        &self, tx: &SignedTransaction, executed: Option<&Executed>,
+        state: &State, spec: SpecId, space: Space,
+    ) -> Result<(), TestErrorKind>;
+}
+
+/// The checks of a run, shared by every test, including the ones of the
+/// workers of `UnitOptions::timeout`.
+#[derive(Clone, Default)]
+pub struct PostChecks(Arc<Vec<Box<dyn PostCheck>>>);
+
+impl PostChecks {
+    pub fn new(checks: Vec<Box<dyn PostCheck>>) -> Self {
+        PostChecks(Arc::new(checks))
+    }
+
+    /// Runs the checks in their registration order, up to the first failure.
+    pub fn check(
+        &self, tx: &SignedTransaction, executed: Option<&Executed>,
+        state: &State, spec: SpecId, space: Space,
+    ) -> Result<(), TestErrorKind> {
+        for check in self.0.iter() {
+            debug!("Running post check {}", check.name());
+            check.check(tx, executed, state, spec, space)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use eest_types::SpecName;
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Least milliseconds between two redraws, so that the tests do not wait on
+/// the terminal.
+const REDRAW_INTERVAL_MS: u64 = 100;
+
+/// Erases the line the cursor is on.
+pub const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Whether a progress line is on the terminal, which the logger clears
+/// before printing a record.
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Progress of a run, drawn on a single line of stdout. The tests may
+/// complete on any thread, only one of them redraws the line at a time.
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    start: Instant,
+    /// Milliseconds since `start` of the last redraw.
+    drawn_at: AtomicU64,
+}
+
+impl Progress {
+    /// A progress over `total` tests.
+    pub fn new(total: usize) -> Self {
+        Progress {
+            total,
+            done: AtomicUsize::new(0),
+            start: Instant::now(),
+            drawn_at: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the progress can be drawn, that is stdout is a terminal.
+    pub fn is_supported() -> bool { io::stdout().is_terminal() }
+
+    /// Counts a completed test of `spec`, and redraws the line if it has
+    /// not been for a while.
+    pub fn inc(&self, spec: SpecName) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let elapsed = self.start.elapsed();
+        let now = elapsed.as_millis() as u64;
+        let drawn_at = self.drawn_at.load(Ordering::Relaxed);
+        if now < drawn_at + REDRAW_INTERVAL_MS {
+            return;
+        }
+        // Left to the concurrent test if it won the redraw
+        if self
+            .drawn_at
+            .compare_exchange(
+                drawn_at,
+                now,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return;
+        }
+        self.draw(done, spec, elapsed);
+    }
+
+    fn draw(&self, done: usize, spec: SpecName, elapsed: Duration) {
+        let left = self.total.saturating_sub(done) as f64;
+        let eta = format_secs(elapsed.as_secs_f64() * left / done as f64);
+        let mut stdout = io::stdout().lock();
+        SHOWN.store(true, Ordering::Relaxed);
+        let _ = write!(
+            stdout,
+            "{}[{}/{}] {:?}, ETA {}",
+            CLEAR_LINE, done, self.total, spec, eta
+        );
+        let _ = stdout.flush();
+    }
+
+    /// Erases the line, so that the summary is printed in its place.
+    pub fn finish(&self) {
+        let mut stdout = io::stdout().lock();
+        if SHOWN.swap(false, Ordering::Relaxed) {
+            let _ = write!(stdout, "{}", CLEAR_LINE);
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// Whether a progress line is drawn, see `CLEAR_LINE`.
+pub fn progress_shown() -> bool { SHOWN.load(Ordering::Relaxed) }
+
+fn format_secs(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_secs;
+
+    #[test]
+    fn eta_is_printed_in_the_largest_units() {
+        assert_eq!(format_secs(0.4), "0s");
+        assert_eq!(format_secs(59.0), "59s");
+        assert_eq!(format_secs(61.0), "1m01s");
+        assert_eq!(format_secs(7260.0), "2h01m");
+    }
+}
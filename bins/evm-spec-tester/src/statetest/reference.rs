@@ -0,0 +1,173 @@
+use super::{error::TestErrorKind, loader::UnitIndex};
+use cfx_types::{H256, U256};
+use eest_types::SpecName;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::Arc,
+    thread,
+};
+
+/// A reference EVM the tests are cross-checked against, run as a shell
+/// command for every test.
+///
+/// The command gets on stdin a state test fixture with the unit of the test,
+/// whose `post` only has the post state of the test. It prints on stdout a
+/// JSON object, or an array whose first element is used, with the
+/// `stateRoot` and optionally the `gasUsed` of its execution, as
+/// `evm statetest` of go-ethereum does for the root.
+#[derive(Clone, Debug)]
+pub struct RefEvm {
+    command: String,
+    /// The JSON of the units sent, which the loader does not keep.
+    units: Arc<UnitIndex>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefOutcome {
+    state_root: H256,
+    #[serde(default)]
+    gas_used: Option<U256>,
+}
+
+impl FromStr for RefEvm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("Empty reference command".into());
+        }
+        Ok(RefEvm {
+            command: s.to_string(),
+            units: Default::default(),
+        })
+    }
+}
+
+impl RefEvm {
+    /// The JSON of the unit `name` of the fixture at `path`, parsed once per
+    /// file over the run.
+    pub fn unit(&self, path: &Path, name: &str) -> Result<Value, String> {
+        self.units.unit(path, name)
+    }
+
+    /// The fixture sent for the `index`-th post state of `spec` in `unit`,
+    /// the JSON of the unit named `name`.
+    pub fn input(
+        name: &str, unit: &Value, spec: SpecName, index: usize,
+    ) -> Value {
+        let mut unit = unit.clone();
+        if let Some(post) = unit.get_mut("post").and_then(Value::as_object_mut)
+        {
+            let test = post
+                .iter()
+                .find(|(key, _)| is_spec(key, spec))
+                .and_then(|(key, tests)| {
+                    Some((key.clone(), tests.get(index)?.clone()))
+                });
+            post.clear();
+            if let Some((key, test)) = test {
+                post.insert(key, json!([test]));
+            }
+        }
+        let mut suite = serde_json::Map::new();
+        suite.insert(name.to_string(), unit);
+        suite.into()
+    }
+
+    /// Runs the reference on `input` and compares its state root, and its
+    /// gas used if given, with the ones of the test.
+    pub fn check(
+        &self, input: &Value, state_root: H256, gas_used: U256,
+    ) -> Result<(), TestErrorKind> {
+        let outcome = self.run(input).map_err(|e| {
+            TestErrorKind::Internal(format!("reference evm: {}", e))
+        })?;
+        if outcome.state_root != state_root
+            || outcome.gas_used.map_or(false, |gas| gas != gas_used)
+        {
+            return Err(TestErrorKind::RefMismatch {
+                got_root: state_root,
+                ref_root: outcome.state_root,
+                got_gas: gas_used,
+                ref_gas: outcome.gas_used,
+            });
+        }
+        Ok(())
+    }
+
+    fn run(&self, input: &Value) -> Result<RefOutcome, String> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start: {}", e))?;
+
+        // Written on another thread, so a reference printing before it has
+        // read the whole input does not block
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+        let writer = thread::spawn(move || stdin.write_all(&input));
+
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        let written = writer.join().expect("writer does not panic");
+        if !output.status.success() {
+            return Err(format!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        written.map_err(|e| format!("failed to write the input: {}", e))?;
+
+        let mut outcome: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("invalid output: {}", e))?;
+        if let Value::Array(outcomes) = &mut outcome {
+            outcome = outcomes.first_mut().map(Value::take).unwrap_or_default();
+        }
+        serde_json::from_value(outcome)
+            .map_err(|e| format!("invalid output: {}", e))
+    }
+}
+
+/// Whether `key` of the fixture post states names `spec`.
+fn is_spec(key: &str, spec: SpecName) -> bool {
+    serde_json::from_value::<SpecName>(Value::String(key.to_string()))
+        .map_or(false, |key| key == spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RefEvm;
+    use eest_types::SpecName;
+    use serde_json::json;
+
+    #[test]
+    fn input_only_keeps_the_post_state_of_the_test() {
+        let unit = json!({
+            "env": {},
+            "post": {
+                "Cancun": [{ "hash": "0x01" }],
+                "Prague": [{ "hash": "0x02" }, { "hash": "0x03" }]
+            }
+        });
+        let input = RefEvm::input("unit", &unit, SpecName::Prague, 1);
+        assert_eq!(
+            input,
+            json!({
+                "unit": {
+                    "env": {},
+                    "post": { "Prague": [{ "hash": "0x03" }] }
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use super::memory::MemoryStats;
+use cfx_executor::executive::ExecutionOutcome;
+use eest_types::{PostIndexes, SpecName};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Skipped,
+    Failed,
+    /// Failed as expected by the xfail list.
+    Xfail,
+    /// Passed although listed in the xfail list.
+    Xpass,
+}
+
+impl TestStatus {
+    /// Whether the test has been executed, even if it failed.
+    pub fn is_executed(&self) -> bool { !matches!(self, TestStatus::Skipped) }
+}
+
+/// How the executor handled the transaction of a test, whatever the test
+/// status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutcomeKind {
+    /// Rejected by the checks before the execution.
+    ConsensusRejected,
+    NotExecutedDrop,
+    NotExecutedToReconsiderPacking,
+    ExecutionErrorBumpNonce,
+    Finished,
+}
+
+impl OutcomeKind {
+    pub fn of(outcome: &ExecutionOutcome) -> Self {
+        match outcome {
+            ExecutionOutcome::NotExecutedDrop(_) => {
+                OutcomeKind::NotExecutedDrop
+            }
+            ExecutionOutcome::NotExecutedToReconsiderPacking(_) => {
+                OutcomeKind::NotExecutedToReconsiderPacking
+            }
+            ExecutionOutcome::ExecutionErrorBumpNonce(..) => {
+                OutcomeKind::ExecutionErrorBumpNonce
+            }
+            ExecutionOutcome::Finished(_) => OutcomeKind::Finished,
+        }
+    }
+}
+
+/// Where the fixture of a test comes from, read from its `_info`, so that a
+/// failure can be traced back to the client and the version that filled it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureInfo {
+    /// The filler, `source` in ethereum/tests and `url` in the execution
+    /// spec tests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// `description`, or the `comment` of ethereum/tests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The reference client and its version, `filling-transition-tool` or
+    /// `filling-rpc-server`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filled_by: Option<String>,
+    /// The version of the generator, `filling-tool-version` in
+    /// ethereum/tests, or the `reference-spec-version` of the EIP the test
+    /// was written against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl FixtureInfo {
+    /// `None` if `info` has none of the fields.
+    pub fn of(info: &Value) -> Option<Self> {
+        let field = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| info.get(key)?.as_str())
+                .map(str::to_string)
+        };
+        let info = FixtureInfo {
+            source: field(&["source", "url"]),
+            description: field(&["description", "comment"]),
+            filled_by: field(&[
+                "filling-transition-tool",
+                "filling-rpc-server",
+            ]),
+            version: field(&["filling-tool-version", "reference-spec-version"]),
+        };
+        (info != FixtureInfo::default()).then_some(info)
+    }
+}
+
+/// Outcome of a single post state, or of a whole unit if it has no post
+/// state to run.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestRecord {
+    pub path: String,
+    pub name: String,
+    pub spec: Option<SpecName>,
+    /// Position of the post state among the ones of `spec`.
+    pub index: Option<usize>,
+    pub indexes: Option<PostIndexes>,
+    pub status: TestStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Why the test is skipped, if it is not just unsupported, or why it is
+    /// expected to fail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub duration_ms: f64,
+    /// Only known for the passing tests whose gas used fits in a `u64`.
+    pub gas_used: Option<u64>,
+    /// Not known for the tests failing before the transaction is checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<OutcomeKind>,
+    /// Only measured with the `profile-mem` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryStats>,
+    /// The `_info` of the fixture, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<FixtureInfo>,
+}
+
+impl TestRecord {
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration_ms = duration.as_secs_f64() * 1000.0;
+    }
+}
+
+/// A report file, the records with what is needed to reproduce the run.
+#[derive(Serialize)]
+struct Report<'a> {
+    seed: u64,
+    records: &'a [TestRecord],
+}
+
+/// Destination of a report, given as `<format>=<path>`.
+#[derive(Clone, Debug)]
+pub enum ReportTarget {
+    Json(PathBuf),
+    /// JUnit XML, for the CI dashboards.
+    Junit(PathBuf),
+}
+
+impl FromStr for ReportTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((format, path)) = s.split_once('=') else {
+            return Err(format!("Expect <format>=<path>, got: {}", s));
+        };
+        if path.is_empty() {
+            return Err(format!("Missing report path: {}", s));
+        }
+        match format {
+            "json" => Ok(ReportTarget::Json(path.into())),
+            "junit" => Ok(ReportTarget::Junit(path.into())),
+            _ => Err(format!("Unknown report format: {}", format)),
+        }
+    }
+}
+
+impl ReportTarget {
+    pub fn write(
+        &self, seed: u64, records: &[TestRecord],
+    ) -> Result<(), String> {
+        let report = Report { seed, records };
+        match self {
+            ReportTarget::Json(path) => {
+                let file = File::create(path).map_err(|e| e.to_string())?;
+                serde_json::to_writer_pretty(BufWriter::new(file), &report)
+                    .map_err(|e| e.to_string())
+            }
+            ReportTarget::Junit(path) => {
+                fs::write(path, junit_xml(seed, records))
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// A JUnit test suite of the records, one test case per record named
+/// `path::name`, with the spec and the index of the post state as its class
+/// name. The xfails are skipped, as they do not fail the run, while the
+/// xpasses are failures. A failure carries the `_info` of its fixture.
+fn junit_xml(seed: u64, records: &[TestRecord]) -> String {
+    let count = |status: &[TestStatus]| {
+        records
+            .iter()
+            .filter(|r| status.contains(&r.status))
+            .count()
+    };
+    let secs: f64 = records.iter().map(|r| r.duration_ms).sum::<f64>() / 1000.0;
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"evm-spec-tester\" tests=\"{}\" failures=\"{}\" \
+         skipped=\"{}\" time=\"{:.3}\">",
+        records.len(),
+        count(&[TestStatus::Failed, TestStatus::Xpass]),
+        count(&[TestStatus::Skipped, TestStatus::Xfail]),
+        secs
+    );
+    let _ = writeln!(
+        xml,
+        "  <properties><property name=\"seed\" value=\"{}\"/></properties>",
+        seed
+    );
+    for record in records {
+        let spec = record.spec.map_or("-".to_string(), |s| format!("{:?}", s));
+        let index = record.index.map_or("-".to_string(), |i| i.to_string());
+        let _ = write!(
+            xml,
+            "  <testcase name=\"{}\" classname=\"{}.{}\" time=\"{:.3}\"",
+            escape_xml(&format!("{}::{}", record.path, record.name)),
+            spec,
+            index,
+            record.duration_ms / 1000.0
+        );
+        let message = |default: &str| {
+            escape_xml(
+                record
+                    .error
+                    .as_deref()
+                    .or(record.reason.as_deref())
+                    .unwrap_or(default),
+            )
+        };
+        match record.status {
+            TestStatus::Passed => xml.push_str("/>\n"),
+            TestStatus::Skipped => {
+                let _ = writeln!(
+                    xml,
+                    "><skipped message=\"{}\"/></testcase>",
+                    message("unsupported")
+                );
+            }
+            TestStatus::Xfail => {
+                let _ = writeln!(
+                    xml,
+                    "><skipped message=\"xfail: {}\"/></testcase>",
+                    message("expected to fail")
+                );
+            }
+            TestStatus::Failed => {
+                let _ = writeln!(
+                    xml,
+                    "><failure message=\"{}\">{}</failure></testcase>",
+                    message("failed"),
+                    fixture_info(record)
+                );
+            }
+            TestStatus::Xpass => {
+                let _ = writeln!(
+                    xml,
+                    "><failure message=\"xpass: {}\">{}</failure></testcase>",
+                    message("expected to fail"),
+                    fixture_info(record)
+                );
+            }
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// The `_info` of the fixture of `record`, one `key: value` line per field.
+fn fixture_info(record: &TestRecord) -> String {
+    let Some(info) = &record.info else {
+        return String::new();
+    };
+    let mut text = String::new();
+    for (key, value) in [
+        ("source", &info.source),
+        ("description", &info.description),
+        ("filled by", &info.filled_by),
+        ("version", &info.version),
+    ] {
+        if let Some(value) = value {
+            let _ = writeln!(text, "{}: {}", key, value);
+        }
+    }
+    escape_xml(&text)
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            // Not allowed in XML 1.0
+            c if c.is_control() && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Default)]
+struct SummaryRow {
+    passed: usize,
+    skipped: usize,
+    failed: usize,
+    xfailed: usize,
+    xpassed: usize,
+    gas_used: u64,
+    duration_ms: f64,
+}
+
+/// Prints a per-spec table of the records.
+pub fn print_summary(records: &[TestRecord]) {
+    let mut rows: BTreeMap<Option<SpecName>, SummaryRow> = BTreeMap::new();
+    for record in records {
+        let row = rows.entry(record.spec).or_default();
+        match record.status {
+            TestStatus::Passed => row.passed += 1,
+            TestStatus::Skipped => row.skipped += 1,
+            TestStatus::Failed => row.failed += 1,
+            TestStatus::Xfail => row.xfailed += 1,
+            TestStatus::Xpass => row.xpassed += 1,
+        }
+        row.gas_used += record.gas_used.unwrap_or_default();
+        row.duration_ms += record.duration_ms;
+    }
+
+    println!(
+        "\n{:<32} {:>8} {:>8} {:>8} {:>8} {:>8} {:>16} {:>12}",
+        "Spec",
+        "Passed",
+        "Skipped",
+        "Failed",
+        "XFail",
+        "XPass",
+        "Gas Used",
+        "Time (ms)"
+    );
+    for (spec, row) in rows {
+        let spec = spec.map_or("-".to_string(), |s| format!("{:?}", s));
+        println!(
+            "{:<32} {:>8} {:>8} {:>8} {:>8} {:>8} {:>16} {:>12.1}",
+            spec,
+            row.passed,
+            row.skipped,
+            row.failed,
+            row.xfailed,
+            row.xpassed,
+            row.gas_used,
+            row.duration_ms
+        );
+    }
+}
+
+#[derive(Default)]
+struct MetricsRow {
+    tests: usize,
+    gas_used: u64,
+    duration_ms: f64,
+}
+
+impl MetricsRow {
+    fn add(&mut self, record: &TestRecord) {
+        self.tests += 1;
+        self.gas_used += record.gas_used.unwrap_or_default();
+        self.duration_ms += record.duration_ms;
+    }
+}
+
+/// Prints the number of executed tests and their gas, with the throughput
+/// over the `wall` time of the run. `per_fork` adds a table whose throughput
+/// is over the time spent in the tests of each fork, as they run in
+/// parallel.
+pub fn print_metrics(records: &[TestRecord], wall: Duration, per_fork: bool) {
+    let mut total = MetricsRow::default();
+    let mut rows: BTreeMap<Option<SpecName>, MetricsRow> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.status.is_executed()) {
+        total.add(record);
+        rows.entry(record.spec).or_default().add(record);
+    }
+
+    let secs = wall.as_secs_f64();
+    println!(
+        "\nMetrics: {} tests, {} gas in {:.2} s ({:.0} gas/s, {:.1} tests/s)",
+        total.tests,
+        total.gas_used,
+        secs,
+        per_second(total.gas_used as f64, secs),
+        per_second(total.tests as f64, secs)
+    );
+    if !per_fork {
+        return;
+    }
+
+    println!(
+        "\n{:<32} {:>8} {:>16} {:>12} {:>16} {:>12}",
+        "Spec", "Tests", "Gas Used", "Time (ms)", "Gas/s", "Tests/s"
+    );
+    for (spec, row) in rows {
+        let spec = spec.map_or("-".to_string(), |s| format!("{:?}", s));
+        let secs = row.duration_ms / 1000.0;
+        println!(
+            "{:<32} {:>8} {:>16} {:>12.1} {:>16.0} {:>12.1}",
+            spec,
+            row.tests,
+            row.gas_used,
+            row.duration_ms,
+            per_second(row.gas_used as f64, secs),
+            per_second(row.tests as f64, secs)
+        );
+    }
+}
+
+pub(super) fn per_second(amount: f64, secs: f64) -> f64 {
+    if secs > 0.0 {
+        amount / secs
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{junit_xml, FixtureInfo, TestRecord, TestStatus};
+    use eest_types::SpecName;
+    use serde_json::json;
+
+    fn record(status: TestStatus, error: Option<&str>) -> TestRecord {
+        TestRecord {
+            path: "fixtures/a.json".to_string(),
+            name: "a".to_string(),
+            spec: Some(SpecName::Prague),
+            index: Some(1),
+            indexes: None,
+            status,
+            error: error.map(str::to_string),
+            reason: None,
+            duration_ms: 1500.0,
+            gas_used: None,
+            outcome: None,
+            memory: None,
+            info: None,
+        }
+    }
+
+    #[test]
+    fn junit_cases_carry_their_outcome() {
+        let xml = junit_xml(
+            7,
+            &[
+                record(TestStatus::Passed, None),
+                record(TestStatus::Failed, Some("gas used < \"expected\"")),
+                record(TestStatus::Skipped, None),
+            ],
+        );
+        assert!(xml.contains(
+            r#"<testsuite name="evm-spec-tester" tests="3" failures="1" skipped="1" time="4.500">"#
+        ));
+        assert!(xml.contains(
+            r#"<testcase name="fixtures/a.json::a" classname="Prague.1" time="1.500"/>"#
+        ));
+        assert!(xml.contains(
+            r#"<failure message="gas used &lt; &quot;expected&quot;"></failure>"#
+        ));
+        assert!(xml.contains(r#"<skipped message="unsupported"/>"#));
+        assert!(xml.ends_with("</testsuite>\n"));
+    }
+
+    #[test]
+    fn fixture_info_reads_both_fixture_formats() {
+        let eest = json!({
+            "comment": "`execution-spec-tests` generated test",
+            "filling-transition-tool": "ethereum-spec-evm-resolver 0.0.5",
+            "description": "Test function documentation",
+            "url": "https://github.com/ethereum/execution-spec-tests/tree/v4.3.0/tests/a.py#L1",
+            "reference-spec-version": "4334df83",
+        });
+        let info = FixtureInfo::of(&eest).unwrap();
+        assert_eq!(
+            info.description.as_deref(),
+            Some("Test function documentation")
+        );
+        assert_eq!(
+            info.filled_by.as_deref(),
+            Some("ethereum-spec-evm-resolver 0.0.5")
+        );
+        assert!(info.source.unwrap().ends_with("a.py#L1"));
+
+        let legacy = json!({
+            "comment": "",
+            "filling-rpc-server": "evm version 1.13.11",
+            "filling-tool-version": "retesteth-0.3.2",
+            "source": "src/GeneralStateTestsFiller/stExample/add11Filler.json",
+        });
+        let info = FixtureInfo::of(&legacy).unwrap();
+        assert_eq!(info.filled_by.as_deref(), Some("evm version 1.13.11"));
+        assert_eq!(info.version.as_deref(), Some("retesteth-0.3.2"));
+        assert_eq!(info.description.as_deref(), Some(""));
+        assert!(FixtureInfo::of(&json!({"hash": "0x00"})).is_none());
+
+        let failed = TestRecord {
+            info: FixtureInfo::of(&legacy),
+            ..record(TestStatus::Failed, Some("state mismatch"))
+        };
+        let xml = junit_xml(7, &[failed]);
+        assert!(xml.contains(
+            "<failure message=\"state mismatch\">source: src/GeneralStateTestsFiller/stExample/add11Filler.json&#10;"
+        ));
+        assert!(xml.contains("filled by: evm version 1.13.11&#10;"));
+    }
+}
@@ -0,0 +1,271 @@
+use super::{
+    error::TestError,
+    report::TestRecord,
+    unit_tester::{
+        BlessedPost, SpecTally, StateSurface, UnitOptions, UnitResult,
+        UnitStatus, UnitTester,
+    },
+};
+use cfx_executor::machine::Machine;
+use cfxcore::verification::VerificationConfig;
+use eest_types::SpecName;
+use rayon::prelude::*;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Outcome of `run_units`, merged over the units in their input order.
+#[derive(Default)]
+pub struct RunReport {
+    pub records: Vec<TestRecord>,
+    pub specs: BTreeMap<SpecName, SpecTally>,
+    pub errors: Vec<TestError>,
+    /// Post states computed with `UnitOptions::bless`.
+    pub blessed: Vec<BlessedPost>,
+    /// Units with at least one executed test and no error.
+    pub success_units: usize,
+    /// Units with no executed test and no error.
+    pub skipped_units: usize,
+    /// The skipped units whose post states are all of forks after the max
+    /// one.
+    pub above_cap_units: usize,
+    /// The post states of a fork after the max one, by fork, of every unit.
+    pub above_cap_tests: BTreeMap<SpecName, usize>,
+    /// The skipped units without any post state.
+    pub empty_units: usize,
+    pub error_units: usize,
+    /// Units not started because an earlier one failed.
+    pub cancelled_units: usize,
+    /// Units not started because `UnitOptions::budget` was used up.
+    pub unscheduled_units: usize,
+    /// The state changed by the passing tests of every unit, an account or
+    /// a slot changed by several units being counted once.
+    pub surface: StateSurface,
+    /// The post states skipped for the type of their transaction, by type.
+    pub unsupported_tx_types: BTreeMap<u8, usize>,
+}
+
+impl RunReport {
+    /// Number of executions, including the failed ones.
+    pub fn executed(&self) -> usize {
+        self.specs.values().map(SpecTally::executed).sum()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.specs.values().map(|t| t.failed).sum()
+    }
+
+    /// The skipped units left out by the filters of the run.
+    pub fn filtered_units(&self) -> usize {
+        self.skipped_units - self.above_cap_units - self.empty_units
+    }
+
+    /// Whether every unit ran without an error.
+    pub fn is_success(&self) -> bool {
+        self.error_units == 0 && self.cancelled_units == 0
+    }
+
+    fn merge(&mut self, result: UnitResult) {
+        let executed = result.executed();
+        self.surface.merge(result.surface);
+        for (spec, count) in &result.above_cap {
+            *self.above_cap_tests.entry(*spec).or_default() += count;
+        }
+        for (tx_type, count) in &result.unsupported_tx_types {
+            *self.unsupported_tx_types.entry(*tx_type).or_default() += count;
+        }
+        for (spec, tally) in &result.specs {
+            self.specs.entry(*spec).or_default().merge(tally);
+        }
+        self.records.extend(result.records);
+        self.blessed.extend(result.blessed);
+
+        if !result.errors.is_empty() {
+            self.error_units += 1;
+            self.errors.extend(result.errors);
+        } else if executed > 0 {
+            self.success_units += 1;
+        } else {
+            self.skipped_units += 1;
+            match result.status {
+                UnitStatus::SkippedAboveCap => self.above_cap_units += 1,
+                UnitStatus::NoPostStates => self.empty_units += 1,
+                UnitStatus::Ran(_) | UnitStatus::FilteredOut => {}
+            }
+        }
+    }
+}
+
+/// The most tests a run executes, shared by its threads. A test takes a
+/// slot right before executing, and gives it back if it turns out to be
+/// skipped, so that the cutoff never waits on the tests in flight.
+#[derive(Debug)]
+pub struct TestBudget {
+    count: usize,
+    left: AtomicUsize,
+}
+
+impl TestBudget {
+    pub fn new(count: usize) -> Self {
+        TestBudget {
+            count,
+            left: AtomicUsize::new(count),
+        }
+    }
+
+    /// Takes a slot for a test, `false` if there is none left.
+    pub fn take(&self) -> bool {
+        self.left
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Returns the slot of a test that was not executed. The slots left
+    /// never exceed the count, even for a slot given back twice.
+    pub fn give_back(&self) {
+        let _ = self.left.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |left| (left < self.count).then(|| left + 1),
+        );
+    }
+
+    pub fn is_used_up(&self) -> bool { self.left.load(Ordering::Relaxed) == 0 }
+
+    pub fn count(&self) -> usize { self.count }
+}
+
+/// Why a unit was not started.
+enum Unstarted {
+    Cancelled,
+    OverBudget,
+}
+
+/// Runs `units` in parallel on the current rayon pool, wrap the call in
+/// `ThreadPool::install` to pick another one. Unless `options.keep_going` is
+/// set, the units not started yet are cancelled after the first failure.
+/// They are not started either once `options.budget` is used up. The
+/// machine is only read by the tests, so the caller can build one for the
+/// whole run and share it between the calls.
+pub fn run_units(
+    units: &[UnitTester], machine: &Arc<Machine>,
+    verification: &VerificationConfig, options: &UnitOptions,
+) -> RunReport {
+    let stop = AtomicBool::new(false);
+    let fail_fast = !options.keep_going;
+
+    // `collect` keeps the input order, so the report does not depend on how
+    // the units are scheduled.
+    let results: Vec<Result<UnitResult, Unstarted>> = units
+        .par_iter()
+        .map(|unit| {
+            if stop.load(Ordering::Relaxed) {
+                return Err(Unstarted::Cancelled);
+            }
+            if options.budget.map_or(false, TestBudget::is_used_up) {
+                return Err(Unstarted::OverBudget);
+            }
+            let result = unit.run(machine, verification, options);
+            if fail_fast && !result.errors.is_empty() {
+                stop.store(true, Ordering::Relaxed);
+            }
+            Ok(result)
+        })
+        .collect();
+
+    let mut report = RunReport::default();
+    for result in results {
+        match result {
+            Ok(result) => report.merge(result),
+            Err(Unstarted::Cancelled) => report.cancelled_units += 1,
+            Err(Unstarted::OverBudget) => report.unscheduled_units += 1,
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::unit_tester::{StateSurface, UnitResult},
+        RunReport, TestBudget,
+    };
+    use cfx_executor::{
+        machine::{Machine, VmFactory},
+        spec::CommonParams,
+    };
+    use cfx_types::{Address, Space, U256};
+    use rayon::{prelude::*, ThreadPoolBuilder};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn budget_gives_back_up_to_its_count() {
+        let budget = TestBudget::new(2);
+        assert!(budget.take());
+        budget.give_back();
+        // A late slot, given back once the count is whole again
+        budget.give_back();
+        assert!(budget.take());
+        assert!(budget.take());
+        assert!(!budget.take());
+        assert!(budget.is_used_up());
+
+        let empty = TestBudget::new(0);
+        empty.give_back();
+        assert!(!empty.take());
+    }
+
+    #[test]
+    fn surface_is_distinct_over_the_units() {
+        let [sender, first, second] =
+            [0xa94f, 0x1000, 0x2000].map(Address::from_low_u64_be);
+        let unit = |contract: Address| UnitResult {
+            surface: StateSurface {
+                accounts: BTreeSet::from([sender, contract]),
+                slots: BTreeSet::from([(contract, U256::zero())]),
+            },
+            ..Default::default()
+        };
+
+        let mut report = RunReport::default();
+        report.merge(unit(first));
+        report.merge(unit(second));
+        report.merge(unit(second));
+        assert_eq!(report.surface.accounts.len(), 3);
+        assert_eq!(report.surface.slots.len(), 2);
+    }
+
+    #[test]
+    fn machine_is_shared_by_the_threads_of_a_run() {
+        fn assert_sync<T: Send + Sync>() {}
+        assert_sync::<Machine>();
+
+        let mut params = CommonParams::default();
+        params.transition_numbers.cip119 = 100;
+        params.transition_heights.cip151 = 200;
+        let machine = Machine::new_with_builtin(params, VmFactory::new(1024));
+        // What every thread reads, the spec and the chain id behind a lock
+        let read = |height: u64| {
+            let chain_id = machine.params().chain_id(height, Space::Ethereum);
+            format!("{:?} {}", machine.spec(height, height), chain_id)
+        };
+        let heights = [0, 99, 100, 199, 200, u64::MAX];
+        let expected: Vec<_> = heights.iter().map(|&h| read(h)).collect();
+
+        let pool = ThreadPoolBuilder::new().num_threads(16).build().unwrap();
+        pool.install(|| {
+            (0..10_000).into_par_iter().for_each(|i| {
+                let i = i % heights.len();
+                assert_eq!(read(heights[i]), expected[i]);
+            })
+        });
+        assert_ne!(expected[1], expected[2]);
+        assert_ne!(expected[3], expected[4]);
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fmt, str::FromStr};
+
+/// Shard `index` of `count`, given as `K/N` with `K` in `0..N`. A test is
+/// assigned to a shard by the FNV-1a hash of its identifier, which does not
+/// depend on the run or the platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    pub fn contains(&self, id: &str) -> bool {
+        fnv1a(id.as_bytes()) % self.count == self.index
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((index, count)) = s.split_once('/') else {
+            return Err(format!("Expect K/N, got: {}", s));
+        };
+        let index: u64 = index
+            .parse()
+            .map_err(|e| format!("Invalid shard index {}: {}", index, e))?;
+        let count: u64 = count
+            .parse()
+            .map_err(|e| format!("Invalid shard count {}: {}", count, e))?;
+        if index >= count {
+            return Err(format!("Shard index must be below {}: {}", count, s));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fnv1a, Shard};
+
+    #[test]
+    fn fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn shards_are_disjoint() {
+        let shards: Vec<Shard> = (0..3)
+            .map(|k| format!("{}/3", k).parse().unwrap())
+            .collect();
+        for i in 0..100 {
+            let id = format!("a.json::test::Cancun::{}", i);
+            assert_eq!(shards.iter().filter(|s| s.contains(&id)).count(), 1);
+        }
+        assert!("3/3".parse::<Shard>().is_err());
+        assert!("0/0".parse::<Shard>().is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use super::unit_tester::TestId;
+use std::cell::RefCell;
+
+thread_local! {
+    static CURRENT_TEST: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Attributes the logs of the current thread to a test, until dropped. The
+/// logger prints the test of `current_test` along every record, so that the
+/// logs of the executor during a test can be told apart.
+pub struct TestSpan {
+    previous: Option<String>,
+}
+
+impl TestSpan {
+    pub fn enter(id: &TestId) -> Self {
+        let previous =
+            CURRENT_TEST.with(|current| current.replace(Some(id.to_string())));
+        TestSpan { previous }
+    }
+}
+
+impl Drop for TestSpan {
+    fn drop(&mut self) {
+        CURRENT_TEST
+            .with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/// The `path::name::spec::index` of the test running on the current thread.
+pub fn current_test() -> Option<String> {
+    CURRENT_TEST.with(|current| current.borrow().clone())
+}
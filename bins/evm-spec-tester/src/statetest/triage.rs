@@ -0,0 +1,241 @@
+//! The interactive triage of the failures of a run, see `--interactive`: a
+//! line-based prompt over the errors of the run, which only re-runs a test
+//! through the callback it is given.
+
+use super::{
+    error::TestError,
+    output_dir::{Artifact, OutputDir},
+    runner::RunReport,
+    unit_tester::TestId,
+};
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+};
+
+/// Most lines of a trace printed at once.
+const MAX_TRACE_LINES: usize = 200;
+
+const HELP: &str = "\
+commands:
+  l         list the failures
+  <n>       select failure <n>
+  n, p      select the next or the previous failure, wrapping around
+  s         show the selected failure, with its post state diff
+  t         show the captured trace of the selected failure
+  r         run the selected test again, tracing it to stdout
+  h         show this help
+  q         quit";
+
+pub struct Triage<'a> {
+    failures: &'a [TestError],
+    /// Where the traces of the run were written, if they were.
+    traces: Option<&'a OutputDir>,
+    /// Runs a single test again with its trace on stdout.
+    rerun: Box<dyn Fn(&TestId) -> RunReport + 'a>,
+    selected: usize,
+}
+
+impl<'a> Triage<'a> {
+    pub fn new(
+        failures: &'a [TestError], traces: Option<&'a OutputDir>,
+        rerun: impl Fn(&TestId) -> RunReport + 'a,
+    ) -> Self {
+        Triage {
+            failures,
+            traces,
+            rerun: Box::new(rerun),
+            selected: 0,
+        }
+    }
+
+    /// Whether the prompt can be used, that is both stdin and stdout are
+    /// terminals.
+    pub fn is_supported() -> bool {
+        io::stdin().is_terminal() && io::stdout().is_terminal()
+    }
+
+    /// Reads the commands from `input` until `q` or its end.
+    pub fn run(
+        &mut self, mut input: impl BufRead, out: &mut impl Write,
+    ) -> io::Result<()> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "\n{} failures, h for help", self.failures.len())?;
+        self.list(out)?;
+        loop {
+            write!(out, "triage [{}]> ", self.selected)?;
+            out.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match line.trim() {
+                "" => {}
+                "q" => return Ok(()),
+                "h" => writeln!(out, "{}", HELP)?,
+                "l" => self.list(out)?,
+                "n" => {
+                    let next = (self.selected + 1) % self.failures.len();
+                    self.select(next, out)?
+                }
+                "p" => {
+                    let len = self.failures.len();
+                    self.select((self.selected + len - 1) % len, out)?
+                }
+                "s" => self.show(out)?,
+                "t" => self.show_trace(out)?,
+                "r" => self.rerun(out)?,
+                other => match other.parse() {
+                    Ok(index) => self.select(index, out)?,
+                    Err(_) => writeln!(
+                        out,
+                        "unknown command {:?}, h for help",
+                        other
+                    )?,
+                },
+            }
+        }
+    }
+
+    fn selected(&self) -> &TestError { &self.failures[self.selected] }
+
+    fn list(&self, out: &mut impl Write) -> io::Result<()> {
+        for (index, err) in self.failures.iter().enumerate() {
+            let marker = if index == self.selected { '*' } else { ' ' };
+            let kind = err.kind.to_string();
+            let summary = kind.lines().next().unwrap_or_default();
+            writeln!(out, "{}{:>3} {}: {}", marker, index, name(err), summary)?;
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, index: usize, out: &mut impl Write) -> io::Result<()> {
+        if index >= self.failures.len() {
+            return writeln!(out, "no failure {}", index);
+        }
+        self.selected = index;
+        writeln!(out, "{}", name(self.selected()))
+    }
+
+    fn show(&self, out: &mut impl Write) -> io::Result<()> {
+        let err = self.selected();
+        writeln!(out, "{}\n{}", name(err), err.kind)?;
+        if let Some(outcome) = &err.outcome {
+            writeln!(out, "outcome: {:?}", outcome)?;
+        }
+        if let Some(repeats) = &err.repeats {
+            writeln!(out, "{}", repeats)?;
+        }
+        Ok(())
+    }
+
+    fn show_trace(&self, out: &mut impl Write) -> io::Result<()> {
+        let (Some(traces), Some(id)) = (self.traces, test_id(self.selected()))
+        else {
+            return writeln!(
+                out,
+                "no trace captured, run with --trace --output-dir or use r"
+            );
+        };
+        let (path, _) = traces.path(Artifact::Trace, &id.to_string())?;
+        let Ok(trace) = fs::read_to_string(&path) else {
+            return writeln!(out, "no trace at {}", path.display());
+        };
+        let lines: Vec<_> = trace.lines().collect();
+        for line in lines.iter().take(MAX_TRACE_LINES) {
+            writeln!(out, "{}", line)?;
+        }
+        if lines.len() > MAX_TRACE_LINES {
+            writeln!(
+                out,
+                "... {} more lines in {}",
+                lines.len() - MAX_TRACE_LINES,
+                path.display()
+            )?;
+        }
+        Ok(())
+    }
+
+    fn rerun(&self, out: &mut impl Write) -> io::Result<()> {
+        let Some(id) = test_id(self.selected()) else {
+            return writeln!(out, "not a single test, it can not be run again");
+        };
+        out.flush()?;
+        let report = (self.rerun)(&id);
+        match report.errors.first() {
+            Some(err) => writeln!(out, "failed again: {}", err.kind),
+            None if report.executed() == 0 => {
+                writeln!(out, "not executed, see the records of the report")
+            }
+            None => writeln!(out, "passed on the re-run"),
+        }
+    }
+}
+
+fn test_id(err: &TestError) -> Option<TestId> {
+    Some(TestId {
+        path: err.path.clone(),
+        name: err.name.clone(),
+        spec: err.spec?,
+        index: err.index?,
+    })
+}
+
+/// The identifier of the test of `err`, or its unit if it has none.
+fn name(err: &TestError) -> String {
+    match test_id(err) {
+        Some(id) => id.to_string(),
+        None => format!("{}::{}", err.path, err.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Triage;
+    use crate::statetest::{
+        error::{TestError, TestErrorKind},
+        runner::RunReport,
+    };
+    use eest_types::SpecName;
+    use std::cell::RefCell;
+
+    fn failure(name: &str, index: Option<usize>) -> TestError {
+        TestError {
+            name: name.into(),
+            path: "a.json".into(),
+            spec: index.map(|_| SpecName::Prague),
+            index,
+            kind: TestErrorKind::Internal(format!("{} broke\ndetails", name)),
+            outcome: None,
+            repeats: None,
+        }
+    }
+
+    #[test]
+    fn commands_navigate_the_failures() {
+        let failures = [failure("a", Some(0)), failure("b", None)];
+        let reruns = RefCell::new(vec![]);
+        let mut triage = Triage::new(&failures, None, |id| {
+            reruns.borrow_mut().push(id.to_string());
+            RunReport::default()
+        });
+        let mut out = vec![];
+        triage
+            .run("n\nr\nn\nr\n5\ns\nq\nl\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // The first line of the error only
+        assert!(out.contains("*  0 a.json::a::Prague::0: internal error"));
+        assert!(out.contains("   1 a.json::b: internal error: b broke\n"));
+        assert!(out.contains("not a single test, it can not be run again"));
+        assert!(out.contains("not executed"));
+        assert!(out.contains("no failure 5"));
+        assert!(out.contains("a broke\ndetails"));
+        // Stopped at `q`
+        assert_eq!(out.matches("triage [").count(), 7);
+        assert_eq!(*reruns.borrow(), ["a.json::a::Prague::0"]);
+    }
+}
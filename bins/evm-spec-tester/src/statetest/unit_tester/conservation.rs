@@ -0,0 +1,140 @@
+//! The conservation of the value held by the Ethereum space accounts over a
+//! test: the balances change by the value the withdrawals mint, less the
+//! base fee the transaction burns. The miner reward is a balance change of
+//! the coinbase like any other. The blob transactions are never executed,
+//! see `check_blob_tx`, so no blob fee is burnt.
+
+use super::super::{
+    error::{TestErrorKind, ValueDelta},
+    observer::selfdestructs,
+};
+use cfx_executor::{executive::Executed, state::State};
+use cfx_types::{Address, AddressSpaceUtil, Space, U256, U512};
+use cfx_vm_types::Env;
+use eest_types::{AccountInfo, SpecId, StateTestUnit};
+use std::collections::{BTreeSet, HashMap};
+
+const GWEI: u64 = 1_000_000_000;
+
+/// Checks the balances of `addresses`, every account of the pre and the post
+/// state, against the value minted and burnt by the test. A contract deleted
+/// by a SELFDESTRUCT destroys the balance it still holds, so only a creation
+/// of value is reported then.
+pub fn check_conservation(
+    state: &State, unit: &StateTestUnit, addresses: &BTreeSet<Address>,
+    executed: Option<&Executed>, env: &Env, spec: SpecId,
+) -> Result<(), TestErrorKind> {
+    let minted = match &unit.env.withdrawals {
+        Some(withdrawals) if spec >= SpecId::SHANGHAI => withdrawals
+            .iter()
+            .map(|w| w.amount.full_mul(U256::from(GWEI)))
+            .fold(U512::zero(), |sum, amount| sum + amount),
+        _ => U512::zero(),
+    };
+    let burnt = executed.map_or(U512::zero(), |executed| {
+        executed
+            .gas_charged
+            .full_mul(env.base_gas_price[Space::Ethereum])
+    });
+    let destroys = executed.map_or(false, |executed| {
+        selfdestructs(executed).iter().any(|s| s.deleted)
+    });
+
+    match value_delta(state, &unit.pre, addresses, minted, burnt)? {
+        Some(ValueDelta::Destroyed(_)) if destroys => Ok(()),
+        Some(delta) => Err(TestErrorKind::ValueNotConserved { delta }),
+        None => Ok(()),
+    }
+}
+
+/// How far the balances of `addresses` in `state` are from the ones of `pre`
+/// changed by `minted` and `burnt`, `None` if they are not.
+fn value_delta(
+    state: &State, pre: &HashMap<Address, AccountInfo>,
+    addresses: &BTreeSet<Address>, minted: U512, burnt: U512,
+) -> Result<Option<ValueDelta>, TestErrorKind> {
+    let mut before = minted;
+    let mut after = burnt;
+    for address in addresses {
+        if let Some(info) = pre.get(address) {
+            before += U512::from(info.balance);
+        }
+        let balance = state
+            .balance(&address.with_evm_space())
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+        after += U512::from(balance);
+    }
+
+    let saturate = |value: U512| U256::try_from(value).unwrap_or(U256::MAX);
+    Ok(if after > before {
+        Some(ValueDelta::Created(saturate(after - before)))
+    } else if after < before {
+        Some(ValueDelta::Destroyed(saturate(before - after)))
+    } else {
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::super::{error::ValueDelta, unit_tester::pre_transact},
+        value_delta,
+    };
+    use cfx_types::{Address, AddressSpaceUtil, Space, U256, U512};
+    use eest_types::AccountInfo;
+    use std::collections::{BTreeSet, HashMap};
+
+    #[test]
+    fn balances_change_by_the_minted_and_burnt_value() {
+        let pre: HashMap<Address, AccountInfo> = serde_json::from_str(
+            r#"{
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {}
+                }
+            }"#,
+        )
+        .unwrap();
+        let sender = *pre.keys().next().unwrap();
+        let receiver = Address::from_low_u64_be(0x1000);
+        let coinbase = Address::from_low_u64_be(0xc0ffee);
+        let addresses = BTreeSet::from([sender, receiver, coinbase]);
+
+        // 21000 gas at 10 wei, 7 of them burnt, and 1 wei sent
+        let mut state = pre_transact::make_state(&pre, Space::Ethereum);
+        let mut transfer = |address: Address, add: bool, amount: u64| {
+            let address = address.with_evm_space();
+            let amount = U256::from(amount);
+            match add {
+                true => state.add_balance(&address, &amount).unwrap(),
+                false => state.sub_balance(&address, &amount).unwrap(),
+            }
+        };
+        transfer(sender, false, 21000 * 10 + 1);
+        transfer(receiver, true, 1);
+        transfer(coinbase, true, 21000 * 3);
+        state.update_state_post_tx_execution(false);
+
+        let burnt = U512::from(21000 * 7);
+        let delta = |minted: u64, burnt: U512| {
+            value_delta(&state, &pre, &addresses, minted.into(), burnt).unwrap()
+        };
+        assert!(delta(0, burnt).is_none());
+        assert!(matches!(
+            delta(0, U512::zero()),
+            Some(ValueDelta::Destroyed(value)) if value == U256::from(21000 * 7)
+        ));
+        // A withdrawal not credited, or a fee burnt twice
+        assert!(matches!(
+            delta(5, burnt),
+            Some(ValueDelta::Destroyed(value)) if value == U256::from(5)
+        ));
+        assert!(matches!(
+            delta(0, burnt * 2),
+            Some(ValueDelta::Created(value)) if value == U256::from(21000 * 7)
+        ));
+    }
+}
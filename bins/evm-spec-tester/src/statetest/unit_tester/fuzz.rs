@@ -0,0 +1,526 @@
+use super::{
+    super::{observer::Observer, shard::fnv1a},
+    post_transact, pre_transact,
+    state_root::compute_state_root,
+    Checks, ExecContext, UnitOptions, UnitTester,
+};
+use cfx_executor::machine::Machine;
+use cfx_types::{Address, Space, U256};
+use cfxcore::verification::VerificationConfig;
+use eest_types::{
+    AccountInfo, SpecId, SpecName, StateTest, StateTestUnit, TransactionParts,
+    TxPartIndices,
+};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+
+/// How many mutated executions to run per unit, and from which seed.
+#[derive(Clone, Copy, Debug)]
+pub struct FuzzOptions {
+    pub iterations: usize,
+    /// Combined with the unit identifier, so a unit is fuzzed the same way
+    /// whatever the other units of the run.
+    pub seed: u64,
+}
+
+/// A change to the transaction parts selected by a post state, or to the
+/// pre-state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    /// Sets the byte at `offset` of the data, padding it with zeros.
+    DataByte {
+        offset: usize,
+        byte: u8,
+    },
+    /// Truncates the data to `len` bytes.
+    DataLen(usize),
+    Value(U256),
+    GasLimit(U256),
+    Nonce(U256),
+    /// Sets the balance of a pre-state account.
+    Balance {
+        address: Address,
+        balance: U256,
+    },
+}
+
+impl Mutation {
+    fn random(
+        rng: &mut Rng, unit: &StateTestUnit, indexes: &TxPartIndices,
+    ) -> Self {
+        let tx = &unit.transaction;
+        let data_len = tx.data.get(indexes.data).map_or(0, |d| d.0.len());
+        match rng.below(6) {
+            0 => Mutation::DataByte {
+                offset: rng.below(data_len + 32),
+                byte: rng.next_u64() as u8,
+            },
+            1 => Mutation::DataLen(rng.below(data_len + 1)),
+            2 => Mutation::Value(rng.interesting(
+                tx.value.get(indexes.value).copied().unwrap_or_default(),
+            )),
+            3 => Mutation::GasLimit(rng.interesting(
+                tx.gas_limit.get(indexes.gas).copied().unwrap_or_default(),
+            )),
+            4 => Mutation::Nonce(rng.interesting(tx.nonce)),
+            _ => {
+                // `pre` is a hash map, sort it to stay reproducible
+                let mut addresses: Vec<_> = unit.pre.keys().copied().collect();
+                addresses.sort();
+                if addresses.is_empty() {
+                    return Mutation::Nonce(rng.interesting(tx.nonce));
+                }
+                let address = addresses[rng.below(addresses.len())];
+                Mutation::Balance {
+                    address,
+                    balance: rng.interesting(unit.pre[&address].balance),
+                }
+            }
+        }
+    }
+
+    fn apply(
+        &self, tx: &mut TransactionParts,
+        pre: &mut HashMap<Address, AccountInfo>, indexes: &TxPartIndices,
+    ) {
+        match self {
+            Mutation::DataByte { offset, byte } => {
+                if let Some(data) = tx.data.get_mut(indexes.data) {
+                    if data.0.len() <= *offset {
+                        data.0.resize(offset + 1, 0);
+                    }
+                    data.0[*offset] = *byte;
+                }
+            }
+            Mutation::DataLen(len) => {
+                if let Some(data) = tx.data.get_mut(indexes.data) {
+                    data.0.truncate(*len);
+                }
+            }
+            Mutation::Value(value) => {
+                if let Some(v) = tx.value.get_mut(indexes.value) {
+                    *v = *value;
+                }
+            }
+            Mutation::GasLimit(gas) => {
+                if let Some(g) = tx.gas_limit.get_mut(indexes.gas) {
+                    *g = *gas;
+                }
+            }
+            Mutation::Nonce(nonce) => tx.nonce = *nonce,
+            Mutation::Balance { address, balance } => {
+                if let Some(account) = pre.get_mut(address) {
+                    account.balance = *balance;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutation::DataByte { offset, byte } => {
+                write!(f, "data[{}] = {:#04x}", offset, byte)
+            }
+            Mutation::DataLen(len) => write!(f, "data truncated to {}", len),
+            Mutation::Value(value) => write!(f, "value = {:#x}", value),
+            Mutation::GasLimit(gas) => write!(f, "gas limit = {:#x}", gas),
+            Mutation::Nonce(nonce) => write!(f, "nonce = {:#x}", nonce),
+            Mutation::Balance { address, balance } => {
+                write!(f, "balance of {:?} = {:#x}", address, balance)
+            }
+        }
+    }
+}
+
+/// A mutated execution that panicked or broke an invariant, with the
+/// smallest set of mutations still failing.
+#[derive(Debug)]
+pub struct FuzzFailure {
+    pub path: String,
+    pub name: String,
+    pub spec: SpecName,
+    pub indexes: TxPartIndices,
+    pub mutations: Vec<Mutation>,
+    pub error: String,
+}
+
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}::{} ({:?}, data {}, gas {}, value {}): {}",
+            self.path,
+            self.name,
+            self.spec,
+            self.indexes.data,
+            self.indexes.gas,
+            self.indexes.value,
+            self.error
+        )?;
+        write!(f, "\tmutations:")?;
+        for mutation in &self.mutations {
+            write!(f, "\n\t\t{}", mutation)?;
+        }
+        Ok(())
+    }
+}
+
+/// A post state to fuzz, with its indexes resolved.
+#[derive(Clone, Copy)]
+struct FuzzCase<'a> {
+    spec: SpecName,
+    /// The index of `test` among the post states of `spec`.
+    index: usize,
+    test: &'a StateTest,
+    indexes: TxPartIndices,
+}
+
+impl UnitTester {
+    /// Runs `fuzz.iterations` executions of the selected post states, each
+    /// with a few random mutations, and only checks that they do not panic
+    /// and keep the invariants of `check_invariants`. The fuzzing of the
+    /// unit stops at the first failure, which is returned minimized.
+    pub fn fuzz(
+        &self, machine: &Arc<Machine>, verification: &VerificationConfig,
+        opts: &UnitOptions, fuzz: &FuzzOptions,
+    ) -> Option<FuzzFailure> {
+        if !self.is_selected(opts) {
+            return None;
+        }
+        // The blob transactions are not executed, see `check_blob_test`
+        if self.unit.transaction.max_fee_per_blob_gas.is_some() {
+            debug!(
+                "Skip fuzzing {}: unsupported transaction type 3",
+                self.name
+            );
+            return None;
+        }
+
+        let (specs, _) = self.pick_specs(opts);
+        let cases: Vec<FuzzCase> = specs
+            .into_iter()
+            .flat_map(|(spec, tests)| {
+                tests
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, test)| (*spec, i, test))
+            })
+            .filter(|(spec, index, _)| {
+                opts.test.map_or(true, |test| {
+                    test.matches(&self.test_id(*spec, *index))
+                })
+            })
+            .filter(|(_, _, test)| {
+                !self.is_unsupported(test)
+                    && (opts.space == Space::Ethereum
+                        || pre_transact::native_skip_reason(&self.unit, test)
+                            .is_none())
+            })
+            .filter_map(|(spec, index, test)| {
+                let indexes =
+                    test.indexes.resolve(&self.unit.transaction).ok()?;
+                Some(FuzzCase {
+                    spec,
+                    index,
+                    test,
+                    indexes,
+                })
+            })
+            .collect();
+        if cases.is_empty() {
+            return None;
+        }
+
+        let ctx = ExecContext {
+            machine,
+            verification,
+            sinks: opts.sinks,
+            space: opts.space,
+            bless: false,
+            coinbase: opts.coinbase,
+            block: opts.block,
+            dump_state: false,
+            reference: None,
+            checks: Checks::default(),
+            spec_from_fork: opts.spec_from_fork || self.has_several_forks(),
+            fork_machines: opts.fork_machines,
+            post_checks: None,
+        };
+        let id = format!("{}::{}", self.path, self.name);
+        let mut rng = Rng(fuzz.seed ^ fnv1a(id.as_bytes()));
+        for _ in 0..fuzz.iterations {
+            let case = cases[rng.below(cases.len())];
+            let count = 1 + rng.below(4);
+            let mutations: Vec<_> = (0..count)
+                .map(|_| Mutation::random(&mut rng, &self.unit, &case.indexes))
+                .collect();
+
+            if let Err(error) = self.run_mutated(&case, &mutations, &ctx) {
+                let (mutations, error) =
+                    self.minimize(&case, mutations, error, &ctx);
+                return Some(FuzzFailure {
+                    path: self.path.clone(),
+                    name: self.name.clone(),
+                    spec: case.spec,
+                    indexes: case.indexes,
+                    mutations,
+                    error,
+                });
+            }
+        }
+        None
+    }
+
+    /// Drops the mutations one at a time as long as the execution still
+    /// fails.
+    fn minimize(
+        &self, case: &FuzzCase, mut mutations: Vec<Mutation>,
+        mut error: String, ctx: &ExecContext,
+    ) -> (Vec<Mutation>, String) {
+        let mut i = 0;
+        while i < mutations.len() {
+            let mut fewer = mutations.clone();
+            fewer.remove(i);
+            match self.run_mutated(case, &fewer, ctx) {
+                Err(e) => {
+                    mutations = fewer;
+                    error = e;
+                }
+                Ok(()) => i += 1,
+            }
+        }
+        (mutations, error)
+    }
+
+    fn run_mutated(
+        &self, case: &FuzzCase, mutations: &[Mutation], ctx: &ExecContext,
+    ) -> Result<(), String> {
+        let mut unit = (*self.unit).clone();
+        for mutation in mutations {
+            mutation.apply(&mut unit.transaction, &mut unit.pre, &case.indexes);
+        }
+        let mutated = UnitTester {
+            path: self.path.clone(),
+            name: self.name.clone(),
+            unit: Arc::new(unit),
+        };
+
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            mutated.check_invariants(case, ctx)
+        }))
+        .unwrap_or_else(|payload| {
+            Err(format!("panicked: {}", panic_message(&*payload)))
+        })
+    }
+
+    /// Executes the transaction of `case` without comparing with the post
+    /// state. A transaction rejected before execution is fine, an executed
+    /// one must not use more gas than it is given, and the state root of the
+    /// resulting state must compute.
+    fn check_invariants(
+        &self, case: &FuzzCase, ctx: &ExecContext,
+    ) -> Result<(), String> {
+        let FuzzCase {
+            spec,
+            index,
+            test,
+            indexes,
+        } = *case;
+        let ExecContext {
+            machine,
+            verification,
+            sinks,
+            space,
+            coinbase,
+            block,
+            ..
+        } = *ctx;
+        let fork_machine = ctx.fork_machine(spec);
+        let machine = fork_machine.as_deref().unwrap_or(machine);
+        let ctx = &ExecContext { machine, ..*ctx };
+
+        let Ok(tx) = self.make_tx(
+            &self.unit.transaction,
+            &test.txbytes,
+            &indexes,
+            machine,
+            space,
+        ) else {
+            return Ok(());
+        };
+        let mut env =
+            pre_transact::make_block_env(machine, &self.unit, spec, tx.hash());
+        if let Some(coinbase) = coinbase {
+            env.author = coinbase;
+        }
+        block.apply(&mut env);
+        let params = machine.params();
+        let tx_spec = pre_transact::make_spec(params, &env, spec);
+        let mut state = pre_transact::make_state(&self.unit.pre, space);
+        // A mutation of the pre-state may make a preceding transaction fail
+        let Ok(accumulated_gas_used) = self.apply_preceding_txs(
+            ctx,
+            &self.test_id(spec, index),
+            spec,
+            &env,
+            &mut state,
+            params,
+            &tx_spec,
+        ) else {
+            return Ok(());
+        };
+        env.accumulated_gas_used = accumulated_gas_used;
+        if pre_transact::check_tx_common(
+            params,
+            &env,
+            &tx,
+            verification,
+            &tx_spec,
+            &mut state,
+        )
+        .is_err()
+        {
+            return Ok(());
+        }
+
+        let transact_options = pre_transact::make_transact_options(
+            true,
+            Observer::new(sinks),
+            space,
+        );
+        let outcome = self
+            .transact(
+                machine,
+                &env,
+                &mut state,
+                &tx,
+                transact_options,
+                &tx_spec,
+            )
+            .map_err(|kind| kind.to_string())?;
+
+        if let Some(executed) = outcome.try_as_executed() {
+            let gas_limit = *tx.gas_limit();
+            if executed.gross_gas_used > gas_limit {
+                return Err(format!(
+                    "gross gas used {} above the gas limit {}",
+                    executed.gross_gas_used, gas_limit
+                ));
+            }
+            if executed.gas_used > executed.gross_gas_used {
+                return Err(format!(
+                    "gas used {} above the gross gas used {}",
+                    executed.gas_used, executed.gross_gas_used
+                ));
+            }
+            if executed.gas_charged > gas_limit {
+                return Err(format!(
+                    "gas charged {} above the gas limit {}",
+                    executed.gas_charged, gas_limit
+                ));
+            }
+            post_transact::distribute_tx_fee_to_miner(
+                &mut state, executed, &tx, &env,
+            );
+        }
+
+        if space == Space::Ethereum {
+            compute_state_root(
+                &mut state,
+                self.unit.pre.keys().copied(),
+                spec.to_spec_id() >= SpecId::SPURIOUS_DRAGON,
+            );
+        }
+        Ok(())
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic payload"
+    }
+}
+
+/// SplitMix64, enough to pick mutations reproducibly.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A value likely to hit an edge case, near `current` or a boundary.
+    fn interesting(&mut self, current: U256) -> U256 {
+        match self.below(7) {
+            0 => U256::zero(),
+            1 => U256::one(),
+            2 => current.saturating_add(U256::one()),
+            3 => current.saturating_sub(U256::one()),
+            4 => U256::from(self.next_u64()),
+            5 => U256::MAX,
+            _ => U256::one() << self.below(256),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mutation;
+    use cfx_types::U256;
+    use eest_types::{TransactionParts, TxPartIndices};
+    use std::collections::HashMap;
+
+    #[test]
+    fn mutations_change_the_selected_parts() {
+        let mut tx = TransactionParts {
+            data: vec![vec![1, 2].into(), vec![3].into()],
+            gas_limit: vec![U256::from(21000)],
+            value: vec![U256::zero(), U256::one()],
+            ..Default::default()
+        };
+        let indexes = TxPartIndices {
+            data: 1,
+            gas: 0,
+            value: 1,
+        };
+        let mut pre = HashMap::new();
+
+        for mutation in [
+            Mutation::DataByte {
+                offset: 2,
+                byte: 0xff,
+            },
+            Mutation::Value(U256::from(7)),
+            Mutation::GasLimit(U256::MAX),
+        ] {
+            mutation.apply(&mut tx, &mut pre, &indexes);
+        }
+
+        assert_eq!(tx.data[0].0, vec![1, 2]);
+        assert_eq!(tx.data[1].0, vec![3, 0, 0xff]);
+        assert_eq!(tx.value, vec![U256::zero(), U256::from(7)]);
+        assert_eq!(tx.gas_limit, vec![U256::MAX]);
+
+        Mutation::DataLen(1).apply(&mut tx, &mut pre, &indexes);
+        assert_eq!(tx.data[1].0, vec![3]);
+    }
+}
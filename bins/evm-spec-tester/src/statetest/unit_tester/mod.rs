@@ -1,26 +1,620 @@
+mod conservation;
+mod fuzz;
 mod post_transact;
 mod pre_transact;
+mod precompile_gas;
+mod state_root;
+#[cfg(test)]
+mod test_tx;
+mod tx_bytes;
 
-use self::post_transact::is_unsupport_reason;
+pub use self::{
+    fuzz::{FuzzFailure, FuzzOptions, Mutation},
+    post_transact::{BlessedState, StateSurface},
+    pre_transact::ForkMachines,
+};
+use self::{
+    post_transact::is_unsupport_reason, pre_transact::PreState,
+    state_root::compute_state_root,
+};
 
 use super::{
-    error::{TestError, TestErrorKind},
-    utils::extract_155_chain_id_from_raw_tx,
+    error::{Repeats, TestError, TestErrorKind},
+    failing::FailingList,
+    memory::{self, MemoryStats},
+    observer::{
+        CoinbaseAccessMismatch, CoinbaseAccessTracer, CreateAddressMismatch,
+        CreationTracer, GasForwardingTracer, GasForwardingViolation, Observer,
+        PrecompileTracer, SelfdestructTracer, Sinks, StorageWritesTracer,
+        TouchTracer, TransientStorageTracer,
+    },
+    output_dir::OutputDir,
+    perf::PerfTotals,
+    post_check::PostChecks,
+    progress::Progress,
+    reference::RefEvm,
+    report::{FixtureInfo, OutcomeKind, TestRecord, TestStatus},
+    runner::TestBudget,
+    shard::Shard,
+    span::TestSpan,
+    utils::{extract_155_chain_id_from_raw_tx, path_has_suffix},
+    xfail::XfailList,
 };
 use cfx_executor::{
     executive::{ExecutionOutcome, ExecutiveContext, TransactOptions},
     machine::Machine,
+    observer::ExecutiveObserver,
+    spec::CommonParams,
     state::State,
 };
-use cfx_vm_types::Env;
+use cfx_rpc_eth_types::Bytes;
+use cfx_types::{Address, Space, H256, U256};
+use cfx_vm_types::{Env, Spec};
 use cfxcore::verification::VerificationConfig;
-use eest_types::{SpecId, SpecName, StateTest, StateTestUnit};
-use primitives::SignedTransaction;
+use eest_types::{
+    SpecId, SpecName, StateTest, StateTestUnit, TransactionParts,
+    TransactionType, TxPartIndices,
+};
+use primitives::{transaction::Action, SignedTransaction};
+use regex::Regex;
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt,
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// The newest spec supported by the executor.
+pub const LATEST_SUPPORTED_SPEC: SpecId = SpecId::PRAGUE;
+
+/// Most workers of timed out tests left running at once, see
+/// `execute_with_timeout`. Each of them keeps a core busy and its state in
+/// memory until its test ends, if ever.
+const MAX_ABANDONED_WORKERS: usize = 8;
+
+/// The workers of timed out tests still running.
+static ABANDONED_WORKERS: AtomicUsize = AtomicUsize::new(0);
 
+/// The states of a worker of `execute_with_timeout`.
+const WORKER_RUNNING: u8 = 0;
+const WORKER_DONE: u8 = 1;
+const WORKER_ABANDONED: u8 = 2;
+
+#[derive(Clone)]
 pub struct UnitTester {
     path: String,
     name: String,
-    unit: StateTestUnit,
+    unit: Arc<StateTestUnit>,
+}
+
+/// Options shared by every unit of a run.
+pub struct UnitOptions<'a> {
+    /// Only run units whose `path::name` matches this regex.
+    pub matches: Option<&'a Regex>,
+    /// Only run the post states of these forks, all forks if empty.
+    pub forks: &'a [SpecName],
+    /// Skip the post states of the forks after this one.
+    pub max_spec: SpecId,
+    /// Only run the post states of the forks in this range.
+    pub fork_range: Option<ForkRange>,
+    /// Only run this post state, whatever `forks` and `max_spec`.
+    pub test: Option<&'a TestId>,
+    /// Only run these post states.
+    pub only_failing: Option<&'a FailingList>,
+    /// Tracer outputs of every execution.
+    pub sinks: &'a Sinks,
+    /// Abandon a test running longer than this and report it as timed out.
+    pub timeout: Option<Duration>,
+    /// Run a test failing on a db error again on a fresh state, up to this
+    /// many times.
+    pub retries: usize,
+    /// Run a failing test this many more times, each on a fresh state, and
+    /// record how many of the repeats failed in `TestError::repeats`. The
+    /// failures expected by `xfail` are not repeated.
+    pub repeat_failures: usize,
+    /// Skip the tests whose transaction gas limit is above this, before
+    /// transacting.
+    pub max_gas: Option<U256>,
+    /// Space to build the state and the transaction in.
+    pub space: Space,
+    /// Only run the post states assigned to this shard.
+    pub shard: Option<Shard>,
+    /// Run the remaining post states of a spec after one fails.
+    pub keep_going: bool,
+    /// Run the remaining post states of a unit after one fails, while still
+    /// cancelling the units not started yet unless `keep_going`.
+    pub keep_going_in_unit: bool,
+    /// Compute the post states instead of checking them, see `BlessedPost`.
+    pub bless: bool,
+    /// Block author replacing the fixture coinbase.
+    pub coinbase: Option<Address>,
+    /// Block number and timestamp replacing the ones of the fixture.
+    pub block: BlockOverrides,
+    /// Tests expected to fail.
+    pub xfail: Option<&'a XfailList>,
+    /// Write the post state of every passing test to this directory, see
+    /// `dump_post_state`.
+    pub dump_state: Option<&'a OutputDir>,
+    /// Compare the state root and gas used of every test with this EVM.
+    pub reference: Option<&'a RefEvm>,
+    /// The checks run on top of the post state.
+    pub checks: Checks,
+    /// Build the spec and the builtins of every test from its fork rather
+    /// than from its block height, see `pre_transact::fork_machine`. Always
+    /// done for the units with the post states of several forks.
+    pub spec_from_fork: bool,
+    /// The machines of the forks shared by the tests of the run. Without
+    /// them, every test built from its fork builds its own machine.
+    pub fork_machines: Option<&'a ForkMachines>,
+    /// Extra invariants checked after the post state of every test.
+    pub post_checks: Option<&'a PostChecks>,
+    /// Counts the executed tests.
+    pub progress: Option<&'a Progress>,
+    /// Stop executing tests once this many have been.
+    pub budget: Option<&'a TestBudget>,
+}
+
+/// What a single test runs with, besides the unit.
+#[derive(Clone, Copy)]
+struct ExecContext<'a> {
+    machine: &'a Machine,
+    verification: &'a VerificationConfig,
+    sinks: &'a Sinks,
+    space: Space,
+    bless: bool,
+    coinbase: Option<Address>,
+    block: BlockOverrides,
+    dump_state: bool,
+    /// The reference EVM, with the JSON of the unit it is sent.
+    reference: Option<(&'a RefEvm, &'a serde_json::Value)>,
+    checks: Checks,
+    spec_from_fork: bool,
+    fork_machines: Option<&'a ForkMachines>,
+    post_checks: Option<&'a PostChecks>,
+}
+
+impl ExecContext<'_> {
+    /// The machine of the `spec` tests built from their fork, if
+    /// `spec_from_fork`.
+    fn fork_machine(&self, spec: SpecName) -> Option<Arc<Machine>> {
+        if !self.spec_from_fork {
+            return None;
+        }
+        Some(match self.fork_machines {
+            Some(machines) => machines.get(spec),
+            None => Arc::new(pre_transact::fork_machine(self.machine, spec)),
+        })
+    }
+}
+
+/// A test that did not fail.
+enum TestRun {
+    Passed(TestPass),
+    Skipped(SkipReason),
+}
+
+/// A test that passed.
+struct TestPass {
+    gas_used: U256,
+    /// How the executor handled the transaction, `None` for a blob
+    /// transaction, which is not executed.
+    outcome: Option<OutcomeKind>,
+    /// The post state computed for the fixture, if blessed.
+    blessed: Option<BlessedState>,
+    /// The post state as a genesis allocation, if dumped.
+    dumped: Option<serde_json::Value>,
+    /// What a second run must agree on, if the test is run twice.
+    fingerprint: Option<RunFingerprint>,
+    /// The state changed by the test, empty if its post state is not
+    /// checked.
+    surface: StateSurface,
+}
+
+impl TestPass {
+    fn checked(gas_used: U256) -> Self {
+        TestPass {
+            gas_used,
+            outcome: None,
+            blessed: None,
+            dumped: None,
+            fingerprint: None,
+            surface: StateSurface::default(),
+        }
+    }
+
+    /// The error of the second run of this test, `None` if it passed with
+    /// the same result.
+    fn check_second_run(
+        &self, second: &Result<TestRun, TestError>,
+    ) -> Option<TestErrorKind> {
+        let second = match second {
+            Ok(TestRun::Passed(second))
+                if second.summary() == self.summary() =>
+            {
+                return None;
+            }
+            Ok(TestRun::Passed(second)) => second.summary(),
+            Ok(TestRun::Skipped(reason)) => format!("skipped: {}", reason),
+            Err(e) => format!("failed: {}", e.kind),
+        };
+        Some(TestErrorKind::Nondeterministic {
+            first: self.summary(),
+            second,
+        })
+    }
+
+    fn summary(&self) -> String {
+        let mut summary =
+            format!("outcome {:?}, gas used {}", self.outcome, self.gas_used);
+        if let Some(fingerprint) = &self.fingerprint {
+            summary += &format!(
+                ", logs {:?}, state root {:?}",
+                fingerprint.logs_hash, fingerprint.state_root
+            );
+        }
+        summary
+    }
+}
+
+/// The hashes of the result of a test compared between its two runs, see
+/// `Checks::double_run`. The state root covers the accounts of the
+/// fixture and the ones touched by the transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RunFingerprint {
+    logs_hash: H256,
+    state_root: H256,
+}
+
+/// A post state computed by a run, to be written back to the fixture.
+pub struct BlessedPost {
+    pub path: String,
+    pub name: String,
+    pub spec: SpecName,
+    /// Position of the post state among the ones of `spec`.
+    pub index: usize,
+    pub state: BlessedState,
+}
+
+/// Pass/fail tally of the tests executed under a single spec.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpecTally {
+    pub passed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// Failed as expected by the xfail list.
+    pub xfailed: usize,
+    /// Passed although listed in the xfail list.
+    pub xpassed: usize,
+}
+
+impl SpecTally {
+    pub fn merge(&mut self, other: &SpecTally) {
+        self.passed += other.passed;
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+        self.xfailed += other.xfailed;
+        self.xpassed += other.xpassed;
+    }
+
+    /// Number of executions, including the failed ones.
+    pub fn executed(&self) -> usize {
+        self.passed + self.failed + self.xfailed + self.xpassed
+    }
+}
+
+/// Fully-qualified identifier of a single post state, printed as
+/// `path::name::spec::index`.
+#[derive(Clone, Debug)]
+pub struct TestId {
+    pub path: String,
+    pub name: String,
+    pub spec: SpecName,
+    /// Position of the post state among the ones of `spec`.
+    pub index: usize,
+}
+
+impl fmt::Display for TestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}::{}::{:?}::{}",
+            self.path, self.name, self.spec, self.index
+        )
+    }
+}
+
+impl FromStr for TestId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Test names may contain `::`, fixture paths and specs do not
+        let parsed = s.split_once("::").and_then(|(path, rest)| {
+            let (rest, index) = rest.rsplit_once("::")?;
+            let (name, spec) = rest.rsplit_once("::")?;
+            Some((path, name, spec, index))
+        });
+        let Some((path, name, spec, index)) = parsed else {
+            return Err(format!("Expect path::name::spec::index, got: {}", s));
+        };
+        let spec = match serde_json::from_value(spec.into()) {
+            Ok(SpecName::Unknown) | Err(_) => {
+                return Err(format!("Unknown fork name: {}", spec))
+            }
+            Ok(spec) => spec,
+        };
+        let index = index
+            .parse()
+            .map_err(|_| format!("Invalid post state index: {}", index))?;
+        Ok(TestId {
+            path: path.to_string(),
+            name: name.to_string(),
+            spec,
+            index,
+        })
+    }
+}
+
+/// Block fields replacing the ones of the fixtures, to probe a test on both
+/// sides of a transition height. The spec of a test is built from the
+/// overridden number, unless `UnitOptions::spec_from_fork`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockOverrides {
+    pub number: Option<u64>,
+    pub timestamp: Option<u64>,
+}
+
+impl BlockOverrides {
+    fn apply(&self, env: &mut Env) {
+        if let Some(number) = self.number {
+            env.number = number;
+            env.epoch_height = number;
+        }
+        if let Some(timestamp) = self.timestamp {
+            env.timestamp = timestamp;
+        }
+    }
+
+    /// Applies the overrides to the `env` of a fixture, the one the reference
+    /// EVM is sent.
+    fn apply_to_fixture(&self, env: &mut serde_json::Value) {
+        if let Some(number) = self.number {
+            env["currentNumber"] = format!("{:#x}", number).into();
+        }
+        if let Some(timestamp) = self.timestamp {
+            env["currentTimestamp"] = format!("{:#x}", timestamp).into();
+        }
+    }
+}
+
+/// The checks of a test on top of its post state, each enabled by the flag
+/// of its field. They are parsed and passed together, so that a check is
+/// added in one place and `flags` gives it back to a repro command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, StructOpt)]
+pub struct Checks {
+    /// Check that the callee of every CALL/CREATE gets the gas of the EIP-150
+    /// forwarding rule of the fork, and fail the test at the first call that
+    /// does not. Traces every opcode, which slows the run down
+    #[structopt(long)]
+    pub check_gas_forwarding: bool,
+
+    /// Record the precompile calls of every transaction, and print the gas
+    /// each of them charged when the gas used of a test does not match
+    #[structopt(long)]
+    pub trace_precompiles: bool,
+
+    /// Fail a test whose transaction reverts if it leaves a slot written by
+    /// SSTORE, or a refund, that the revert should have undone. Traces every
+    /// opcode, which slows the run down
+    #[structopt(long)]
+    pub check_revert_rollback: bool,
+
+    /// Check that the first BALANCE, EXTCODESIZE or EXTCODEHASH of the
+    /// coinbase charges the cost of its warmth at the start of the
+    /// transaction (EIP-3651). Traces every opcode, which slows the run
+    /// down
+    #[structopt(long)]
+    pub check_coinbase_access: bool,
+
+    /// Check the gas charged by every successful MODEXP and ECPAIRING call
+    /// against the formula of the fork of the test
+    #[structopt(long)]
+    pub check_precompile_gas: bool,
+
+    /// Fail a test if the executor commits an account outside of the
+    /// pre-state that the fixture does not expect to change, such as an
+    /// account it only read but marked dirty
+    #[structopt(long, conflicts_with_all = &["bless", "list", "fuzz"])]
+    pub audit_touches: bool,
+
+    /// Fail a test if the balances of the Ethereum space accounts do not add
+    /// up to those of the pre-state, plus the withdrawals and less the burnt
+    /// fees
+    #[structopt(long, conflicts_with_all = &["bless", "list", "fuzz"])]
+    pub check_conservation: bool,
+
+    /// Run every passing test a second time on a state built from scratch,
+    /// and fail it if the two runs disagree on the outcome, the gas used, the
+    /// logs or the state root
+    #[structopt(long, conflicts_with_all = &["bless", "list", "fuzz"])]
+    pub double_run: bool,
+}
+
+impl Checks {
+    /// The command line flags enabling these checks.
+    pub fn flags(&self) -> Vec<&'static str> {
+        let Checks {
+            check_gas_forwarding,
+            trace_precompiles,
+            check_revert_rollback,
+            check_coinbase_access,
+            check_precompile_gas,
+            audit_touches,
+            check_conservation,
+            double_run,
+        } = *self;
+        [
+            (check_gas_forwarding, "--check-gas-forwarding"),
+            (trace_precompiles, "--trace-precompiles"),
+            (check_revert_rollback, "--check-revert-rollback"),
+            (check_coinbase_access, "--check-coinbase-access"),
+            (check_precompile_gas, "--check-precompile-gas"),
+            (audit_touches, "--audit-touches"),
+            (check_conservation, "--check-conservation"),
+            (double_run, "--double-run"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, flag)| enabled.then_some(flag))
+        .collect()
+    }
+}
+
+/// The forks from `start` to `end` included, parsed from `Start..End`.
+#[derive(Clone, Copy, Debug)]
+pub struct ForkRange {
+    pub start: SpecName,
+    pub end: SpecName,
+}
+
+impl ForkRange {
+    pub fn contains(&self, spec: SpecId) -> bool {
+        (self.start.to_spec_id()..=self.end.to_spec_id()).contains(&spec)
+    }
+}
+
+impl fmt::Display for ForkRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}..{:?}", self.start, self.end)
+    }
+}
+
+impl FromStr for ForkRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = s.split_once("..") else {
+            return Err(format!("Expect Start..End, got: {}", s));
+        };
+        let range = ForkRange {
+            start: parse_range_fork(start)?,
+            end: parse_range_fork(end)?,
+        };
+        if range.start.to_spec_id() > range.end.to_spec_id() {
+            return Err(format!("Empty fork range: {}", s));
+        }
+        Ok(range)
+    }
+}
+
+/// The spec names that can bound a `ForkRange`, leaving out the ones
+/// overridden by Petersburg.
+fn range_forks() -> impl Iterator<Item = SpecName> {
+    SpecName::ALL.iter().copied().filter(|spec| {
+        !matches!(
+            spec,
+            SpecName::ByzantiumToConstantinopleAt5 | SpecName::Constantinople
+        )
+    })
+}
+
+fn parse_range_fork(name: &str) -> Result<SpecName, String> {
+    range_forks()
+        .find(|spec| format!("{:?}", spec) == name)
+        .ok_or_else(|| {
+            let names: Vec<_> =
+                range_forks().map(|spec| format!("{:?}", spec)).collect();
+            format!(
+                "Unknown fork name: {}, expect one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+impl TestId {
+    /// Whether the fixture `path` ends with the one of this identifier.
+    pub fn matches_path(&self, path: &str) -> bool {
+        path_has_suffix(path, &self.path)
+    }
+
+    /// Whether `other` is this test, see `matches_path`.
+    pub fn matches(&self, other: &TestId) -> bool {
+        self.name == other.name
+            && self.spec == other.spec
+            && self.index == other.index
+            && self.matches_path(&other.path)
+    }
+}
+
+/// Post states of a unit, by spec.
+type SpecTests<'a> = Vec<(&'a SpecName, &'a Vec<StateTest>)>;
+
+/// Result of running every selected spec of a test unit.
+#[derive(Default)]
+pub struct UnitResult {
+    pub status: UnitStatus,
+    /// The state changed by the passing tests of the unit.
+    pub surface: StateSurface,
+    /// The post states skipped for the type of their transaction, by type.
+    pub unsupported_tx_types: BTreeMap<u8, usize>,
+    /// The post states skipped for a fork after `UnitOptions::max_spec`, by
+    /// fork, also of a unit whose other post states ran.
+    pub above_cap: BTreeMap<SpecName, usize>,
+    pub specs: BTreeMap<SpecName, SpecTally>,
+    pub errors: Vec<TestError>,
+    pub records: Vec<TestRecord>,
+    pub blessed: Vec<BlessedPost>,
+}
+
+/// Whether a unit had post states to run, and why not, so that the forks
+/// missing from a run are told apart from the filters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnitStatus {
+    /// Some post states were picked, this many of which were executed, the
+    /// others being skipped on their own.
+    Ran(usize),
+    /// The unit, or every post state of it, was left out by the filters of
+    /// the options.
+    #[default]
+    FilteredOut,
+    /// Every post state picked is of a fork after `UnitOptions::max_spec`,
+    /// see `UnitResult::above_cap` for their forks.
+    SkippedAboveCap,
+    /// The unit has no post state at all.
+    NoPostStates,
+}
+
+/// Why a post state was skipped once run, so that the fixtures the executor
+/// can not run yet are counted apart from the ones skipped on purpose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The transaction is of an EIP-2718 type the executor has no
+    /// transaction of in the space of the run, such as a blob one.
+    UnsupportedTxType(u8),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UnsupportedTxType(tx_type) => {
+                write!(f, "unsupported transaction type {}", tx_type)
+            }
+        }
+    }
+}
+
+impl UnitResult {
+    /// Number of executions, including the failed ones.
+    pub fn executed(&self) -> usize {
+        self.specs.values().map(SpecTally::executed).sum()
+    }
 }
 
 impl UnitTester {
@@ -28,7 +622,30 @@ impl UnitTester {
         UnitTester {
             path: path.clone(),
             name,
-            unit,
+            unit: Arc::new(unit),
+        }
+    }
+
+    /// A record of the unit, or of its `index`-th post state of `spec` if
+    /// `test` is given as `(index, test)`.
+    fn record(
+        &self, spec: Option<SpecName>, test: Option<(usize, &StateTest)>,
+        status: TestStatus,
+    ) -> TestRecord {
+        TestRecord {
+            path: self.path.clone(),
+            name: self.name.clone(),
+            spec,
+            index: test.map(|(index, _)| index),
+            indexes: test.map(|(_, t)| t.indexes.clone()),
+            status,
+            error: None,
+            reason: None,
+            duration_ms: 0.0,
+            gas_used: None,
+            outcome: None,
+            memory: None,
+            info: self.unit.info.as_ref().and_then(FixtureInfo::of),
         }
     }
 
@@ -36,157 +653,1634 @@ impl UnitTester {
         TestError {
             name: self.name.clone(),
             path: self.path.clone(),
+            spec: None,
+            index: None,
             kind,
+            outcome: None,
+            repeats: None,
+        }
+    }
+
+    fn is_selected(&self, opts: &UnitOptions) -> bool {
+        opts.test.map_or(true, |test| {
+            test.name == self.name && test.matches_path(&self.path)
+        }) && opts
+            .only_failing
+            .map_or(true, |list| list.has_unit(&self.path, &self.name))
+            && opts.matches.map_or(true, |re| {
+                re.is_match(&format!("{}::{}", &self.path, &self.name))
+            })
+    }
+
+    /// Whether `test` expects an exception the tester can not check. An
+    /// expectation that can not be resolved is not, it fails once run.
+    fn is_unsupported(&self, test: &StateTest) -> bool {
+        let expected = test
+            .indexes
+            .resolve(&self.unit.transaction)
+            .ok()
+            .and_then(|indexes| test.expected_exception(&indexes).ok())
+            .flatten();
+        is_unsupport_reason(expected)
+    }
+
+    /// Why `test` is skipped with `opts` before transacting, if it is: its
+    /// gas limit is above `max_gas`, or it has no native analogue.
+    fn skip_reason(
+        &self, opts: &UnitOptions, test: &StateTest,
+    ) -> Option<String> {
+        let max_gas = opts.max_gas.and_then(|max_gas| {
+            pre_transact::max_gas_skip_reason(&self.unit, test, max_gas)
+        });
+        max_gas.or_else(|| {
+            (opts.space == Space::Native)
+                .then(|| pre_transact::native_skip_reason(&self.unit, test))
+                .flatten()
+        })
+    }
+
+    /// The specs to run with `opts`, and the ones skipped after
+    /// `opts.max_spec`.
+    fn pick_specs(&self, opts: &UnitOptions) -> (SpecTests, SpecTests) {
+        match opts.test {
+            Some(test) => (
+                self.unit
+                    .post
+                    .iter()
+                    .filter(|(spec, _)| **spec == test.spec)
+                    .collect(),
+                vec![],
+            ),
+            None => pick_specs(
+                self.unit.post.iter(),
+                opts.forks,
+                opts.fork_range,
+                opts.max_spec,
+            ),
         }
     }
 
+    /// Whether the `index`-th post state of `spec` is in the shard of `opts`,
+    /// and is the test of `opts.test` or of `opts.only_failing` if any.
+    fn is_picked(
+        &self, opts: &UnitOptions, spec: SpecName, index: usize,
+    ) -> bool {
+        let id = self.test_id(spec, index);
+        opts.test.map_or(true, |test| test.matches(&id))
+            && opts.only_failing.map_or(true, |list| list.contains(&id))
+            && opts
+                .shard
+                .map_or(true, |shard| shard.contains(&id.to_string()))
+    }
+
+    /// Whether the spec of the tests is built from their fork with
+    /// `spec_from_fork` unset. The post states of every fork run at the
+    /// block of the unit, so the spec of the block would check them all
+    /// with the same activations. Decided by the forks of the fixture
+    /// rather than the selected ones, so that the repro command of a test
+    /// runs it with the same spec.
+    fn has_several_forks(&self) -> bool { self.unit.post.len() > 1 }
+
+    fn test_id(&self, spec: SpecName, index: usize) -> TestId {
+        TestId {
+            path: self.path.clone(),
+            name: self.name.clone(),
+            spec,
+            index,
+        }
+    }
+
+    /// Every post state of the unit, whatever the options.
+    pub fn ids(&self) -> impl Iterator<Item = TestId> + '_ {
+        self.unit.post.iter().flat_map(move |(spec, tests)| {
+            (0..tests.len()).map(move |index| self.test_id(*spec, index))
+        })
+    }
+
+    /// The post states `run` would execute with `opts`, leaving out the ones
+    /// it would skip before transacting.
+    pub fn list(&self, opts: &UnitOptions) -> Vec<TestId> {
+        if !self.is_selected(opts) {
+            return vec![];
+        }
+
+        let (specs, _) = self.pick_specs(opts);
+        let mut ids = vec![];
+        for (spec, tests) in specs {
+            for (index, test) in tests.iter().enumerate() {
+                if !self.is_picked(opts, *spec, index)
+                    || self.is_unsupported(test)
+                    || self.skip_reason(opts, test).is_some()
+                {
+                    continue;
+                }
+                ids.push(self.test_id(*spec, index));
+            }
+        }
+        ids
+    }
+
     pub fn run(
-        &self, machine: &Machine, verification: &VerificationConfig,
-        matches: Option<&str>,
-    ) -> Result<usize, TestError> {
-        if !matches.map_or(true, |pat| {
-            format!("{}::{}", &self.path, &self.name).contains(pat)
-        }) {
-            return Ok(0);
+        &self, machine: &Arc<Machine>, verification: &VerificationConfig,
+        opts: &UnitOptions,
+    ) -> UnitResult {
+        let mut result = UnitResult::default();
+
+        if !self.is_selected(opts) {
+            return result;
         }
 
-        if matches.is_some() {
-            info!("Running TestUnit: {}", self.name);
+        debug!("Running TestUnit: {}", self.name);
+
+        let (specs, beyond_max) = self.pick_specs(opts);
+        for (spec, tests) in &beyond_max {
+            let tests: Vec<_> = tests
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| self.is_picked(opts, **spec, *index))
+                .collect();
+            if tests.is_empty() {
+                continue;
+            }
+            debug!(
+                "Skip spec {:?} of {}: after max fork {:?}",
+                spec, self.name, opts.max_spec
+            );
+            *result.above_cap.entry(**spec).or_default() += tests.len();
+            result.specs.entry(**spec).or_default().skipped += tests.len();
+            for test in tests {
+                let mut record =
+                    self.record(Some(**spec), Some(test), TestStatus::Skipped);
+                record.reason =
+                    Some(format!("after max fork {:?}", opts.max_spec));
+                result.records.push(record);
+            }
+        }
+
+        // A unit with nothing to run is reported by a single shard
+        let unit_in_shard = opts.shard.map_or(true, |shard| {
+            shard.contains(&format!("{}::{}", self.path, self.name))
+        });
+        result.status = if !result.above_cap.is_empty() {
+            UnitStatus::SkippedAboveCap
+        } else if self.unit.post.is_empty() {
+            UnitStatus::NoPostStates
         } else {
-            trace!("Running TestUnit: {}", self.name);
+            UnitStatus::FilteredOut
+        };
+        if specs.is_empty() && beyond_max.is_empty() && unit_in_shard {
+            let mut record = self.record(None, None, TestStatus::Skipped);
+            record.reason = match result.status {
+                UnitStatus::NoPostStates => Some("no post states".to_string()),
+                _ => opts
+                    .fork_range
+                    .map(|range| format!("no fork in range {}", range)),
+            };
+            result.records.push(record);
         }
 
-        let Some((spec, tests)) = pick_spec(self.unit.post.iter()) else {
-            return Ok(0);
+        // The reference is sent the fixture JSON, which is not kept by the
+        // loader
+        let ref_unit = match opts
+            .reference
+            .map(|evm| evm.unit(Path::new(&self.path), &self.name))
+            .transpose()
+        {
+            Ok(unit) => unit,
+            Err(e) => {
+                result.errors.push(self.err(TestErrorKind::Internal(format!(
+                    "failed to read the unit for the reference: {}",
+                    e
+                ))));
+                return result;
+            }
         };
 
-        let mut transact_cnt = 0;
-        // running each test
-        for single_test in tests.iter() {
-            if is_unsupport_reason(&single_test.expect_exception) {
+        // Shared by the indexed tests of the unit, which all start from the
+        // same pre-state
+        let mut pre_state = PreState::default();
+        let ctx = ExecContext {
+            machine,
+            verification,
+            sinks: opts.sinks,
+            space: opts.space,
+            bless: opts.bless,
+            coinbase: opts.coinbase,
+            block: opts.block,
+            dump_state: opts.dump_state.is_some(),
+            reference: opts.reference.zip(ref_unit.as_ref()),
+            checks: opts.checks,
+            spec_from_fork: opts.spec_from_fork || self.has_several_forks(),
+            fork_machines: opts.fork_machines,
+            post_checks: opts.post_checks,
+        };
+        'specs: for (spec, tests) in specs {
+            let tests: Vec<_> = tests
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| self.is_picked(opts, *spec, *index))
+                .collect();
+            if tests.is_empty() {
                 continue;
             }
-            if matches.is_some() {
-                info!("Running item with spec {:?}", spec);
+            result.status = UnitStatus::Ran(0);
+            let tally = result.specs.entry(*spec).or_default();
+            // running each test
+            for (index, single_test) in tests {
+                if self.is_unsupported(single_test) {
+                    tally.skipped += 1;
+                    result.records.push(self.record(
+                        Some(*spec),
+                        Some((index, single_test)),
+                        TestStatus::Skipped,
+                    ));
+                    continue;
+                }
+                if let Some(reason) = self.skip_reason(opts, single_test) {
+                    debug!("Skip {:?} of {}: {}", spec, self.name, reason);
+                    tally.skipped += 1;
+                    let mut record = self.record(
+                        Some(*spec),
+                        Some((index, single_test)),
+                        TestStatus::Skipped,
+                    );
+                    record.reason = Some(reason);
+                    result.records.push(record);
+                    continue;
+                }
+                if opts.budget.map_or(false, |budget| !budget.take()) {
+                    debug!("Stop {}: the test count is reached", self.name);
+                    break 'specs;
+                }
+                debug!("Running item with spec {:?}", spec);
+                let start = Instant::now();
+                // Without a pre-state, the state is built from scratch
+                let execute =
+                    |pre_state: Option<&mut PreState>| match opts.timeout {
+                        Some(timeout) => self.execute_with_timeout(
+                            *spec, index, machine, &ctx, timeout,
+                        ),
+                        None => memory::measure(|| {
+                            self.execute_single_test(
+                                single_test,
+                                *spec,
+                                index,
+                                &ctx,
+                                pre_state,
+                            )
+                        }),
+                    };
+                let (mut outcome, mut memory) = execute(Some(&mut pre_state));
+                for attempt in 1..=opts.retries {
+                    if !is_db_error(&outcome) {
+                        break;
+                    }
+                    // The shared pre-state may be left half written
+                    pre_state = PreState::default();
+                    debug!(
+                        "Retrying {} on a fresh state after a db error, \
+                         attempt {}",
+                        self.test_id(*spec, index),
+                        attempt
+                    );
+                    (outcome, memory) = execute(None);
+                }
+                let double_checked =
+                    |outcome: Result<TestRun, TestError>| match (
+                        opts.checks.double_run,
+                        outcome,
+                    ) {
+                        (true, Ok(TestRun::Passed(first))) => {
+                            let (second, _) = execute(None);
+                            match first.check_second_run(&second) {
+                                Some(kind) => Err(TestError {
+                                    outcome: first.outcome,
+                                    ..self.err(kind)
+                                }),
+                                None => Ok(TestRun::Passed(first)),
+                            }
+                        }
+                        (_, outcome) => outcome,
+                    };
+                outcome = double_checked(outcome);
+                let xfail = opts
+                    .xfail
+                    .and_then(|x| x.reason(&self.path, &self.name, *spec));
+                // An expected failure is not reported, nor repeated
+                if let (1.., Err(error), None) =
+                    (opts.repeat_failures, &mut outcome, xfail)
+                {
+                    let runs = opts.repeat_failures;
+                    let failed = (0..runs)
+                        .filter(|_| double_checked(execute(None).0).is_err())
+                        .count();
+                    error.repeats = Some(Repeats { runs, failed });
+                }
+                if let Some(progress) = opts.progress {
+                    progress.inc(*spec);
+                }
+                if let (Some(budget), Ok(TestRun::Skipped(_))) =
+                    (opts.budget, &outcome)
+                {
+                    budget.give_back();
+                }
+                let status = match (&outcome, xfail) {
+                    (Ok(TestRun::Passed(_)), None) => TestStatus::Passed,
+                    (Ok(TestRun::Passed(_)), Some(_)) => TestStatus::Xpass,
+                    (Ok(TestRun::Skipped(_)), _) => TestStatus::Skipped,
+                    (Err(_), None) => TestStatus::Failed,
+                    (Err(_), Some(_)) => TestStatus::Xfail,
+                };
+                let mut record = self.record(
+                    Some(*spec),
+                    Some((index, single_test)),
+                    status,
+                );
+                record.set_duration(start.elapsed());
+                record.memory = memory;
+                record.reason = xfail.map(str::to_string);
+                record.outcome = match &outcome {
+                    Ok(TestRun::Passed(pass)) => pass.outcome,
+                    Ok(TestRun::Skipped(_)) => None,
+                    Err(e) => e.outcome,
+                };
+
+                match (outcome, xfail) {
+                    (Ok(TestRun::Passed(pass)), Some(reason)) => {
+                        tally.xpassed += 1;
+                        record.gas_used = record_gas(pass.gas_used);
+                        result.records.push(record);
+                        let mut e = self.err(TestErrorKind::UnexpectedPass {
+                            reason: reason.to_string(),
+                        });
+                        e.spec = Some(*spec);
+                        e.index = Some(index);
+                        result.errors.push(e);
+                        if !opts.keep_going && !opts.keep_going_in_unit {
+                            break;
+                        }
+                    }
+                    (Ok(TestRun::Passed(pass)), None) => {
+                        tally.passed += 1;
+                        record.gas_used = record_gas(pass.gas_used);
+                        result.records.push(record);
+                        result.surface.merge(pass.surface);
+                        if let (Some(output), Some(alloc)) =
+                            (opts.dump_state, &pass.dumped)
+                        {
+                            let id = self.test_id(*spec, index);
+                            if let Err(e) = post_transact::write_state_dump(
+                                output, &id, alloc,
+                            ) {
+                                error!("Failed to dump state of {}: {}", id, e);
+                            }
+                        }
+                        if let Some(state) = pass.blessed {
+                            result.blessed.push(BlessedPost {
+                                path: self.path.clone(),
+                                name: self.name.clone(),
+                                spec: *spec,
+                                index,
+                                state,
+                            });
+                        }
+                    }
+                    (Ok(TestRun::Skipped(reason)), _) => {
+                        tally.skipped += 1;
+                        record.reason = Some(reason.to_string());
+                        result.records.push(record);
+                        let SkipReason::UnsupportedTxType(tx_type) = reason;
+                        *result
+                            .unsupported_tx_types
+                            .entry(tx_type)
+                            .or_default() += 1;
+                    }
+                    (Err(e), Some(_)) => {
+                        tally.xfailed += 1;
+                        record.error = Some(e.kind.to_string());
+                        result.records.push(record);
+                    }
+                    (Err(mut e), None) => {
+                        tally.failed += 1;
+                        record.error = Some(e.kind.to_string());
+                        result.records.push(record);
+                        e.spec = Some(*spec);
+                        e.index = Some(index);
+                        result.errors.push(e);
+                        if !opts.keep_going && !opts.keep_going_in_unit {
+                            break;
+                        }
+                    }
+                }
             }
-            self.execute_single_test(single_test, machine, verification)?;
-            transact_cnt += 1;
         }
 
-        Ok(transact_cnt)
+        let executed = result.executed();
+        if let UnitStatus::Ran(count) = &mut result.status {
+            *count = executed;
+        }
+        result
     }
 
+    /// Returns why the test has been skipped if it is not supported. The
+    /// state is restored from `pre_state` if given, or built from the unit
+    /// otherwise. `test` is the `index`-th post state of `spec`.
     fn execute_single_test(
-        &self, test: &StateTest, machine: &Machine,
-        verification: &VerificationConfig,
-    ) -> Result<(), TestError> {
-        let mut state = pre_transact::make_state(&self.unit.pre);
+        &self, test: &StateTest, spec: SpecName, index: usize,
+        ctx: &ExecContext, pre_state: Option<&mut PreState>,
+    ) -> Result<TestRun, TestError> {
+        let ExecContext {
+            machine,
+            verification,
+            sinks,
+            space,
+            bless,
+            coinbase,
+            block,
+            dump_state,
+            reference,
+            checks,
+            post_checks,
+            ..
+        } = *ctx;
+        let Checks {
+            check_gas_forwarding,
+            trace_precompiles,
+            check_revert_rollback,
+            check_coinbase_access,
+            check_precompile_gas,
+            audit_touches,
+            check_conservation,
+            double_run,
+        } = checks;
+        let fork_machine = ctx.fork_machine(spec);
+        let machine = fork_machine.as_deref().unwrap_or(machine);
+        let ctx = &ExecContext { machine, ..*ctx };
+        let id = self.test_id(spec, index);
+        let _span = TestSpan::enter(&id);
 
-        let Some(tx) = pre_transact::make_tx(
-            &self.unit.transaction,
-            &test.indexes,
-            self.unit.config.chainid,
-            extract_155_chain_id_from_raw_tx(&test.txbytes).is_none(),
-        ) else {
-            return Ok(());
+        let indexes = test
+            .indexes
+            .resolve(&self.unit.transaction)
+            .map_err(|e| self.err(TestErrorKind::BadIndex(e)))?;
+        let expect_exception = test
+            .expected_exception(&indexes)
+            .map_err(|e| self.err(TestErrorKind::MissingExpectException(e)))?;
+
+        let blob_skip = || {
+            TestRun::Skipped(SkipReason::UnsupportedTxType(
+                TransactionType::Eip4844 as u8,
+            ))
         };
+        if self.unit.transaction.max_fee_per_blob_gas.is_some() {
+            // The blob checks are the Ethereum ones
+            if space == Space::Native {
+                return Ok(blob_skip());
+            }
+            return self.check_blob_test(expect_exception, spec).map(
+                |gas_used| match gas_used {
+                    Some(gas_used) => {
+                        TestRun::Passed(TestPass::checked(gas_used))
+                    }
+                    None => blob_skip(),
+                },
+            );
+        }
 
-        pre_transact::check_tx_bytes(
-            test.txbytes.as_ref().map(|x| &x.0[..]),
-            &tx,
-        )
-        .map_err(|kind| self.err(kind))?;
+        let mut fresh_state;
+        let state = match pre_state {
+            Some(pre_state) => pre_state.checkout(&self.unit.pre, space),
+            None => {
+                fresh_state = pre_transact::make_state(&self.unit.pre, space);
+                &mut fresh_state
+            }
+        };
 
-        let env = pre_transact::make_block_env(
+        if space == Space::Ethereum {
+            pre_transact::check_tx_chain_id(
+                &test.txbytes,
+                self.unit.config.chainid,
+            )
+            .map_err(|kind| self.err(kind))?;
+        }
+        let tx = match self.make_tx(
+            &self.unit.transaction,
+            &test.txbytes,
+            &indexes,
             machine,
-            &self.unit.env,
-            self.unit.config.chainid,
-            tx.hash(),
-        );
+            space,
+        ) {
+            Ok(tx) => tx,
+            Err(reason) => return Ok(TestRun::Skipped(reason)),
+        };
 
-        if let Err(e) =
-            pre_transact::check_tx_common(machine, &env, &tx, verification)
-        {
+        // The fixture only has the Ethereum encoding
+        if space == Space::Ethereum {
+            pre_transact::check_tx_bytes(
+                test.txbytes.as_ref().map(|x| &x.0[..]),
+                &tx,
+            )
+            .map_err(|kind| self.err(kind))?;
+        }
+
+        pre_transact::check_authorization_list(&self.unit.transaction)
+            .map_err(|kind| self.err(kind))?;
+
+        let mut env =
+            pre_transact::make_block_env(machine, &self.unit, spec, tx.hash());
+        if let Some(coinbase) = coinbase {
+            env.author = coinbase;
+        }
+        block.apply(&mut env);
+
+        let params = machine.params();
+        let tx_spec = pre_transact::make_spec(params, &env, spec);
+
+        env.accumulated_gas_used = self.apply_preceding_txs(
+            ctx, &id, spec, &env, state, params, &tx_spec,
+        )?;
+
+        if let Err(e) = pre_transact::check_tx_common(
+            params,
+            &env,
+            &tx,
+            verification,
+            &tx_spec,
+            state,
+        ) {
+            let outcome = Some(OutcomeKind::ConsensusRejected);
             return post_transact::process_consensus_check_fail(
                 e,
-                test.expect_exception.as_ref(),
+                expect_exception,
             )
-            .map_err(|kind| self.err(kind));
+            .map(|()| {
+                TestRun::Passed(TestPass {
+                    outcome,
+                    ..TestPass::checked(U256::zero())
+                })
+            })
+            .map_err(|kind| TestError {
+                outcome,
+                ..self.err(kind)
+            });
         }
 
-        let transact_options = pre_transact::make_transact_options(true);
-
-        let outcome =
-            self.transact(machine, &env, &mut state, &tx, transact_options);
+        let mut observer = Observer::new(sinks);
+        let checks = observer.checks();
+        if trace_precompiles || check_precompile_gas {
+            checks.precompiles = Some(PrecompileTracer::default());
+        }
+        if check_revert_rollback {
+            checks.storage_writes = Some(StorageWritesTracer::default());
+        }
+        if check_gas_forwarding {
+            checks.gas_forwarding =
+                Some(GasForwardingTracer::new(spec.to_spec_id()));
+        }
+        if space == Space::Ethereum {
+            let creation = (tx.action() == Action::Create)
+                .then(|| (tx.sender().address, *tx.nonce()));
+            checks.creation = Some(CreationTracer::new(creation));
+            checks.selfdestruct = Some(SelfdestructTracer::default());
+            if spec.to_spec_id() >= SpecId::CANCUN {
+                checks.transient_storage =
+                    Some(TransientStorageTracer::default());
+            }
+            if spec.to_spec_id() >= SpecId::SPURIOUS_DRAGON {
+                checks.touches = Some(TouchTracer::default());
+            }
+            if check_coinbase_access && tx_spec.cip645.eip_cold_warm_access {
+                let warm = pre_transact::coinbase_warm_at_start(
+                    &tx, env.author, &tx_spec,
+                );
+                checks.coinbase_access =
+                    Some(CoinbaseAccessTracer::new(env.author, warm));
+            }
+        }
+        let transact_options =
+            pre_transact::make_transact_options(true, observer, space);
 
-        let maybe_executed = post_transact::extract_executed(
-            outcome,
-            test.expect_exception.as_ref(),
-        )
+        let outcome = PerfTotals::measure(sinks.perf.as_deref(), || {
+            self.transact(machine, &env, state, &tx, transact_options, &tx_spec)
+        })
         .map_err(|kind| self.err(kind))?;
 
-        if let Some(ref executed) = maybe_executed {
+        sinks.write_outcome(&id, spec.to_spec_id(), &outcome);
+        // Before the fee of the coinbase and the withdrawals are applied
+        let touched = (audit_touches && space == Space::Ethereum)
+            .then(|| post_transact::touched_accounts(state, space));
+        // Every failure from here on knows how the transaction was handled
+        let outcome_kind = Some(OutcomeKind::of(&outcome));
+        let err = |kind| TestError {
+            outcome: outcome_kind,
+            ..self.err(kind)
+        };
+
+        let logs_hash = post_transact::logs_hash(&outcome);
+        let reverted =
+            matches!(outcome, ExecutionOutcome::ExecutionErrorBumpNonce(..));
+
+        let maybe_executed =
+            post_transact::extract_executed(outcome, expect_exception)
+                .map_err(err)?;
+
+        // Reported at the call site, before the post state it spoils
+        if let Some(violation) =
+            maybe_executed.as_ref().and_then(GasForwardingViolation::of)
+        {
+            return Err(err(TestErrorKind::GasForwardingViolation(violation)));
+        }
+        if let Some(mismatch) =
+            maybe_executed.as_ref().and_then(CreateAddressMismatch::of)
+        {
+            return Err(err(TestErrorKind::CreateAddressMismatch(mismatch)));
+        }
+        if let Some(mismatch) =
+            maybe_executed.as_ref().and_then(CoinbaseAccessMismatch::of)
+        {
+            return Err(err(TestErrorKind::CoinbaseAccessMismatch(mismatch)));
+        }
+        if let Some(executed) = &maybe_executed {
+            post_transact::check_warm_accounts(
+                executed,
+                &env,
+                spec.to_spec_id(),
+                space,
+            )
+            .map_err(err)?;
+            post_transact::check_selfdestructs(
+                executed,
+                state,
+                spec.to_spec_id(),
+                space,
+            )
+            .map_err(err)?;
+            if check_precompile_gas {
+                precompile_gas::check_precompile_gas(
+                    executed,
+                    spec.to_spec_id(),
+                )
+                .map_err(err)?;
+            }
+            if reverted && check_revert_rollback {
+                // The preceding transactions change the state the slots are
+                // rolled back to
+                let pre = self
+                    .unit
+                    .preceding_transactions
+                    .is_empty()
+                    .then_some(&self.unit.pre);
+                post_transact::check_revert_rollback(
+                    executed,
+                    post_transact::authorization_refund(&tx, &tx_spec),
+                    state,
+                    pre,
+                    space,
+                )
+                .map_err(err)?;
+            }
+            post_transact::check_transient_storage(executed, state, space)
+                .map_err(err)?;
+        }
+
+        if let Some(executed) = &maybe_executed {
             post_transact::distribute_tx_fee_to_miner(
-                &mut state,
-                &executed,
-                &env.author,
+                state, executed, &tx, &env,
             );
         }
 
+        // Withdrawals are processed after the transactions of the block
+        if let Some(withdrawals) = &self.unit.env.withdrawals {
+            if spec.to_spec_id() >= SpecId::SHANGHAI {
+                post_transact::apply_withdrawals(state, withdrawals, space);
+            }
+        }
+
         let gas_used = maybe_executed
             .as_ref()
             .map(|e| e.gas_used)
             .unwrap_or_default();
-        post_transact::check_execution_outcome(
+
+        // A test expecting an exception is checked as usual, so that blessing
+        // can not hide the intended failure
+        if bless && expect_exception.is_none() {
+            let blessed = post_transact::bless_post_state(
+                state,
+                &self.unit,
+                logs_hash,
+                spec.to_spec_id(),
+            );
+            return Ok(TestRun::Passed(TestPass {
+                gas_used,
+                outcome: outcome_kind,
+                blessed: Some(blessed),
+                dumped: None,
+                fingerprint: None,
+                surface: StateSurface::default(),
+            }));
+        }
+
+        // Collected before a state root flushes the touched accounts
+        let addresses = post_transact::post_state_addresses(state, &self.unit);
+
+        // Flushes the state like the state root check below
+        let fingerprint = double_run.then(|| RunFingerprint {
+            logs_hash,
+            state_root: compute_state_root(
+                state,
+                addresses.iter().copied(),
+                spec.to_spec_id() >= SpecId::SPURIOUS_DRAGON,
+            ),
+        });
+
+        // Checked first, so that a divergence is reported even if the
+        // fixture disagrees with both
+        if let (Some((evm, unit)), Space::Ethereum) = (reference, space) {
+            let mut input = RefEvm::input(&self.name, unit, spec, index);
+            // The reference runs the block of the test, overrides included
+            if let Some(env) = input[&self.name].get_mut("env") {
+                if let Some(coinbase) = coinbase {
+                    env["currentCoinbase"] = format!("{:?}", coinbase).into();
+                }
+                block.apply_to_fixture(env);
+            }
+            post_transact::check_reference(
+                state,
+                &addresses,
+                evm,
+                &input,
+                gas_used,
+                spec.to_spec_id(),
+            )
+            .map_err(err)?;
+        }
+
+        // Checked before the post state, whose balance rows do not tell a
+        // fee burnt twice from a value created. The preceding transactions
+        // move value the pre-state does not show.
+        if check_conservation
+            && space == Space::Ethereum
+            && self.unit.preceding_transactions.is_empty()
+        {
+            conservation::check_conservation(
+                state,
+                &self.unit,
+                &addresses,
+                maybe_executed.as_ref(),
+                &env,
+                spec.to_spec_id(),
+            )
+            .map_err(err)?;
+        }
+
+        let rebased;
+        let expected_state = match coinbase {
+            Some(coinbase) => {
+                rebased = post_transact::rebase_coinbase(
+                    &self.unit,
+                    &test.state,
+                    coinbase,
+                );
+                &rebased
+            }
+            None => &test.state,
+        };
+
+        let surface = post_transact::check_execution_outcome(
             &tx,
-            &state,
+            state,
             &self.unit,
-            &test.state,
-            gas_used,
+            test,
+            expected_state,
+            maybe_executed.as_ref(),
+            spec.to_spec_id(),
         )
-        .map_err(|kind| self.err(kind))?;
+        .map_err(err)?;
+
+        post_transact::check_logs_hash(logs_hash, test.logs).map_err(err)?;
+
+        if let Some(touched) = &touched {
+            post_transact::check_touched_accounts(touched, &surface)
+                .map_err(err)?;
+        }
+
+        // Only kept if the state root check passes
+        let dumped = (dump_state && space == Space::Ethereum).then(|| {
+            post_transact::dump_post_state(state, addresses, spec.to_spec_id())
+        });
+
+        // The state root only covers the Ethereum space accounts, and commits
+        // to the fixture coinbase
+        if let (Some(expected_root), Space::Ethereum, None) =
+            (test.hash, space, coinbase)
+        {
+            post_transact::check_state_root(
+                state,
+                &self.unit,
+                &test.state,
+                expected_root,
+                spec.to_spec_id(),
+            )
+            .map_err(err)?;
+        }
+
+        if let Some(checks) = post_checks {
+            checks
+                .check(
+                    &tx,
+                    maybe_executed.as_ref(),
+                    state,
+                    spec.to_spec_id(),
+                    space,
+                )
+                .map_err(err)?;
+        }
+
+        Ok(TestRun::Passed(TestPass {
+            gas_used,
+            outcome: outcome_kind,
+            blessed: None,
+            dumped,
+            fingerprint,
+            surface,
+        }))
+    }
+
+    /// Runs the `index`-th test of `spec` on a worker thread. A worker still
+    /// running after `timeout` is abandoned, it holds its own copy of the unit
+    /// and state, so the next test can start right away. The executor can
+    /// not be interrupted, so an abandoned worker runs until its test ends:
+    /// once `MAX_ABANDONED_WORKERS` of them are left, the tests fail without
+    /// running instead of piling up more.
+    fn execute_with_timeout(
+        &self, spec: SpecName, index: usize, machine: &Arc<Machine>,
+        ctx: &ExecContext, timeout: Duration,
+    ) -> (Result<TestRun, TestError>, Option<MemoryStats>) {
+        let running = ABANDONED_WORKERS.load(Ordering::SeqCst);
+        if running >= MAX_ABANDONED_WORKERS {
+            let kind = TestErrorKind::TooManyTimedOut { running };
+            return (Err(self.err(kind)), None);
+        }
+        let worker_state = Arc::new(AtomicU8::new(WORKER_RUNNING));
+        let (sender, receiver) = mpsc::channel();
+        let unit = self.clone();
+        let machine = machine.clone();
+        let verification = ctx.verification.clone();
+        let sinks = ctx.sinks.clone();
+        let post_checks = ctx.post_checks.cloned();
+        let fork_machines = ctx.fork_machines.cloned();
+        let ExecContext {
+            space,
+            bless,
+            coinbase,
+            block,
+            dump_state,
+            checks,
+            spec_from_fork,
+            ..
+        } = *ctx;
+        let reference =
+            ctx.reference.map(|(evm, unit)| (evm.clone(), unit.clone()));
+        let state = worker_state.clone();
+        let worker = thread::Builder::new()
+            .name(format!("{}:{:?}:{}", self.name, spec, index))
+            .spawn(move || {
+                let test = &unit.unit.post[&spec][index];
+                let ctx = ExecContext {
+                    machine: &machine,
+                    verification: &verification,
+                    sinks: &sinks,
+                    space,
+                    bless,
+                    coinbase,
+                    block,
+                    dump_state,
+                    reference: reference
+                        .as_ref()
+                        .map(|(evm, unit)| (evm, unit)),
+                    checks,
+                    spec_from_fork,
+                    fork_machines: fork_machines.as_ref(),
+                    post_checks: post_checks.as_ref(),
+                };
+                let result = memory::measure(|| {
+                    unit.execute_single_test(test, spec, index, &ctx, None)
+                });
+                let _ = sender.send(result);
+                // Counted by the timeout before it is abandoned
+                let done = state.compare_exchange(
+                    WORKER_RUNNING,
+                    WORKER_DONE,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+                if done.is_err() {
+                    ABANDONED_WORKERS.fetch_sub(1, Ordering::SeqCst);
+                }
+            });
+        if let Err(e) = worker {
+            let kind = TestErrorKind::Internal(format!(
+                "failed to spawn test worker: {}",
+                e
+            ));
+            return (Err(self.err(kind)), None);
+        }
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                ABANDONED_WORKERS.fetch_add(1, Ordering::SeqCst);
+                let abandoned = worker_state.compare_exchange(
+                    WORKER_RUNNING,
+                    WORKER_ABANDONED,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+                // Finished in the meantime
+                if abandoned.is_err() {
+                    ABANDONED_WORKERS.fetch_sub(1, Ordering::SeqCst);
+                }
+                warn!(
+                    "Test {}::{} ({:?}, #{}) timed out",
+                    self.path, self.name, spec, index
+                );
+                let kind = TestErrorKind::Timeout {
+                    timeout_ms: timeout.as_millis() as u64,
+                };
+                (Err(self.err(kind)), None)
+            }
+            // The worker dropped the sender without a result
+            Err(RecvTimeoutError::Disconnected) => {
+                (Err(self.err(TestErrorKind::Panic)), None)
+            }
+        }
+    }
+
+    /// A valid blob transaction can not be executed and is skipped, so only
+    /// the tests expecting it to be rejected are verified.
+    fn check_blob_test(
+        &self, expect_exception: Option<&String>, spec: SpecName,
+    ) -> Result<Option<U256>, TestError> {
+        let Some(got) = pre_transact::check_blob_tx(&self.unit, spec) else {
+            return Ok(None);
+        };
+        match expect_exception {
+            Some(reason) if reason.split('|').any(|r| r == got) => {
+                Ok(Some(U256::zero()))
+            }
+            fail_reason => {
+                Err(self.err(TestErrorKind::InconsistentBlobTxError {
+                    got,
+                    fail_reason: fail_reason.cloned(),
+                }))
+            }
+        }
+    }
+
+    /// Applies the `preceding_transactions` of the unit to `state`, in order,
+    /// and returns the gas they used in the block. Each of them must be
+    /// included, a failure is reported with its position in the list. Their
+    /// traces are dropped, the trace of a test being the one of its own
+    /// transaction.
+    fn apply_preceding_txs(
+        &self, ctx: &ExecContext, id: &TestId, spec: SpecName, env: &Env,
+        state: &mut State, params: &CommonParams, tx_spec: &Spec,
+    ) -> Result<U256, TestError> {
+        let mut env = env.clone();
+        let sinks = Sinks {
+            trace: None,
+            ..ctx.sinks.clone()
+        };
+        for (index, tx_meta) in
+            self.unit.preceding_transactions.iter().enumerate()
+        {
+            let failed = |error| {
+                self.err(TestErrorKind::PrecedingTxFailed {
+                    index,
+                    error: Box::new(error),
+                })
+            };
+            if tx_meta.max_fee_per_blob_gas.is_some() {
+                return Err(failed(TestErrorKind::Internal(
+                    "blob transactions can not precede".into(),
+                )));
+            }
+            let indexes = TxPartIndices {
+                data: 0,
+                gas: 0,
+                value: 0,
+            };
+            let tx = self
+                .make_tx(tx_meta, &None, &indexes, ctx.machine, ctx.space)
+                .map_err(|reason| {
+                    failed(TestErrorKind::Internal(format!(
+                        "failed to build the transaction: {}",
+                        reason
+                    )))
+                })?;
+            env.transaction_hash = tx.hash();
+
+            pre_transact::check_tx_common(
+                params,
+                &env,
+                &tx,
+                ctx.verification,
+                tx_spec,
+                state,
+            )
+            .map_err(|kind| match kind {
+                TestErrorKind::ConsensusCheckFail(tx_error) => {
+                    failed(TestErrorKind::CommonCheckError { tx_error })
+                }
+                TestErrorKind::DbError(_) => self.err(kind),
+                kind => failed(kind),
+            })?;
+
+            let transact_options = pre_transact::make_transact_options(
+                true,
+                Observer::new(&sinks),
+                ctx.space,
+            );
+            // A db error is kept as is, so that the test can be retried
+            let outcome = self
+                .transact(
+                    ctx.machine,
+                    &env,
+                    state,
+                    &tx,
+                    transact_options,
+                    tx_spec,
+                )
+                .map_err(|kind| self.err(kind))?;
+            sinks.write_outcome(id, spec.to_spec_id(), &outcome);
+
+            let executed = post_transact::extract_executed(outcome, None)
+                .map_err(failed)?
+                .ok_or_else(|| {
+                    failed(TestErrorKind::Internal("not executed".into()))
+                })?;
+            post_transact::distribute_tx_fee_to_miner(
+                state, &executed, &tx, &env,
+            );
+            // The executor expects the cache of the next transaction empty
+            state.commit_cache(false);
+            env.accumulated_gas_used += executed.gas_used;
+        }
+        Ok(env.accumulated_gas_used)
+    }
 
-        Ok(())
+    /// The transaction of `tx_meta` in `space`, signed as `txbytes` if they
+    /// are given, or why it is not supported.
+    fn make_tx(
+        &self, tx_meta: &TransactionParts, txbytes: &Option<Bytes>,
+        indexes: &TxPartIndices, machine: &Machine, space: Space,
+    ) -> Result<SignedTransaction, SkipReason> {
+        match space {
+            Space::Ethereum => pre_transact::make_tx(
+                tx_meta,
+                txbytes,
+                indexes,
+                self.unit.config.chainid,
+                extract_155_chain_id_from_raw_tx(txbytes).is_none(),
+            ),
+            Space::Native => pre_transact::make_native_tx(
+                tx_meta,
+                txbytes,
+                indexes,
+                machine
+                    .params()
+                    .chain_id
+                    .read()
+                    .get_chain_id(0)
+                    .in_native_space(),
+                self.unit.env.current_number.as_u64(),
+            ),
+        }
     }
 
     fn transact(
         &self, machine: &Machine, env: &Env, state: &mut State,
-        transaction: &SignedTransaction, options: TransactOptions<()>,
-    ) -> ExecutionOutcome {
-        let spec = machine.spec(env.number, env.epoch_height);
-
-        let evm = ExecutiveContext::new(state, env, &machine, &spec);
-        let outcome = evm.transact(transaction, options).expect("db error");
+        transaction: &SignedTransaction, options: TransactOptions<Observer>,
+        spec: &Spec,
+    ) -> Result<ExecutionOutcome, TestErrorKind> {
+        let evm = ExecutiveContext::new(state, env, machine, spec);
+        let outcome = evm
+            .transact(transaction, options)
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
         state.update_state_post_tx_execution(false);
-        outcome
+        Ok(outcome)
     }
 }
 
-fn pick_spec<'a, T>(
-    specs: impl Iterator<Item = (&'a SpecName, &'a T)>,
-) -> Option<(&'a SpecName, &'a T)> {
-    specs
-        .filter_map(|spec| {
-            let spec_id = spec.0.to_spec_id();
-            if spec_id <= SpecId::PRAGUE {
-                Some((spec, spec_id))
-            } else {
-                None
-            }
-        })
-        .fold(None, |acc, (spec, spec_id)| match acc {
-            Some((_, old_spec_id)) if spec_id > old_spec_id => {
-                Some((spec, spec_id))
-            }
-            Some((old_spec, old_spec_id)) if spec_id == old_spec_id => {
+/// Executes `transaction` on `state` as a test does, but restores `state`
+/// afterwards instead of committing the changes, so that the outcome can be
+/// inspected and variants of the transaction run from the same state. Fails
+/// if `state` has uncommitted changes, which could not be restored, and
+/// leaves it half written on a db error.
+pub fn transact_dry<O: ExecutiveObserver>(
+    machine: &Machine, env: &Env, state: &mut State,
+    transaction: &SignedTransaction, options: TransactOptions<O>, spec: &Spec,
+) -> Result<ExecutionOutcome, TestErrorKind> {
+    let Some(snapshot) = state.snapshot() else {
+        return Err(TestErrorKind::Internal(
+            "a dry run needs a state without uncommitted changes".into(),
+        ));
+    };
+    let evm = ExecutiveContext::new(state, env, machine, spec);
+    let outcome = evm
+        .transact(transaction, options)
+        .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+    state.restore_snapshot(&snapshot);
+    Ok(outcome)
+}
+
+/// The gas used of a passing test as its record has it, none if it does not
+/// fit, as a fixture can give a transaction any gas limit.
+fn record_gas(gas_used: U256) -> Option<u64> {
+    (gas_used <= U256::from(u64::MAX)).then(|| gas_used.low_u64())
+}
+
+fn is_db_error<T>(outcome: &Result<T, TestError>) -> bool {
+    matches!(outcome, Err(e) if matches!(e.kind, TestErrorKind::DbError(_)))
+}
+
+/// Select every spec in `post` up to `max_spec` (and listed in `forks` if it
+/// is not empty, and in `range` if any), ordered by spec id. Specs sharing
+/// the same id are only run once. The specs after `max_spec` are returned
+/// separately.
+fn pick_specs<'a, T>(
+    specs: impl Iterator<Item = (&'a SpecName, &'a T)>, forks: &[SpecName],
+    range: Option<ForkRange>, max_spec: SpecId,
+) -> (Vec<(&'a SpecName, &'a T)>, Vec<(&'a SpecName, &'a T)>) {
+    let mut picked = BTreeMap::new();
+    let mut beyond_max = vec![];
+    for spec in specs {
+        if !forks.is_empty() && !forks.contains(spec.0) {
+            continue;
+        }
+
+        let spec_id = spec.0.to_spec_id();
+        if range.map_or(false, |range| !range.contains(spec_id)) {
+            continue;
+        }
+        if spec_id > max_spec {
+            beyond_max.push(spec);
+            continue;
+        }
+
+        match picked.entry(spec_id) {
+            Entry::Occupied(old) => {
+                let (old_spec, _): &(&SpecName, &T) = old.get();
                 warn!(
                     "Duplicate spec with the same id: {:?} {:?}",
-                    old_spec.0, spec.0
+                    old_spec, spec.0
                 );
-                acc
             }
-            Some(_) => acc,
-            None => Some((spec, spec_id)),
-        })
-        .map(|(spec, _)| spec)
+            Entry::Vacant(slot) => {
+                slot.insert(spec);
+            }
+        }
+    }
+    (picked.into_values().collect(), beyond_max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            command::make_configuration, error::TestErrorKind,
+            loader::load_units, reference::RefEvm, Sinks,
+        },
+        pre_transact, record_gas,
+        test_tx::signed_tx,
+        transact_dry, BlockOverrides, Checks, UnitOptions, UnitResult,
+        UnitStatus, UnitTester, LATEST_SUPPORTED_SPEC,
+    };
+    use cfx_executor::{
+        executive::{ExecutionOutcome, TransactOptions},
+        machine::{Machine, VmFactory},
+        spec::CommonParams,
+    };
+    use cfx_types::{Address, AddressSpaceUtil, Space, U256};
+    use cfx_vm_types::Env;
+    use cfxcore::verification::VerificationConfig;
+    use eest_types::{AccountInfo, SpecName};
+    use rayon::ThreadPoolBuilder;
+    use serde_json::json;
+    use std::{
+        collections::{BTreeMap, HashMap},
+        path::Path,
+        sync::Arc,
+    };
+    use structopt::StructOpt;
+
+    /// The code of the EIP-2935 history contract deployed by Prague, which
+    /// serves the hash of block `n` from slot `n % 8191`.
+    const EIP2935_CODE: &str =
+        "0x3373fffffffffffffffffffffffffffffffffffffffe1460465760203603604257\
+         5f35600143038111604257611fff81430311604257611fff9006545f5260205ff35b\
+         5f5ffd5b5f35611fff60014303065500";
+
+    /// The units of a fixture of the test data, with the machine of the
+    /// default configuration.
+    struct Fixture {
+        units: Vec<UnitTester>,
+        machine: Arc<Machine>,
+        verification: VerificationConfig,
+        sinks: Sinks,
+    }
+
+    impl Fixture {
+        fn load(name: &str) -> Self {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("testdata")
+                .join(name);
+            let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+            let (units, errors) = load_units(&[path], &pool);
+            assert!(errors.is_empty(), "{:?}", errors);
+
+            let config = make_configuration("").unwrap().parsed;
+            let machine = Arc::new(Machine::new_with_builtin(
+                config.common_params(),
+                VmFactory::new(1024 * 32),
+            ));
+            let verification = config.verification_config(machine.clone());
+            Fixture {
+                units,
+                machine,
+                verification,
+                sinks: Sinks::default(),
+            }
+        }
+
+        /// The options of a run without flags.
+        fn options(&self) -> UnitOptions<'_> {
+            UnitOptions {
+                matches: None,
+                forks: &[],
+                max_spec: LATEST_SUPPORTED_SPEC,
+                fork_range: None,
+                test: None,
+                only_failing: None,
+                sinks: &self.sinks,
+                timeout: None,
+                retries: 0,
+                repeat_failures: 0,
+                max_gas: None,
+                space: Space::Ethereum,
+                shard: None,
+                keep_going: false,
+                keep_going_in_unit: false,
+                bless: false,
+                coinbase: None,
+                block: BlockOverrides::default(),
+                xfail: None,
+                dump_state: None,
+                reference: None,
+                checks: Checks::default(),
+                spec_from_fork: false,
+                fork_machines: None,
+                post_checks: None,
+                progress: None,
+                budget: None,
+            }
+        }
+
+        fn run(&self, opts: &UnitOptions) -> Vec<UnitResult> {
+            self.units
+                .iter()
+                .map(|unit| unit.run(&self.machine, &self.verification, opts))
+                .collect()
+        }
+    }
+
+    /// Runs the units of the fixture `name` of the test data, with the
+    /// default configuration and the options of a run without flags changed
+    /// by `configure`.
+    fn run_fixture(
+        name: &str, configure: impl FnOnce(&mut UnitOptions),
+    ) -> Vec<UnitResult> {
+        let fixture = Fixture::load(name);
+        let mut opts = fixture.options();
+        configure(&mut opts);
+        fixture.run(&opts)
+    }
+
+    /// The result of the single unit of `results`, checked to have run
+    /// `tests` post states without an error.
+    fn assert_ran(results: &[UnitResult], tests: usize) -> &UnitResult {
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.status, UnitStatus::Ran(tests));
+        result
+    }
+
+    #[test]
+    fn check_flags_parse_back_to_the_checks() {
+        // A repro command gives back every check of the failing run
+        let all = Checks {
+            check_gas_forwarding: true,
+            trace_precompiles: true,
+            check_revert_rollback: true,
+            check_coinbase_access: true,
+            check_precompile_gas: true,
+            audit_touches: true,
+            check_conservation: true,
+            double_run: true,
+        };
+        let args = std::iter::once("checks").chain(all.flags());
+        assert_eq!(Checks::from_iter_safe(args).unwrap(), all);
+        assert!(Checks::default().flags().is_empty());
+    }
+
+    #[test]
+    fn fixtures_of_several_forks_run_from_their_fork() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let (units, errors) = load_units(
+            &[
+                testdata.join("calldata_cost.json"),
+                testdata.join("pointer_reentry.json"),
+            ],
+            &pool,
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        let several: Vec<_> =
+            units.iter().map(|unit| unit.has_several_forks()).collect();
+        assert_eq!(several, [true, false]);
+    }
+
+    #[test]
+    fn gas_used_beyond_u64_is_not_recorded() {
+        let max = U256::from(u64::MAX);
+        assert_eq!(record_gas(U256::from(21000)), Some(21000));
+        assert_eq!(record_gas(max), Some(u64::MAX));
+        assert_eq!(record_gas(max + 1), None);
+    }
+
+    #[test]
+    fn fixture_passes_end_to_end() {
+        // The pre-checks read the sender before the executor runs
+        let results = run_fixture("pointer_reentry.json", |_| {});
+        assert_ran(&results, 1);
+    }
+
+    #[test]
+    fn burnt_fee_is_the_one_of_the_executor() {
+        // 21000 gas at 9 wei, the base fee of 7 burnt and the tip of 2 paid
+        // to the coinbase
+        let results = run_fixture("transfer_priority_fee.json", |opts| {
+            opts.checks.check_conservation = true
+        });
+        assert_ran(&results, 1);
+    }
+
+    #[test]
+    fn prevrandao_is_stored_from_the_block_env() {
+        // The contract stores DIFFICULTY, PREVRANDAO since the Merge, to
+        // slot 0: 0x020000 in Berlin and the currentRandom 0x0a11ce in Paris
+        let results = run_fixture("prevrandao.json", |_| {});
+        let result = assert_ran(&results, 2);
+        assert_eq!(result.specs[&SpecName::Berlin].passed, 1);
+        assert_eq!(result.specs[&SpecName::Paris].passed, 1);
+    }
+
+    #[test]
+    fn post_states_after_the_max_fork_are_counted_by_fork() {
+        // Istanbul is after the max fork, ConstantinopleFix still runs
+        let results = run_fixture("calldata_cost.json", |opts| {
+            opts.max_spec = SpecName::ConstantinopleFix;
+        });
+        let result = assert_ran(&results, 2);
+        assert_eq!(result.above_cap, BTreeMap::from([(SpecName::Istanbul, 2)]));
+
+        let results = run_fixture("eip161_touch.json", |opts| {
+            opts.max_spec = SpecName::Homestead;
+        });
+        let result = &results[0];
+        assert_eq!(result.status, UnitStatus::SkippedAboveCap);
+        assert_eq!(
+            result.above_cap,
+            BTreeMap::from([(SpecName::EIP150, 1), (SpecName::EIP158, 1)])
+        );
+    }
+
+    #[test]
+    fn reference_is_sent_the_overridden_block() {
+        // The reference saves its input, then disagrees on the root
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.json");
+        let evm: RefEvm = format!(
+            "cat > '{}' && echo '{{\"stateRoot\": \"0x{}\"}}'",
+            input.display(),
+            "00".repeat(32)
+        )
+        .parse()
+        .unwrap();
+        let fixture = Fixture::load("eip161_touch.json");
+        let opts = UnitOptions {
+            reference: Some(&evm),
+            coinbase: Some(Address::from_low_u64_be(0xc0ffee)),
+            block: BlockOverrides {
+                number: Some(1000),
+                timestamp: Some(0x2000),
+            },
+            ..fixture.options()
+        };
+        let results = fixture.run(&opts);
+        assert!(matches!(
+            results[0].errors[0].kind,
+            TestErrorKind::RefMismatch { .. }
+        ));
+
+        let sent: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&input).unwrap())
+                .unwrap();
+        let env = &sent["eip161_touch"]["env"];
+        assert_eq!(env["currentNumber"], "0x3e8");
+        assert_eq!(env["currentTimestamp"], "0x2000");
+        assert_eq!(
+            env["currentCoinbase"],
+            "0x0000000000000000000000000000000000c0ffee"
+        );
+        assert_eq!(env["currentDifficulty"], "0x020000");
+    }
+
+    #[test]
+    fn only_touched_empty_accounts_are_cleared() {
+        // The empty recipient of a zero value transfer is cleared from
+        // Spurious Dragon on, the untouched empty account is kept by both
+        let results = run_fixture("eip161_touch.json", |_| {});
+        let result = assert_ran(&results, 2);
+        assert_eq!(result.specs[&SpecName::EIP150].passed, 1);
+        assert_eq!(result.specs[&SpecName::EIP158].passed, 1);
+    }
+
+    #[test]
+    fn calldata_is_priced_by_the_fork() {
+        // 21040 gas pay for the calldata from Istanbul only, 21144 before
+        let results = run_fixture("calldata_cost.json", |_| {});
+        let result = assert_ran(&results, 4);
+        assert_eq!(result.specs[&SpecName::ConstantinopleFix].passed, 2);
+        assert_eq!(result.specs[&SpecName::Istanbul].passed, 2);
+    }
+
+    #[test]
+    fn only_the_valid_authorizations_set_code() {
+        // Of the four authorizations of the same authority, the one of any
+        // chain and the last one apply, the one with a stale nonce and the
+        // one of another chain are skipped without failing the transaction
+        let results = run_fixture("authorization_nonces.json", |_| {});
+        assert_ran(&results, 1);
+    }
+
+    #[test]
+    fn blob_transactions_are_not_executed() {
+        // Rejected before Cancun as expected, skipped from it, so that the
+        // blob fee of the post state is never checked
+        let results = run_fixture("blob_transfer.json", |opts| {
+            opts.checks.check_conservation = true
+        });
+        let result = assert_ran(&results, 1);
+        assert_eq!(result.specs[&SpecName::Shanghai].passed, 1);
+        assert_eq!(result.specs[&SpecName::Cancun].skipped, 1);
+        assert_eq!(result.unsupported_tx_types, BTreeMap::from([(3, 1)]));
+    }
+
+    #[test]
+    fn dry_run_leaves_the_state_unchanged() {
+        let tx = signed_tx(json!({ "value": ["0x01"] }));
+        let sender = tx.sender().address;
+        let pre: HashMap<Address, AccountInfo> =
+            serde_json::from_str(&format!(
+                r#"{{
+                "{:?}": {{
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {{}}
+                }}
+            }}"#,
+                sender
+            ))
+            .unwrap();
+        let mut state = pre_transact::make_state(&pre, Space::Ethereum);
+
+        let machine = Machine::new_with_builtin(
+            CommonParams::default(),
+            VmFactory::new(1024),
+        );
+        let env = Env {
+            chain_id: BTreeMap::from([
+                (Space::Native, 1),
+                (Space::Ethereum, 1),
+            ]),
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        };
+        let spec = machine.spec(env.number, env.epoch_height);
+        let mut dry_run = || {
+            let outcome = transact_dry(
+                &machine,
+                &env,
+                &mut state,
+                &tx,
+                TransactOptions::default(),
+                &spec,
+            )
+            .unwrap();
+            match outcome {
+                ExecutionOutcome::Finished(executed) => executed.gas_used,
+                outcome => panic!("not executed: {:?}", outcome),
+            }
+        };
+
+        // Both runs start from the pre-state
+        assert_eq!(dry_run(), U256::from(21000));
+        assert_eq!(dry_run(), U256::from(21000));
+        let sender = sender.with_evm_space();
+        assert_eq!(state.nonce(&sender).unwrap(), U256::zero());
+        assert_eq!(
+            state.balance(&sender).unwrap(),
+            U256::from(1_000_000_000_000_000_000u64)
+        );
+    }
+
+    #[test]
+    fn blockhash_beyond_the_window_is_zero_with_eip2935() {
+        // The contract returns BLOCKHASH(700) and what the EIP-2935 history
+        // contract returns for 700, at block 1000 with every transition
+        // active: the history contract serves the seeded hash, the opcode
+        // keeps its 256-block window
+        let history = "0x0000f90827f1c53a10cb7a02335b175320002935";
+        let code = format!(
+            "0x6102bc405f526102bc6020526020602060206020\
+             5f73{}5af15060405ff3",
+            &history[2..]
+        );
+        let hash = format!("0x{}", "be".repeat(32));
+        let tx = signed_tx(json!({ "gasLimit": ["0x0f4240"] }));
+        let pre: HashMap<Address, AccountInfo> =
+            serde_json::from_str(&format!(
+                r#"{{
+                "{:?}": {{
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {{}}
+                }},
+                "0x0000000000000000000000000000000000001000": {{
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "{}",
+                    "storage": {{}}
+                }},
+                "{}": {{
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "{}",
+                    "storage": {{ "0x02bc": "{}" }}
+                }}
+            }}"#,
+                tx.sender().address,
+                code,
+                history,
+                EIP2935_CODE,
+                hash
+            ))
+            .unwrap();
+        let mut state = pre_transact::make_state(&pre, Space::Ethereum);
+
+        let machine = Machine::new_with_builtin(
+            CommonParams::default(),
+            VmFactory::new(1024),
+        );
+        let env = Env {
+            chain_id: BTreeMap::from([
+                (Space::Native, 1),
+                (Space::Ethereum, 1),
+            ]),
+            number: 1000,
+            epoch_height: 1000,
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        };
+        let spec = machine.spec(env.number, env.epoch_height);
+        assert!(spec.eip2935);
+        let outcome = transact_dry(
+            &machine,
+            &env,
+            &mut state,
+            &tx,
+            TransactOptions::default(),
+            &spec,
+        )
+        .unwrap();
+        let ExecutionOutcome::Finished(executed) = outcome else {
+            panic!("not executed: {:?}", outcome);
+        };
+        assert_eq!(executed.output[..32], [0; 32]);
+        assert_eq!(executed.output[32..], [0xbe; 32]);
+    }
 }
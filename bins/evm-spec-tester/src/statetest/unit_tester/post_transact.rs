@@ -1,4 +1,20 @@
-use super::super::error::{StateMismatch, TestErrorKind};
+use super::{
+    super::{
+        error::{
+            AccountDiff, AccountFields, GasSide, RollbackLeak, StateDiff,
+            StateMismatch, TestErrorKind, TransientLeak, ValueDiff,
+        },
+        observer::{
+            creations, selfdestructs, storage_writes, touches,
+            transient_storage, warm_accounts, Creations, PrecompileCalls,
+        },
+        output_dir::{Artifact, OutputDir},
+        reference::RefEvm,
+    },
+    pre_transact,
+    state_root::compute_state_root,
+    TestId,
+};
 use cfx_executor::{
     executive::{
         execution_outcome::ToRepackError, Executed, ExecutionError,
@@ -6,11 +22,21 @@ use cfx_executor::{
     },
     state::State,
 };
-use cfx_types::{AddressSpaceUtil, AddressWithSpace, Space, U256};
+use cfx_types::{AddressSpaceUtil, AddressWithSpace, Space, H256, U256};
+use cfx_vm_types::{Env, Spec, CODE_PREFIX_7702};
 use cfxkey::Address;
-use eest_types::{AccountInfo, StateTestUnit};
-use primitives::{transaction::TransactionError, SignedTransaction};
-use std::collections::HashMap;
+use eest_types::{
+    AccountInfo, Authorization, SpecId, StateTest, StateTestUnit, Withdrawal,
+};
+use keccak_hash::keccak;
+use primitives::{transaction::TransactionError, LogEntry, SignedTransaction};
+use rlp::RlpStream;
+use serde_json::json;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::BufWriter,
+};
 
 macro_rules! bail {
     ($e:expr) => {
@@ -22,6 +48,8 @@ macro_rules! bail {
 pub enum TestOutcome<'a> {
     Consensus(&'a TransactionError),
     Execution(&'a ExecutionOutcome),
+    /// A rejection by a pre-check of the tester, see `check_tx_common`.
+    Tester(&'a TestErrorKind),
 }
 
 pub fn extract_executed(
@@ -63,20 +91,37 @@ pub fn extract_executed(
     }
 }
 
+/// Matches a failure of `check_tx_common` against the expected exception.
+/// Only the consensus errors and the rejections of the tester are expected,
+/// any other failure of the checks is the one of the test.
 pub fn process_consensus_check_fail(
-    error: TransactionError, expect_exception: Option<&String>,
+    error: TestErrorKind, expect_exception: Option<&String>,
 ) -> Result<(), TestErrorKind> {
-    if let Some(fail_reason) = expect_exception {
-        if match_fail_reason(fail_reason, TestOutcome::Consensus(&error)) {
-            Ok(())
-        } else {
-            Err(TestErrorKind::InconsistentErrorConsensus {
-                error,
-                fail_reason: fail_reason.clone(),
-            })
+    let Some(fail_reason) = expect_exception else {
+        return Err(error);
+    };
+    match error {
+        TestErrorKind::ConsensusCheckFail(error) => {
+            if match_fail_reason(fail_reason, TestOutcome::Consensus(&error)) {
+                Ok(())
+            } else {
+                Err(TestErrorKind::InconsistentErrorConsensus {
+                    error,
+                    fail_reason: fail_reason.clone(),
+                })
+            }
         }
-    } else {
-        Err(error.into())
+        error @ TestErrorKind::SenderNotEoa { .. } => {
+            if match_fail_reason(fail_reason, TestOutcome::Tester(&error)) {
+                Ok(())
+            } else {
+                Err(TestErrorKind::InconsistentErrorTester {
+                    error: Box::new(error),
+                    fail_reason: fail_reason.clone(),
+                })
+            }
+        }
+        error => Err(error),
     }
 }
 
@@ -86,8 +131,8 @@ fn match_fail_reason(reason: &str, outcome: TestOutcome<'_>) -> bool {
         .any(|reason| match_fail_single_reason(reason, outcome))
 }
 
-pub fn is_unsupport_reason(expected_reason: &Option<String>) -> bool {
-    let Some(reason) = expected_reason.as_ref() else {
+pub fn is_unsupport_reason(expected_reason: Option<&String>) -> bool {
+    let Some(reason) = expected_reason else {
         return false;
     };
     match &**reason {
@@ -97,15 +142,21 @@ pub fn is_unsupport_reason(expected_reason: &Option<String>) -> bool {
     }
 }
 
+/// Matches one exception of the fixture, in the naming of the execution spec
+/// tests or of the legacy `TR_*` ones, against the actual failure.
 fn match_fail_single_reason(reason: &str, outcome: TestOutcome<'_>) -> bool {
     use ExecutionOutcome::*;
     use TestOutcome::*;
     match reason {
-        "TransactionException.INITCODE_SIZE_EXCEEDED" => matches!(
+        "TransactionException.INITCODE_SIZE_EXCEEDED"
+        | "TR_InitCodeLimitExceeded" => matches!(
             outcome,
             Consensus(TransactionError::CreateInitCodeSizeLimit)
         ),
-        "TransactionException.INSUFFICIENT_ACCOUNT_FUNDS" => matches!(
+        "TransactionException.INSUFFICIENT_ACCOUNT_FUNDS"
+        | "TR_NoFunds"
+        | "TR_NoFundsValue"
+        | "TR_NoFundsOrGas" => matches!(
             outcome,
             Execution(
                 ExecutionErrorBumpNonce(
@@ -115,50 +166,171 @@ fn match_fail_single_reason(reason: &str, outcome: TestOutcome<'_>) -> bool {
                     ToRepackError::SenderDoesNotExist
                         | ToRepackError::NotEnoughBalance { .. }
                 )
-            )
+            ) | Consensus(TransactionError::InsufficientBalance { .. })
         ),
-        "TransactionException.INSUFFICIENT_MAX_FEE_PER_GAS" => matches!(
+        "TransactionException.INSUFFICIENT_MAX_FEE_PER_GAS"
+        | "TR_FeeCapLessThanBlocks" => matches!(
             outcome,
             Execution(NotExecutedToReconsiderPacking(
                 ToRepackError::NotEnoughBaseFee { .. }
-            ))
+            )) | Consensus(
+                TransactionError::InsufficientGasPrice { .. }
+                    | TransactionError::ZeroGasPrice
+            )
         ),
-        "TransactionException.INTRINSIC_GAS_TOO_LOW" => matches!(
+        "TransactionException.INTRINSIC_GAS_TOO_LOW"
+        | "TransactionException.INTRINSIC_GAS_BELOW_FLOOR_GAS_COST"
+        | "TR_IntrinsicGas" => matches!(
             outcome,
             Execution(NotExecutedDrop(TxDropError::NotEnoughGasLimit { .. }))
-                | Consensus(TransactionError::NotEnoughBaseGas { .. })
+                | Consensus(
+                    TransactionError::NotEnoughBaseGas { .. }
+                        | TransactionError::InsufficientGas { .. }
+                )
         ),
-        "TransactionException.NONCE_IS_MAX" => matches!(
+        "TransactionException.GAS_LIMIT_EXCEEDS_MAXIMUM"
+        | "TR_GasLimitReached" => matches!(
             outcome,
-            Execution(ExecutionErrorBumpNonce(
-                ExecutionError::NonceOverflow(_),
-                _
-            ))
+            Consensus(
+                TransactionError::GasLimitExceeded { .. }
+                    | TransactionError::InvalidGasLimit(_)
+            )
         ),
-        "TransactionException.PRIORITY_GREATER_THAN_MAX_FEE_PER_GAS" => {
+        "TransactionException.NONCE_IS_MAX" | "TR_NonceHasMaxValue" => {
+            matches!(
+                outcome,
+                Execution(ExecutionErrorBumpNonce(
+                    ExecutionError::NonceOverflow(_),
+                    _
+                )) | Consensus(TransactionError::TooLargeNonce)
+            )
+        }
+        "TransactionException.NONCE_MISMATCH_TOO_LOW" | "TR_NonceTooLow" => {
+            matches!(
+                outcome,
+                Execution(NotExecutedDrop(TxDropError::OldNonce(..)))
+            )
+        }
+        "TransactionException.NONCE_MISMATCH_TOO_HIGH" | "TR_NonceTooHigh" => {
+            matches!(
+                outcome,
+                Execution(NotExecutedToReconsiderPacking(
+                    ToRepackError::InvalidNonce { expected, got }
+                )) if got > expected
+            )
+        }
+        "TransactionException.PRIORITY_GREATER_THAN_MAX_FEE_PER_GAS"
+        | "TR_TipGtFeeCap" => {
             matches!(
                 outcome,
                 Consensus(TransactionError::PriortyGreaterThanMaxFee)
             )
         }
-        "TransactionException.SENDER_NOT_EOA" => matches!(
+        "TransactionException.SENDER_NOT_EOA" | "SenderNotEOA" => matches!(
             outcome,
             Execution(NotExecutedDrop(TxDropError::SenderWithCode { .. }))
+                | Tester(TestErrorKind::SenderNotEoa { .. })
+        ),
+        "TransactionException.TYPE_1_TX_PRE_FORK"
+        | "TransactionException.TYPE_2_TX_PRE_FORK"
+        | "TransactionException.TYPE_4_TX_PRE_FORK"
+        | "TR_TypeNotSupported" => matches!(
+            outcome,
+            Consensus(TransactionError::FutureTransactionType)
         ),
         "TransactionException.TYPE_4_EMPTY_AUTHORIZATION_LIST" => matches!(
             outcome,
             Consensus(TransactionError::EmptyAuthorizationList)
         ),
+        "TransactionException.INVALID_SIGNATURE_VRS"
+        | "TR_InvalidSignature" => {
+            matches!(outcome, Consensus(TransactionError::InvalidSignature(_)))
+        }
+        "TransactionException.INVALID_CHAINID" | "TR_ChainId" => matches!(
+            outcome,
+            Consensus(TransactionError::ChainIdMismatch { .. })
+        ),
+        "TR_RLP_WRONGVALUE" | "TR_InvalidRLP" => {
+            matches!(outcome, Consensus(TransactionError::InvalidRlp(_)))
+        }
         _ => false,
     }
 }
 
+/// The accounts and storage slots a test changes, from the difference
+/// between the pre and the post state of its fixture.
+#[derive(Clone, Debug, Default)]
+pub struct StateSurface {
+    /// The accounts created, changed or deleted.
+    pub accounts: BTreeSet<Address>,
+    /// The slots whose value changed, as `(contract, key)`.
+    pub slots: BTreeSet<(Address, U256)>,
+}
+
+impl StateSurface {
+    pub fn merge(&mut self, other: StateSurface) {
+        self.accounts.extend(other.accounts);
+        self.slots.extend(other.slots);
+    }
+
+    fn add_account(
+        &mut self, addr: Address, pre: Option<&AccountInfo>,
+        post: Option<&AccountInfo>,
+    ) {
+        if pre == post {
+            return;
+        }
+        self.accounts.insert(addr);
+        let no_storage = HashMap::new();
+        let pre_storage = pre.map_or(&no_storage, |info| &info.storage);
+        let post_storage = post.map_or(&no_storage, |info| &info.storage);
+        let keys = pre_storage.keys().chain(post_storage.keys());
+        for &key in keys {
+            let value = |storage: &HashMap<U256, U256>| {
+                storage.get(&key).copied().unwrap_or_default()
+            };
+            if value(pre_storage) != value(post_storage) {
+                self.slots.insert((addr, key));
+            }
+        }
+    }
+}
+
+/// Checks the post state of a test, then returns its `StateSurface`.
 pub fn check_execution_outcome(
     tx: &SignedTransaction, state: &State, unit: &StateTestUnit,
-    expected_state: &HashMap<Address, AccountInfo>, gas_used: U256,
-) -> Result<(), TestErrorKind> {
+    test: &StateTest, expected_state: &HashMap<Address, AccountInfo>,
+    executed: Option<&Executed>, spec: SpecId,
+) -> Result<StateSurface, TestErrorKind> {
+    let gas_used = executed.map(|e| e.gas_used).unwrap_or_default();
+    let space = tx.space();
+    let cleared = match spec >= SpecId::SPURIOUS_DRAGON {
+        true => eip161_touched(tx, state, unit, executed),
+        false => BTreeSet::new(),
+    };
+    let mut diff = StateDiff::default();
+    let mut code_hash_mismatch = None;
+    let mut surface = StateSurface::default();
     for (&addr, account_info) in expected_state {
-        let user_addr = addr.with_evm_space();
+        let user_addr = addr.with_space(space);
+        surface.add_account(addr, unit.pre.get(&addr), Some(account_info));
+
+        // EIP-161 clears the touched empty accounts, so the post state can
+        // not have one
+        let clear = cleared.contains(&addr);
+        if clear && is_empty_account(account_info) {
+            if in_post_state(state, &user_addr, clear) {
+                diff.accounts.insert(addr, AccountDiff::Extra);
+            }
+            continue;
+        }
+
+        if !state.exists(&user_addr).unwrap_or_default() {
+            diff.accounts.insert(addr, AccountDiff::Missing);
+            continue;
+        }
+
+        let mut fields = AccountFields::default();
 
         // balance check
         let expected_balance = account_info.balance;
@@ -171,17 +343,17 @@ pub fn check_execution_outcome(
                 let expected_gas_used =
                     (before_balance - expected_balance) / tx.gas_price();
                 if expected_gas_used != gas_used {
-                    bail!(StateMismatch::GasMismatch {
-                        got: gas_used,
+                    diff.gas_used = Some(ValueDiff {
                         expected: expected_gas_used,
+                        got: gas_used,
                     });
+                    diff.precompiles = PrecompileCalls::of(executed);
                 }
             }
 
-            bail!(StateMismatch::BalanceMismatch {
-                address: user_addr.address,
-                got: got_balance,
+            fields.balance = Some(ValueDiff {
                 expected: expected_balance,
+                got: got_balance,
             });
         }
 
@@ -189,11 +361,10 @@ pub fn check_execution_outcome(
         let expected_nonce = U256::from(account_info.nonce);
         let got_nonce = state.nonce(&user_addr).unwrap_or_default();
         if got_nonce != expected_nonce {
-            bail!(StateMismatch::NonceMismatch {
-                address: user_addr.address,
-                got: got_nonce,
+            fields.nonce = Some(ValueDiff {
                 expected: expected_nonce,
-            })
+                got: got_nonce,
+            });
         }
 
         // code check
@@ -202,10 +373,27 @@ pub fn check_execution_outcome(
             _ => Default::default(),
         };
         let expected_code = account_info.code.0.clone();
+        // The code hash is stored apart from the code, an account with the
+        // right code can still have a stale hash
+        if space == Space::Ethereum && code_hash_mismatch.is_none() {
+            let expected = keccak(&expected_code);
+            let got = state
+                .code_hash(&user_addr)
+                .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+            if got != expected {
+                code_hash_mismatch = Some(TestErrorKind::CodeMismatch {
+                    addr,
+                    expected,
+                    got,
+                    expected_len: expected_code.len(),
+                    got_len: got_code.len(),
+                });
+            }
+        }
         if got_code != expected_code {
-            bail!(StateMismatch::CodeMismatch {
-                got: hex::encode(got_code),
-                expected: hex::encode(expected_code),
+            fields.code = Some(ValueDiff {
+                expected: expected_code,
+                got: got_code,
             });
         }
 
@@ -216,30 +404,1443 @@ pub fn check_execution_outcome(
             let curr_value =
                 state.storage_at(&user_addr, &key_bytes).unwrap_or_default();
             if curr_value != value {
-                bail!(StateMismatch::StorageMismatch {
+                fields.storage.insert(
                     key,
-                    got: curr_value,
-                    expected: value,
-                });
+                    ValueDiff {
+                        expected: value,
+                        got: curr_value,
+                    },
+                );
+            }
+        }
+
+        if !fields.is_empty() {
+            diff.accounts.insert(addr, AccountDiff::Fields(fields));
+        }
+    }
+
+    // Accounts left in the state but not in the post state
+    let touched = state
+        .committed_cache
+        .keys()
+        .filter(|addr| addr.space == space)
+        .map(|addr| addr.address);
+    for addr in unit.pre.keys().copied().chain(touched) {
+        if expected_state.contains_key(&addr) {
+            continue;
+        }
+        surface.add_account(addr, unit.pre.get(&addr), None);
+        let clear = cleared.contains(&addr);
+        if in_post_state(state, &addr.with_space(space), clear) {
+            diff.accounts.insert(addr, AccountDiff::Extra);
+        }
+    }
+
+    // A more precise cause than the nonce row of the post state diff
+    if let Some(creations) = executed.and_then(creations) {
+        check_creator_nonces(creations, state, unit, space)?;
+    }
+    // A more precise cause than the code row, the authorizations are applied
+    // even if the execution reverts
+    if executed.is_some() && test.expect_exception.is_none() {
+        check_delegations(tx, state, unit, space)?;
+    }
+
+    // Whether the code itself differs or only its hash
+    if let Some(mismatch) = code_hash_mismatch {
+        bail!(mismatch);
+    }
+    if !diff.is_empty() {
+        bail!(StateMismatch::PostState(diff));
+    }
+
+    // Compared on every fork: the executor caps the refund at a fifth of the
+    // gas used (EIP-3529) whatever the spec, so a fixture of an earlier fork
+    // refunding more fails here rather than passing unnoticed
+    if let Some(expected) = test.gas_used {
+        let side = match expected.compare(gas_used) {
+            Ordering::Less => Some(GasSide::Under),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(GasSide::Over),
+        };
+        if let Some(side) = side {
+            bail!(StateMismatch::GasUsedMismatch {
+                got: gas_used,
+                expected,
+                side,
+                gross: executed.map(|e| e.gross_gas_used).unwrap_or_default(),
+                refund_counter: executed
+                    .map(|e| e.refund_counter)
+                    .unwrap_or_default(),
+                refund_applied: executed
+                    .map(|e| e.refund_applied)
+                    .unwrap_or_default(),
+                precompiles: PrecompileCalls::of(executed),
+            });
+        }
+    }
+
+    Ok(surface)
+}
+
+/// Checks that the nonce of every contract of the pre-state running CREATE
+/// or CREATE2 went up by one per creation in the frames that were not
+/// reverted. The EOAs, whose nonce the transaction and the EIP-7702
+/// authorizations also bump, and the contracts that may have lost a nonce to
+/// an address collision are left to the post state.
+fn check_creator_nonces(
+    creations: &Creations, state: &State, unit: &StateTestUnit, space: Space,
+) -> Result<(), TestErrorKind> {
+    // The preceding transactions change the nonce the creations start from
+    if !unit.preceding_transactions.is_empty() {
+        return Ok(());
+    }
+    for (&addr, &count) in &creations.counts {
+        let Some(pre) = unit.pre.get(&addr) else {
+            continue;
+        };
+        if pre.code.0.is_empty()
+            || pre.code.0.starts_with(CODE_PREFIX_7702)
+            || creations.unsure.contains(&addr)
+        {
+            continue;
+        }
+        let user_addr = addr.with_space(space);
+        // Deleted by a SELFDESTRUCT, checked apart
+        if !state
+            .exists(&user_addr)
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?
+        {
+            continue;
+        }
+        let expected = U256::from(pre.nonce) + count;
+        let got = state
+            .nonce(&user_addr)
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+        if got != expected {
+            bail!(TestErrorKind::NonceMismatch {
+                addr,
+                expected,
+                got
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the code of the authorities of an EIP-7702 transaction against its
+/// authorizations replayed on the pre-state. The authorities that no valid
+/// authorization delegates are left to the post state.
+fn check_delegations(
+    tx: &SignedTransaction, state: &State, unit: &StateTestUnit, space: Space,
+) -> Result<(), TestErrorKind> {
+    let Some(authorization_list) = &unit.transaction.authorization_list else {
+        return Ok(());
+    };
+    // The preceding transactions change the nonces and the codes the
+    // authorizations are checked against
+    if !unit.preceding_transactions.is_empty() || space != Space::Ethereum {
+        return Ok(());
+    }
+    let authorizations = authorization_list.iter().map(|auth| {
+        (
+            pre_transact::executor_authority(auth.signed()),
+            auth.signed().inner(),
+        )
+    });
+    let expected = expected_delegations(
+        authorizations,
+        unit.config.chainid,
+        tx.sender().address,
+        &unit.pre,
+    );
+
+    for (authority, delegate) in expected {
+        let code = state
+            .code(&authority.with_space(space))
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+        let got = code.as_deref().and_then(|code| delegation_of(code));
+        let expected = Some(delegate).filter(|d| !d.is_zero());
+        if got != expected
+            || (expected.is_none() && code.map_or(false, |c| !c.is_empty()))
+        {
+            bail!(TestErrorKind::DelegationMismatch {
+                authority,
+                expected,
+                got
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The address delegated to by `code`, if it is an EIP-7702 delegation.
+fn delegation_of(code: &[u8]) -> Option<Address> {
+    let address = code.strip_prefix(CODE_PREFIX_7702)?;
+    (address.len() == Address::len_bytes())
+        .then(|| Address::from_slice(address))
+}
+
+/// Replays the authorizations, with the authority each one is recovered as,
+/// the way EIP-7702 applies them after the nonce of `sender` is bumped. An
+/// authorization signed for another chain than `chain_id`, 0 being any chain,
+/// whose nonce is not the one of its authority by then, or whose authority
+/// has a code other than a delegation, is skipped alone. Returns the last
+/// address each authority is delegated to, the zero address clearing the
+/// delegation.
+fn expected_delegations<'a>(
+    authorizations: impl IntoIterator<Item = (Option<Address>, &'a Authorization)>,
+    chain_id: u64, sender: Address, pre: &HashMap<Address, AccountInfo>,
+) -> BTreeMap<Address, Address> {
+    let mut nonces = HashMap::new();
+    let mut delegations = BTreeMap::new();
+    for (authority, auth) in authorizations {
+        let Some(authority) = authority else {
+            continue;
+        };
+        if !(auth.chain_id.is_zero() || auth.chain_id == U256::from(chain_id))
+            || auth.nonce == u64::MAX
+        {
+            continue;
+        }
+        let account = pre.get(&authority);
+        let can_set_code = delegations.contains_key(&authority)
+            || account.map_or(true, |a| {
+                a.code.0.is_empty() || delegation_of(&a.code.0).is_some()
+            });
+        if !can_set_code {
+            continue;
+        }
+        let nonce = nonces.entry(authority).or_insert_with(|| {
+            let nonce = account.map_or(0, |a| a.nonce);
+            if authority == sender {
+                nonce + 1
+            } else {
+                nonce
             }
+        });
+        if *nonce != auth.nonce {
+            continue;
         }
+        *nonce += 1;
+        delegations.insert(authority, auth.address);
+    }
+    delegations
+}
+
+/// The expected post state of a run whose block author is overridden by
+/// `coinbase`: the balance gained by the fixture coinbase is moved to
+/// `coinbase`, so every other account is expected unchanged.
+pub fn rebase_coinbase(
+    unit: &StateTestUnit, expected_state: &HashMap<Address, AccountInfo>,
+    coinbase: Address,
+) -> HashMap<Address, AccountInfo> {
+    let mut expected = expected_state.clone();
+    let fixture_coinbase = unit.env.current_coinbase;
+    if fixture_coinbase == coinbase {
+        return expected;
+    }
+    let Some(account) = expected.get_mut(&fixture_coinbase) else {
+        return expected;
+    };
+
+    let pre_balance = unit
+        .pre
+        .get(&fixture_coinbase)
+        .map(|v| v.balance)
+        .unwrap_or_default();
+    let gain = account.balance.saturating_sub(pre_balance);
+    account.balance -= gain;
+    if !unit.pre.contains_key(&fixture_coinbase) && is_empty_account(account) {
+        expected.remove(&fixture_coinbase);
+    }
+
+    let author = expected.entry(coinbase).or_insert_with(|| {
+        unit.pre
+            .get(&coinbase)
+            .cloned()
+            .unwrap_or_else(|| AccountInfo {
+                balance: U256::zero(),
+                code: Default::default(),
+                nonce: 0,
+                storage: HashMap::new(),
+            })
+    });
+    author.balance += gain;
+    expected
+}
+
+/// Whether `address` is left in the post state: an empty account is cleared
+/// if touched since EIP-161, and stays otherwise.
+fn in_post_state(
+    state: &State, address: &AddressWithSpace, clear_empty: bool,
+) -> bool {
+    state.exists(address).unwrap_or_default()
+        && !(clear_empty && state.is_eip158_empty(address).unwrap_or_default())
+}
+
+/// The accounts the transaction touched, which EIP-161 clears if empty: the
+/// ones written, the sender and the coinbase paid by every transaction, and
+/// the ones of `touches`. The pre-state is committed by `make_state`, so only
+/// the accounts written by the transaction are dirty.
+fn eip161_touched(
+    tx: &SignedTransaction, state: &State, unit: &StateTestUnit,
+    executed: Option<&Executed>,
+) -> BTreeSet<Address> {
+    let space = tx.space();
+    let mut touched: BTreeSet<Address> = state
+        .committed_cache
+        .iter()
+        .filter(|(addr, entry)| addr.space == space && entry.is_dirty())
+        .map(|(addr, _)| addr.address)
+        .collect();
+    if let Some(executed) = executed {
+        touched.extend([tx.sender().address, unit.env.current_coinbase]);
+        touched.extend(touches(executed).into_iter().flatten());
+    }
+    touched
+}
+
+fn is_empty_account(info: &AccountInfo) -> bool {
+    info.balance.is_zero()
+        && info.nonce == 0
+        && info.code.0.is_empty()
+        && info.storage.values().all(U256::is_zero)
+}
+
+/// Keccak hash of the rlp encoded logs, as the `logs` field of the fixture.
+/// Only a successful execution keeps its logs, all the other outcomes hash an
+/// empty list.
+pub fn logs_hash(outcome: &ExecutionOutcome) -> H256 {
+    let logs: &[LogEntry] = match outcome {
+        ExecutionOutcome::Finished(executed) => &executed.logs,
+        _ => &[],
+    };
+
+    let mut stream = RlpStream::new_list(logs.len());
+    for log in logs {
+        stream.begin_list(3);
+        stream.append(&log.address);
+        stream.append_list::<H256, _>(&log.topics);
+        stream.append(&log.data);
+    }
+    keccak(stream.out())
+}
 
-        // TODO: logs hash check
+pub fn check_logs_hash(got: H256, expected: H256) -> Result<(), TestErrorKind> {
+    if got != expected {
+        bail!(StateMismatch::LogsRootMismatch { got, expected });
     }
+    Ok(())
+}
 
+/// Compares the Ethereum state root of `state` with the fixture. `state` is
+/// flushed to the statedb and can not be checked by the other functions
+/// anymore.
+pub fn check_state_root(
+    state: &mut State, unit: &StateTestUnit,
+    expected_state: &HashMap<Address, AccountInfo>, expected: H256,
+    spec: SpecId,
+) -> Result<(), TestErrorKind> {
+    let addresses = unit.pre.keys().chain(expected_state.keys()).copied();
+    let got =
+        compute_state_root(state, addresses, spec >= SpecId::SPURIOUS_DRAGON);
+    if got != expected {
+        bail!(StateMismatch::StateRootMismatch { got, expected });
+    }
     Ok(())
 }
 
+/// Compares the state root of the `addresses` and the gas used with the
+/// ones of the reference EVM for `input`. Like `check_state_root`, `state`
+/// is flushed to the statedb.
+pub fn check_reference(
+    state: &mut State, addresses: &BTreeSet<Address>, reference: &RefEvm,
+    input: &serde_json::Value, gas_used: U256, spec: SpecId,
+) -> Result<(), TestErrorKind> {
+    let root = compute_state_root(
+        state,
+        addresses.iter().copied(),
+        spec >= SpecId::SPURIOUS_DRAGON,
+    );
+    reference.check(input, root, gas_used)
+}
+
+/// The post state of a test as computed by a run, in the fixture shape.
+pub struct BlessedState {
+    /// The eSpace accounts, keyed by address.
+    pub state: serde_json::Value,
+    pub hash: H256,
+    pub logs: H256,
+}
+
+/// Collects the eSpace accounts of `state` after the transaction, leaving
+/// out the empty ones since EIP-161. Like `check_state_root`, `state` is
+/// flushed to the statedb.
+pub fn bless_post_state(
+    state: &mut State, unit: &StateTestUnit, logs: H256, spec: SpecId,
+) -> BlessedState {
+    let clear_empty = spec >= SpecId::SPURIOUS_DRAGON;
+    let addresses = post_state_addresses(state, unit);
+    let hash =
+        compute_state_root(state, addresses.iter().copied(), clear_empty);
+
+    BlessedState {
+        state: collect_accounts(state, addresses, clear_empty),
+        hash,
+        logs,
+    }
+}
+
+/// Collects the eSpace accounts of `state` as a genesis allocation, keyed by
+/// address. As in `bless_post_state`, the empty accounts are left out since
+/// EIP-161 and the storage only has the non-zero slots. `state` is flushed
+/// to the statedb, which `check_state_root` can still run on.
+pub fn dump_post_state(
+    state: &mut State, addresses: BTreeSet<Address>, spec: SpecId,
+) -> serde_json::Value {
+    state.apply_changes_to_statedb(None).expect("db error");
+    collect_accounts(state, addresses, spec >= SpecId::SPURIOUS_DRAGON)
+}
+
+/// Writes a state dump to `output`, in the file named after `id` by
+/// `OutputDir::path`.
+pub fn write_state_dump(
+    output: &OutputDir, id: &TestId, alloc: &serde_json::Value,
+) -> Result<(), String> {
+    let file = output
+        .open(Artifact::StateDump, &id.to_string())
+        .map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(BufWriter::new(file), alloc)
+        .map_err(|e| e.to_string())
+}
+
+/// The pre-state accounts and the eSpace accounts touched by the transaction,
+/// which are only known before `state` is flushed.
+pub fn post_state_addresses(
+    state: &State, unit: &StateTestUnit,
+) -> BTreeSet<Address> {
+    let mut addresses: BTreeSet<Address> = unit.pre.keys().copied().collect();
+    addresses.extend(
+        state
+            .committed_cache
+            .keys()
+            .filter(|addr| addr.space == Space::Ethereum)
+            .map(|addr| addr.address),
+    );
+    addresses
+}
+
+/// The accounts that the executor marked dirty, so will be committed, and
+/// that exist after the transaction. The pre-state is committed by
+/// `make_state`, so its accounts are only dirty once written again.
+pub fn touched_accounts(state: &State, space: Space) -> BTreeSet<Address> {
+    state
+        .committed_cache
+        .iter()
+        .filter(|(addr, entry)| addr.space == space && entry.is_dirty())
+        .filter(|(addr, _)| state.exists(addr).expect("db error"))
+        .map(|(addr, _)| addr.address)
+        .collect()
+}
+
+/// Fails on the first of the `touched` accounts the fixture does not expect
+/// to create, see `touched_accounts`.
+pub fn check_touched_accounts(
+    touched: &BTreeSet<Address>, surface: &StateSurface,
+) -> Result<(), TestErrorKind> {
+    match touched.iter().find(|addr| !surface.accounts.contains(addr)) {
+        Some(&addr) => Err(TestErrorKind::SpuriousTouch { addr }),
+        None => Ok(()),
+    }
+}
+
+fn collect_accounts(
+    state: &mut State, addresses: BTreeSet<Address>, clear_empty: bool,
+) -> serde_json::Value {
+    let mut accounts = serde_json::Map::new();
+    for address in addresses {
+        let user_addr = address.with_evm_space();
+        if !state.exists(&user_addr).expect("db error") {
+            continue;
+        }
+
+        // The statedb does not list the slots in a stable order
+        let mut entries =
+            state.storage_entries_in_db(&user_addr).expect("db error");
+        entries.sort();
+        let storage: serde_json::Map<_, _> = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let key = U256::from_big_endian(&key);
+                (format!("{:#x}", key), json!(format!("{:#x}", value)))
+            })
+            .collect();
+        if clear_empty
+            && storage.is_empty()
+            && state.is_eip158_empty(&user_addr).expect("db error")
+        {
+            continue;
+        }
+
+        let balance = state.balance(&user_addr).expect("db error");
+        let nonce = state.nonce(&user_addr).expect("db error");
+        let code = state.code(&user_addr).expect("db error");
+        let code = code.as_deref().map_or(&[][..], |c| &c[..]);
+        accounts.insert(
+            format!("{:?}", address),
+            json!({
+                "balance": format!("{:#x}", balance),
+                "code": format!("0x{}", hex::encode(code)),
+                "nonce": format!("{:#x}", nonce),
+                "storage": storage,
+            }),
+        );
+    }
+    accounts.into()
+}
+
+/// Credits the transaction fee to the block author. In eSpace the base fee is
+/// burnt (EIP-1559) and the author only receives the priority fee of the
+/// charged gas, while a native transaction pays the fee minus the CIP-1559
+/// burnt part.
 pub fn distribute_tx_fee_to_miner(
-    state: &mut State, executed: &Executed, miner: &Address,
+    state: &mut State, executed: &Executed, tx: &SignedTransaction, env: &Env,
 ) {
-    let to_add = match executed.burnt_fee {
-        Some(burnt_fee) => executed.fee - burnt_fee,
-        None => executed.fee,
+    let to_add = match tx.space() {
+        Space::Ethereum => {
+            let base_fee = env.base_gas_price[Space::Ethereum];
+            executed.gas_charged * tx.priority_gas_price(&base_fee)
+        }
+        Space::Native => match executed.burnt_fee {
+            Some(burnt_fee) => executed.fee - burnt_fee,
+            None => executed.fee,
+        },
     };
     let miner = AddressWithSpace {
-        address: miner.clone(),
-        space: Space::Ethereum,
+        address: env.author,
+        space: tx.space(),
     };
     state.add_balance(&miner, &to_add).expect("should success");
 }
+
+/// The number of precompiles of `spec`, at the addresses from 1 on.
+fn precompile_count(spec: SpecId) -> u64 {
+    match spec {
+        spec if spec >= SpecId::PRAGUE => 0x11,
+        spec if spec >= SpecId::CANCUN => 0x0a,
+        spec if spec >= SpecId::ISTANBUL => 0x09,
+        spec if spec >= SpecId::BYZANTIUM => 0x08,
+        _ => 0x04,
+    }
+}
+
+/// The accounts that must be warm at the start of the execution in `spec`
+/// (EIP-2929) but are not in `warm`: the precompiles, and from Shanghai the
+/// coinbase (EIP-3651). The sender, receiver and access list are warmed
+/// together with the transaction, so they are left to the gas checks.
+fn missing_warm_accounts(
+    warm: &[AddressWithSpace], coinbase: Address, spec: SpecId, space: Space,
+) -> Vec<Address> {
+    let precompiles =
+        (1..=precompile_count(spec)).map(Address::from_low_u64_be);
+    let coinbase = (spec >= SpecId::SHANGHAI).then_some(coinbase);
+    precompiles
+        .chain(coinbase)
+        .filter(|address| !warm.contains(&address.with_space(space)))
+        .collect()
+}
+
+/// Checks the accounts warm at the start of the execution, from Berlin on.
+pub fn check_warm_accounts(
+    executed: &Executed, env: &Env, spec: SpecId, space: Space,
+) -> Result<(), TestErrorKind> {
+    let Some(warm) = warm_accounts(executed) else {
+        return Ok(());
+    };
+    if spec < SpecId::BERLIN {
+        return Ok(());
+    }
+    let missing = missing_warm_accounts(warm, env.author, spec, space);
+    if !missing.is_empty() {
+        bail!(TestErrorKind::AccessListInitMismatch { missing });
+    }
+    Ok(())
+}
+
+/// The refund a reverted transaction keeps at most: the one of its EIP-7702
+/// authorizations, granted before the execution.
+pub fn authorization_refund(tx: &SignedTransaction, spec: &Spec) -> U256 {
+    let per_authorization = spec.per_empty_account_cost * spec.evm_gas_ratio
+        - spec.per_auth_base_cost;
+    U256::from(tx.authorization_len()) * U256::from(per_authorization)
+}
+
+/// Checks that a transaction whose top frame reverted or halted discarded
+/// the refund of its execution, keeping at most `max_refund`, and that every
+/// slot it wrote holds its value from `pre`, the state before the
+/// transaction. The slots are not checked if `pre` is not known.
+pub fn check_revert_rollback(
+    executed: &Executed, max_refund: U256, state: &State,
+    pre: Option<&HashMap<Address, AccountInfo>>, space: Space,
+) -> Result<(), TestErrorKind> {
+    if executed.refund_counter > max_refund {
+        bail!(TestErrorKind::RevertRollbackIncomplete(
+            RollbackLeak::Refund {
+                counter: executed.refund_counter,
+                allowed: max_refund,
+            }
+        ));
+    }
+    let (Some(pre), Some(writes)) = (pre, storage_writes(executed)) else {
+        return Ok(());
+    };
+    for &(contract, key) in writes {
+        let mut key_bytes = [0u8; 32];
+        key.to_big_endian(&mut key_bytes);
+        let expected = pre
+            .get(&contract)
+            .and_then(|account| account.storage.get(&key))
+            .copied()
+            .unwrap_or_default();
+        let got = state
+            .storage_at(&contract.with_space(space), &key_bytes)
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+        if got != expected {
+            bail!(TestErrorKind::RevertRollbackIncomplete(
+                RollbackLeak::Storage {
+                    contract,
+                    key,
+                    got,
+                    expected,
+                }
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every TLOAD of the transaction read the transient storage
+/// left by the frames that were not reverted, and that none of the slots it
+/// wrote is still set in `state`, where the transaction is committed.
+pub fn check_transient_storage(
+    executed: &Executed, state: &State, space: Space,
+) -> Result<(), TestErrorKind> {
+    let Some(trace) = transient_storage(executed) else {
+        return Ok(());
+    };
+    if let Some(load) = &trace.stale_load {
+        bail!(TestErrorKind::TransientStorageLeak(
+            TransientLeak::StaleLoad {
+                contract: load.contract,
+                key: load.key,
+                got: load.got,
+                expected: load.expected,
+            }
+        ));
+    }
+    for &(contract, key) in &trace.slots {
+        let mut key_bytes = [0u8; 32];
+        key.to_big_endian(&mut key_bytes);
+        let value = state
+            .transient_storage_at(&contract.with_space(space), &key_bytes)
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+        if !value.is_zero() {
+            bail!(TestErrorKind::TransientStorageLeak(
+                TransientLeak::Committed {
+                    contract,
+                    key,
+                    value,
+                }
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a SELFDESTRUCT deletes its contract under `spec`. From Cancun,
+/// EIP-6780 only lets the contracts created by the transaction delete
+/// themselves, the others only send their balance.
+fn selfdestruct_deletes(spec: SpecId, created_in_tx: bool) -> bool {
+    spec < SpecId::CANCUN || created_in_tx
+}
+
+/// Checks that the SELFDESTRUCTs of the transaction deleted their contract,
+/// or left its code in `state`, as the rule of `spec` says.
+pub fn check_selfdestructs(
+    executed: &Executed, state: &State, spec: SpecId, space: Space,
+) -> Result<(), TestErrorKind> {
+    for selfdestruct in selfdestructs(executed) {
+        let expected = selfdestruct_deletes(spec, selfdestruct.created_in_tx);
+        let has_code = state
+            .is_contract_with_code(&selfdestruct.contract.with_space(space))
+            .map_err(|e| TestErrorKind::DbError(e.to_string()))?;
+        let deleted = selfdestruct.deleted && !has_code;
+        if deleted != expected {
+            bail!(TestErrorKind::SelfdestructMismatch {
+                contract: selfdestruct.contract,
+                created_in_tx: selfdestruct.created_in_tx,
+                deleted,
+                expected,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Credits the withdrawals of the block, whose amounts are in Gwei, to the
+/// accounts in `space`.
+pub fn apply_withdrawals(
+    state: &mut State, withdrawals: &[Withdrawal], space: Space,
+) {
+    const GWEI: u64 = 1_000_000_000;
+    for withdrawal in withdrawals {
+        let amount = withdrawal.amount * U256::from(GWEI);
+        state
+            .add_balance(&withdrawal.address.with_space(space), &amount)
+            .expect("should success");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            super::{
+                error::{
+                    AccountDiff, RollbackLeak, StateMismatch, TestErrorKind,
+                    TransientLeak,
+                },
+                observer::{
+                    Creations, StaleTransientLoad, StorageWritesKey,
+                    TransientStorageKey, TransientStorageTrace,
+                },
+            },
+            pre_transact,
+            test_tx::{signed_tx, tx_json, FIRST},
+        },
+        apply_withdrawals, check_creator_nonces, check_execution_outcome,
+        check_revert_rollback, check_touched_accounts, check_transient_storage,
+        distribute_tx_fee_to_miner, expected_delegations, match_fail_reason,
+        missing_warm_accounts, process_consensus_check_fail,
+        selfdestruct_deletes, touched_accounts, StateSurface, TestOutcome,
+    };
+    use cfx_executor::executive::Executed;
+    use cfx_types::{Address, AddressSpaceUtil, Space, SpaceMap, U256};
+    use cfx_vm_types::Env;
+    use eest_types::{
+        AccountInfo, Authorization, SpecId, StateTest, StateTestUnit,
+        Withdrawal,
+    };
+    use keccak_hash::keccak;
+    use primitives::transaction::TransactionError;
+    use serde_json::json;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    #[test]
+    fn legacy_exception_names_match() {
+        let error = TransactionError::PriortyGreaterThanMaxFee;
+        let outcome = TestOutcome::Consensus(&error);
+        assert!(match_fail_reason("TR_TipGtFeeCap", outcome));
+        assert!(match_fail_reason(
+            "TR_NoFunds|TransactionException.PRIORITY_GREATER_THAN_MAX_FEE_PER_GAS",
+            outcome
+        ));
+        assert!(!match_fail_reason("TR_FeeCapLessThanBlocks", outcome));
+
+        let error = TransactionError::TooLargeNonce;
+        let outcome = TestOutcome::Consensus(&error);
+        assert!(match_fail_reason("TR_NonceHasMaxValue", outcome));
+    }
+
+    #[test]
+    fn sender_with_code_is_rejected_by_the_tester() {
+        let rejected = || TestErrorKind::SenderNotEoa {
+            addr: Address::from_low_u64_be(0x1000),
+        };
+        let expect = |reason: &str| Some(reason.to_string());
+        assert!(process_consensus_check_fail(
+            rejected(),
+            expect("TransactionException.SENDER_NOT_EOA").as_ref()
+        )
+        .is_ok());
+        assert!(matches!(
+            process_consensus_check_fail(
+                rejected(),
+                expect("TR_NoFunds").as_ref()
+            ),
+            Err(TestErrorKind::InconsistentErrorTester { .. })
+        ));
+        assert!(matches!(
+            process_consensus_check_fail(rejected(), None),
+            Err(TestErrorKind::SenderNotEoa { .. })
+        ));
+        // A db error is never an expected exception
+        assert!(matches!(
+            process_consensus_check_fail(
+                TestErrorKind::DbError("lost".into()),
+                expect("TransactionException.SENDER_NOT_EOA").as_ref()
+            ),
+            Err(TestErrorKind::DbError(_))
+        ));
+    }
+
+    #[test]
+    fn london_miner_only_receives_tip() {
+        let tx = signed_tx(json!({
+            "gasPrice": null,
+            "maxPriorityFeePerGas": "0x02",
+            "maxFeePerGas": "0x0a"
+        }));
+
+        let coinbase = Address::from_low_u64_be(0xc0ffee);
+        let base_fee = U256::from(7);
+        let env = Env {
+            author: coinbase,
+            base_gas_price: SpaceMap::new(base_fee, base_fee),
+            ..Default::default()
+        };
+
+        // effective gas price: min(10, 7 + 2) = 9, of which 7 is burnt
+        let gas = U256::from(21000);
+        let executed = Executed {
+            base_gas: 21000,
+            gas_used: gas,
+            gross_gas_used: gas,
+            refund_counter: U256::zero(),
+            refund_applied: U256::zero(),
+            fee: gas * 9,
+            burnt_fee: Some(gas * 7),
+            gas_charged: gas,
+            gas_sponsor_paid: false,
+            logs: vec![],
+            storage_sponsor_paid: false,
+            storage_collateralized: vec![],
+            storage_released: vec![],
+            contracts_created: vec![],
+            output: vec![],
+            ext_result: typemap::ShareDebugMap::custom(),
+        };
+
+        let mut state =
+            pre_transact::make_state(&HashMap::new(), Space::Ethereum);
+        distribute_tx_fee_to_miner(&mut state, &executed, &tx, &env);
+
+        let miner_balance = state.balance(&coinbase.with_evm_space()).unwrap();
+        assert_eq!(miner_balance, gas * 2);
+    }
+
+    #[test]
+    fn reverted_transactions_leave_nothing_behind() {
+        let contract = Address::from_low_u64_be(0x1000);
+        let pre = |slot_2: u64| -> HashMap<Address, AccountInfo> {
+            serde_json::from_str(&format!(
+                r#"{{
+                    "0x0000000000000000000000000000000000001000": {{
+                        "nonce": "0x01",
+                        "balance": "0x00",
+                        "code": "0x600160025500",
+                        "storage": {{ "0x01": "0x05", "0x02": "{:#x}" }}
+                    }}
+                }}"#,
+                slot_2
+            ))
+            .unwrap()
+        };
+        let executed = |refund_counter: u64| {
+            let mut ext_result = typemap::ShareDebugMap::custom();
+            ext_result.insert::<StorageWritesKey>(BTreeSet::from([
+                (contract, U256::from(1)),
+                (contract, U256::from(2)),
+            ]));
+            Executed {
+                base_gas: 21000,
+                gas_used: U256::from(30000),
+                gross_gas_used: U256::from(30000),
+                refund_counter: U256::from(refund_counter),
+                refund_applied: U256::zero(),
+                fee: U256::zero(),
+                burnt_fee: None,
+                gas_charged: U256::from(30000),
+                gas_sponsor_paid: false,
+                logs: vec![],
+                storage_sponsor_paid: false,
+                storage_collateralized: vec![],
+                storage_released: vec![],
+                contracts_created: vec![],
+                output: vec![],
+                ext_result,
+            }
+        };
+        let check = |state_slot_2: u64, refund_counter: u64| {
+            let state =
+                pre_transact::make_state(&pre(state_slot_2), Space::Ethereum);
+            check_revert_rollback(
+                &executed(refund_counter),
+                U256::from(12500),
+                &state,
+                Some(&pre(0)),
+                Space::Ethereum,
+            )
+        };
+
+        assert!(check(0, 12500).is_ok());
+        assert!(matches!(
+            check(7, 0),
+            Err(TestErrorKind::RevertRollbackIncomplete(
+                RollbackLeak::Storage { got, .. }
+            )) if got == U256::from(7)
+        ));
+        assert!(matches!(
+            check(0, 12501),
+            Err(TestErrorKind::RevertRollbackIncomplete(
+                RollbackLeak::Refund { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn transient_storage_is_gone_once_committed() {
+        let contract = Address::from_low_u64_be(0x1000).with_evm_space();
+        let slot = (contract.address, U256::from(1));
+        let executed = |stale_load| {
+            let mut ext_result = typemap::ShareDebugMap::custom();
+            ext_result.insert::<TransientStorageKey>(TransientStorageTrace {
+                slots: BTreeSet::from([slot]),
+                stale_load,
+            });
+            Executed {
+                base_gas: 21000,
+                gas_used: U256::from(21000),
+                gross_gas_used: U256::from(21000),
+                refund_counter: U256::zero(),
+                refund_applied: U256::zero(),
+                fee: U256::zero(),
+                burnt_fee: None,
+                gas_charged: U256::from(21000),
+                gas_sponsor_paid: false,
+                logs: vec![],
+                storage_sponsor_paid: false,
+                storage_collateralized: vec![],
+                storage_released: vec![],
+                contracts_created: vec![],
+                output: vec![],
+                ext_result,
+            }
+        };
+        let mut key = [0u8; 32];
+        slot.1.to_big_endian(&mut key);
+        let pre: HashMap<Address, AccountInfo> = serde_json::from_str(
+            r#"{
+                "0x0000000000000000000000000000000000001000": {
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "0x600560015d00",
+                    "storage": {}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let mut state = pre_transact::make_state(&pre, Space::Ethereum);
+        state
+            .transient_set_storage(&contract, key.to_vec(), U256::from(5))
+            .unwrap();
+        assert!(matches!(
+            check_transient_storage(&executed(None), &state, Space::Ethereum),
+            Err(TestErrorKind::TransientStorageLeak(
+                TransientLeak::Committed { value, .. }
+            )) if value == U256::from(5)
+        ));
+
+        state.update_state_post_tx_execution(false);
+        assert!(check_transient_storage(
+            &executed(None),
+            &state,
+            Space::Ethereum
+        )
+        .is_ok());
+
+        let stale = StaleTransientLoad {
+            contract: slot.0,
+            key: slot.1,
+            got: U256::from(7),
+            expected: U256::zero(),
+        };
+        assert!(matches!(
+            check_transient_storage(
+                &executed(Some(stale)),
+                &state,
+                Space::Ethereum
+            ),
+            Err(TestErrorKind::TransientStorageLeak(
+                TransientLeak::StaleLoad { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn withdrawals_are_credited_in_wei() {
+        let address = Address::from_low_u64_be(2);
+        let withdrawal = |amount: u64| Withdrawal {
+            index: U256::zero(),
+            validator_index: U256::zero(),
+            address,
+            amount: U256::from(amount),
+        };
+
+        let mut state =
+            pre_transact::make_state(&HashMap::new(), Space::Ethereum);
+        apply_withdrawals(
+            &mut state,
+            &[withdrawal(1), withdrawal(2)],
+            Space::Ethereum,
+        );
+
+        let balance = state.balance(&address.with_evm_space()).unwrap();
+        assert_eq!(balance, U256::from(3_000_000_000u64));
+    }
+
+    #[test]
+    fn empty_accounts_follow_eip161() {
+        let unit: StateTestUnit = serde_json::from_value(json!({
+            "env": {
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x05f5e100",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8"
+            },
+            "pre": {
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {}
+                },
+                "0x0000000000000000000000000000000000001000": {
+                    "nonce": "0x00",
+                    "balance": "0x00",
+                    "code": "0x",
+                    "storage": {}
+                }
+            },
+            "post": {},
+            "transaction": tx_json(json!({})),
+            "config": { "chainid": "0x01" }
+        }))
+        .unwrap();
+        let test: StateTest = serde_json::from_str(
+            r#"{
+                "indexes": { "data": 0, "gas": 0, "value": 0 },
+                "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"
+            }"#,
+        )
+        .unwrap();
+        let tx =
+            pre_transact::make_tx(&unit.transaction, &None, &FIRST, 1, false)
+                .unwrap();
+
+        let sender: Address =
+            "a94f5374fce5edbc8e2a8697c15331677e6ebf0b".parse().unwrap();
+        let empty = Address::from_low_u64_be(0x1000);
+        let expected = |with_empty: bool| -> HashMap<Address, AccountInfo> {
+            let mut expected = HashMap::new();
+            expected.insert(sender, unit.pre[&sender].clone());
+            if with_empty {
+                expected.insert(empty, unit.pre[&empty].clone());
+            }
+            expected
+        };
+        // Touched by a write leaving it empty
+        let check_touched = |pre: &HashMap<Address, AccountInfo>,
+                             with_empty: bool,
+                             touch: bool,
+                             spec: SpecId| {
+            let mut state = pre_transact::make_state(pre, Space::Ethereum);
+            if touch {
+                let one = U256::one();
+                state.add_balance(&empty.with_evm_space(), &one).unwrap();
+                state.sub_balance(&empty.with_evm_space(), &one).unwrap();
+                state.update_state_post_tx_execution(false);
+            }
+            check_execution_outcome(
+                &tx,
+                &state,
+                &unit,
+                &test,
+                &expected(with_empty),
+                None,
+                spec,
+            )
+        };
+        let check = |pre: &HashMap<Address, AccountInfo>,
+                     with_empty: bool,
+                     spec: SpecId| {
+            check_touched(pre, with_empty, true, spec)
+        };
+        let diff_of = |result: Result<StateSurface, TestErrorKind>| match result
+        {
+            Err(TestErrorKind::StateMismatch(StateMismatch::PostState(
+                mut diff,
+            ))) => diff.accounts.remove(&empty),
+            other => panic!("expect a post state mismatch, got {:?}", other),
+        };
+
+        // The touched empty account is cleared since Spurious Dragon, whether
+        // the fixture lists it or not
+        assert!(check(&unit.pre, false, SpecId::SPURIOUS_DRAGON).is_ok());
+        assert!(check(&unit.pre, true, SpecId::SPURIOUS_DRAGON).is_ok());
+
+        // An untouched one stays and must be listed
+        let untouched = |with_empty| {
+            check_touched(&unit.pre, with_empty, false, SpecId::SPURIOUS_DRAGON)
+        };
+        assert!(untouched(true).is_ok());
+        assert!(matches!(
+            diff_of(untouched(false)),
+            Some(AccountDiff::Extra)
+        ));
+
+        // Before, it stays and must be listed
+        assert!(check(&unit.pre, true, SpecId::HOMESTEAD).is_ok());
+        assert!(matches!(
+            diff_of(check(&unit.pre, false, SpecId::HOMESTEAD)),
+            Some(AccountDiff::Extra)
+        ));
+
+        let mut without_empty = unit.pre.clone();
+        without_empty.remove(&empty);
+        assert!(check(&without_empty, true, SpecId::SPURIOUS_DRAGON).is_ok());
+        assert!(matches!(
+            diff_of(check_touched(
+                &without_empty,
+                true,
+                false,
+                SpecId::HOMESTEAD
+            )),
+            Some(AccountDiff::Missing)
+        ));
+    }
+
+    #[test]
+    fn code_is_compared_by_size_and_hash() {
+        let unit: StateTestUnit = serde_json::from_value(json!({
+            "env": {
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x05f5e100",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8"
+            },
+            "pre": {
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {}
+                },
+                "0x0000000000000000000000000000000000001000": {
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "0x60016000f3",
+                    "storage": {}
+                }
+            },
+            "post": {},
+            "transaction": tx_json(json!({})),
+            "config": { "chainid": "0x01" }
+        }))
+        .unwrap();
+        let test: StateTest = serde_json::from_str(
+            r#"{
+                "indexes": { "data": 0, "gas": 0, "value": 0 },
+                "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"
+            }"#,
+        )
+        .unwrap();
+        let tx =
+            pre_transact::make_tx(&unit.transaction, &None, &FIRST, 1, false)
+                .unwrap();
+        let contract = Address::from_low_u64_be(0x1000);
+        let state = pre_transact::make_state(&unit.pre, Space::Ethereum);
+        let check = |expected: &HashMap<Address, AccountInfo>| {
+            check_execution_outcome(
+                &tx,
+                &state,
+                &unit,
+                &test,
+                expected,
+                None,
+                SpecId::CANCUN,
+            )
+        };
+
+        // The sender has no code, whose hash is the one of empty code
+        assert!(check(&unit.pre).is_ok());
+
+        let mut truncated = unit.pre.clone();
+        truncated.get_mut(&contract).unwrap().code.0.push(0x00);
+        assert!(matches!(
+            check(&truncated),
+            Err(TestErrorKind::CodeMismatch {
+                addr,
+                expected,
+                got,
+                expected_len: 6,
+                got_len: 5,
+            }) if addr == contract
+                && expected == keccak(hex::decode("60016000f300").unwrap())
+                && got == keccak(hex::decode("60016000f3").unwrap())
+        ));
+    }
+
+    #[test]
+    fn creator_nonces_count_the_creations() {
+        let unit: StateTestUnit = serde_json::from_value(json!({
+            "env": {
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x05f5e100",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8"
+            },
+            "pre": {
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {}
+                },
+                "0x0000000000000000000000000000000000001000": {
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "0x600060006000f000",
+                    "storage": {}
+                }
+            },
+            "post": {},
+            "transaction": tx_json(json!({})),
+            "config": { "chainid": "0x01" }
+        }))
+        .unwrap();
+        let sender: Address =
+            "a94f5374fce5edbc8e2a8697c15331677e6ebf0b".parse().unwrap();
+        let contract = Address::from_low_u64_be(0x1000);
+        let mut state = pre_transact::make_state(&unit.pre, Space::Ethereum);
+        let mut creations = Creations::default();
+        creations.counts.insert(contract, 1);
+        // Not a contract, whose nonce the transaction bumps anyway
+        creations.counts.insert(sender, 2);
+
+        match check_creator_nonces(&creations, &state, &unit, Space::Ethereum) {
+            Err(TestErrorKind::NonceMismatch {
+                addr,
+                expected,
+                got,
+            }) => {
+                assert_eq!(addr, contract);
+                assert_eq!((expected, got), (U256::from(2), U256::from(1)));
+            }
+            other => panic!("expect a nonce mismatch, got {:?}", other),
+        }
+
+        state.inc_nonce(&contract.with_evm_space()).unwrap();
+        assert!(check_creator_nonces(
+            &creations,
+            &state,
+            &unit,
+            Space::Ethereum
+        )
+        .is_ok());
+
+        // A failed CREATE may have consumed a nonce on a collision
+        state.inc_nonce(&contract.with_evm_space()).unwrap();
+        creations.unsure.insert(contract);
+        assert!(check_creator_nonces(
+            &creations,
+            &state,
+            &unit,
+            Space::Ethereum
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn surface_has_the_changed_accounts_and_slots() {
+        let accounts: HashMap<Address, AccountInfo> = serde_json::from_str(
+            r#"{
+                "0x0000000000000000000000000000000000001000": {
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "0x00",
+                    "storage": { "0x01": "0x05", "0x02": "0x07" }
+                },
+                "0x0000000000000000000000000000000000002000": {
+                    "nonce": "0x01",
+                    "balance": "0x00",
+                    "code": "0x00",
+                    "storage": { "0x01": "0x05", "0x03": "0x09" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let contract = Address::from_low_u64_be(0x1000);
+        let before = &accounts[&contract];
+        let after = &accounts[&Address::from_low_u64_be(0x2000)];
+
+        let mut surface = StateSurface::default();
+        surface.add_account(contract, Some(before), Some(before));
+        assert!(surface.accounts.is_empty());
+
+        // Slot 2 is cleared and slot 3 set
+        surface.add_account(contract, Some(before), Some(after));
+        assert_eq!(surface.accounts, BTreeSet::from([contract]));
+        assert_eq!(
+            surface.slots,
+            BTreeSet::from([
+                (contract, U256::from(2)),
+                (contract, U256::from(3))
+            ])
+        );
+
+        let deleted = Address::from_low_u64_be(0x3000);
+        surface.add_account(deleted, Some(before), None);
+        assert_eq!(surface.accounts.len(), 2);
+        assert_eq!(surface.slots.len(), 4);
+    }
+
+    #[test]
+    fn coinbase_is_warm_since_shanghai() {
+        let coinbase = Address::from_low_u64_be(0xc0ffee);
+        let precompiles: Vec<_> = (1..=0x0a)
+            .map(|i| Address::from_low_u64_be(i).with_evm_space())
+            .collect();
+        let with_coinbase: Vec<_> = precompiles
+            .iter()
+            .cloned()
+            .chain([coinbase.with_evm_space()])
+            .collect();
+        let missing = |warm: &[_], spec| {
+            missing_warm_accounts(warm, coinbase, spec, Space::Ethereum)
+        };
+
+        assert!(missing(&precompiles, SpecId::LONDON).is_empty());
+        assert_eq!(missing(&precompiles, SpecId::SHANGHAI), vec![coinbase]);
+        assert!(missing(&with_coinbase, SpecId::CANCUN).is_empty());
+        // The BLS precompiles of Prague are missing
+        assert_eq!(missing(&with_coinbase, SpecId::PRAGUE).len(), 7);
+    }
+
+    #[test]
+    fn selfdestruct_only_deletes_new_contracts_since_cancun() {
+        assert!(selfdestruct_deletes(SpecId::SHANGHAI, false));
+        assert!(selfdestruct_deletes(SpecId::CANCUN, true));
+        assert!(!selfdestruct_deletes(SpecId::CANCUN, false));
+        assert!(!selfdestruct_deletes(SpecId::PRAGUE, false));
+    }
+
+    #[test]
+    fn only_the_valid_authorizations_delegate() {
+        let sender = Address::from_low_u64_be(0xa);
+        let authority = Address::from_low_u64_be(0xb);
+        let contract = Address::from_low_u64_be(0xc);
+        let (first, second, third) = (
+            Address::from_low_u64_be(0x1000),
+            Address::from_low_u64_be(0x2000),
+            Address::from_low_u64_be(0x3000),
+        );
+        let account = |nonce, code: Vec<u8>| AccountInfo {
+            balance: U256::zero(),
+            code: code.into(),
+            nonce,
+            storage: HashMap::new(),
+        };
+        let pre = HashMap::from([
+            (sender, account(0, vec![])),
+            (authority, account(3, vec![])),
+            (contract, account(1, vec![0x00])),
+        ]);
+        let auth = |chain_id: u64, address, nonce| Authorization {
+            chain_id: U256::from(chain_id),
+            address,
+            nonce,
+        };
+        let authorizations = [
+            // Another chain
+            (Some(authority), auth(2, first, 3)),
+            // Any chain
+            (Some(authority), auth(0, first, 3)),
+            // Stale nonce, skipped alone
+            (Some(authority), auth(1, second, 3)),
+            // Not recovered
+            (None, auth(1, second, 4)),
+            // The last valid one wins
+            (Some(authority), auth(1, third, 4)),
+            // Signed before the transaction bumped the nonce
+            (Some(sender), auth(1, first, 0)),
+            (Some(sender), auth(1, Address::zero(), 1)),
+            // Has a code of its own
+            (Some(contract), auth(1, first, 1)),
+        ];
+        let delegations = expected_delegations(
+            authorizations
+                .iter()
+                .map(|(authority, auth)| (*authority, auth)),
+            1,
+            sender,
+            &pre,
+        );
+        assert_eq!(
+            delegations,
+            BTreeMap::from([(authority, third), (sender, Address::zero())])
+        );
+    }
+
+    #[test]
+    fn only_the_unexpected_written_accounts_are_spurious() {
+        let unit: StateTestUnit = serde_json::from_value(json!({
+            "env": {
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x05f5e100",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8"
+            },
+            "pre": {
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "nonce": "0x00",
+                    "balance": "0x0de0b6b3a7640000",
+                    "code": "0x",
+                    "storage": {}
+                }
+            },
+            "post": {},
+            "transaction": tx_json(json!({ "value": ["0x01"] })),
+            "config": { "chainid": "0x01" }
+        }))
+        .unwrap();
+        let sender = *unit.pre.keys().next().unwrap();
+        let receiver = Address::from_low_u64_be(0x1000);
+        let read = Address::from_low_u64_be(0x2000);
+
+        let mut state = pre_transact::make_state(&unit.pre, Space::Ethereum);
+        state
+            .sub_balance(&sender.with_evm_space(), &U256::one())
+            .unwrap();
+        state
+            .add_balance(&receiver.with_evm_space(), &U256::one())
+            .unwrap();
+        state.balance(&read.with_evm_space()).unwrap();
+        state.update_state_post_tx_execution(false);
+
+        // The pre-state starts clean, and the read account stays so
+        let touched = touched_accounts(&state, Space::Ethereum);
+        assert_eq!(touched, BTreeSet::from([sender, receiver]));
+
+        let mut surface = StateSurface::default();
+        surface.accounts.insert(sender);
+        assert!(matches!(
+            check_touched_accounts(&touched, &surface),
+            Err(TestErrorKind::SpuriousTouch { addr }) if addr == receiver
+        ));
+        surface.accounts.insert(receiver);
+        assert!(check_touched_accounts(&touched, &surface).is_ok());
+    }
+}
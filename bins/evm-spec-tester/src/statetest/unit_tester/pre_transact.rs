@@ -1,41 +1,64 @@
-use super::super::error::TestErrorKind;
+use super::{
+    super::{
+        error::TestErrorKind,
+        observer::Observer,
+        utils::{extract_155_chain_id_from_raw_tx, raw_tx_type},
+    },
+    tx_bytes::diff_tx_bytes,
+    SkipReason,
+};
 use cfx_executor::{
-    executive::{ChargeCollateral, TransactOptions, TransactSettings},
+    executive::{
+        eip7623_required_gas, gas_required_for, ChargeCollateral,
+        TransactOptions, TransactSettings,
+    },
     machine::Machine,
-    state::State,
+    spec::CommonParams,
+    state::{State, StateSnapshot},
 };
 use cfx_rpc_eth_types::{
-    AccountOverride, AccountStateOverrideMode, StateOverride,
+    AccountOverride, AccountStateOverrideMode, Bytes, StateOverride,
 };
 use cfx_statedb::StateDb;
 use cfx_types::{
-    h256_to_u256_be, u256_to_h256_be, AllChainID, Space, SpaceMap, H256, U256,
-    U64,
+    h256_to_u256_be, u256_to_h256_be, AddressUtil, AllChainID, Space, SpaceMap,
+    H256, U256, U512, U64,
 };
-use cfx_vm_types::Env;
+use cfx_vm_types::{extract_7702_payload, ConsensusGasSpec, Env, Spec};
 use cfxcore::verification::{VerificationConfig, VerifyTxMode};
-use cfxkey::{Address, Secret};
+use cfxkey::{public_to_address, Address, KeyPair, Secret, Signature};
 use eest_types::{
-    AccountInfo, Env as StateTestEnv, SignedAuthorization, TransactionParts,
-    TransactionType, TxPartIndices,
+    AccountInfo, SignedAuthorization, SpecId, SpecName, StateTest,
+    StateTestUnit, TransactionParts, TransactionType, TxPartIndices,
 };
+use keccak_hash::keccak;
 use primitives::{
     transaction::{
-        Action, AuthorizationListItem, Eip1559Transaction, Eip155Transaction,
-        Eip2930Transaction, Eip7702Transaction, EthereumTransaction,
-        TransactionError,
+        Action, AuthorizationListItem, Cip1559Transaction, Cip2930Transaction,
+        Eip1559Transaction, Eip155Transaction, Eip2930Transaction,
+        Eip7702Transaction, EthereumTransaction, NativeTransaction,
+        TransactionError, TypedNativeTransaction,
     },
     SignedTransaction, Transaction,
 };
+use rlp::RlpStream;
 use std::{
     collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
     u64,
 };
 
+/// Builds and signs the transaction of the post state. Every transaction
+/// type except the unprotected legacy one is signed for `chain_id`, the
+/// `config.chainid` of the fixture, which is also what the sender is
+/// recovered with. The raw `txbytes` only tell whether a legacy one is
+/// `unprotected` and the type an unsupported one is reported with, their
+/// chain id is checked by `check_tx_chain_id`. Fails with the type of the
+/// transaction if the executor has none of it.
 pub fn make_tx(
-    tx_meta: &TransactionParts, tx_part_indices: &TxPartIndices, chain_id: u64,
-    unprotected: bool,
-) -> Option<SignedTransaction> {
+    tx_meta: &TransactionParts, txbytes: &Option<Bytes>,
+    tx_part_indices: &TxPartIndices, chain_id: u64, unprotected: bool,
+) -> Result<SignedTransaction, SkipReason> {
     // basic fields
     let action = match tx_meta.to {
         Some(to) => Action::Call(to),
@@ -102,7 +125,11 @@ pub fn make_tx(
         }
         Some(TransactionType::Eip4844) => {
             // conflux does not support EIP-4844
-            return None;
+            return Err(unsupported_tx_type(
+                tx_meta,
+                txbytes,
+                tx_part_indices.data,
+            ));
         }
         Some(TransactionType::Eip7702) => {
             let authorization_list = tx_meta
@@ -137,35 +164,303 @@ pub fn make_tx(
             })
         }
         _ => {
-            return None;
+            return Err(unsupported_tx_type(
+                tx_meta,
+                txbytes,
+                tx_part_indices.data,
+            ));
+        }
+    };
+
+    let secret = Secret::from(tx_meta.secret_key);
+    Ok(Transaction::Ethereum(tx).sign(&secret))
+}
+
+/// Builds the native space counterpart of the fixture transaction. The
+/// storage limit is not bounded, the collateral is left out of the run (see
+/// `make_transact_options`) so that the balances stay comparable with the
+/// fixture.
+pub fn make_native_tx(
+    tx_meta: &TransactionParts, txbytes: &Option<Bytes>,
+    tx_part_indices: &TxPartIndices, chain_id: u32, epoch_height: u64,
+) -> Result<SignedTransaction, SkipReason> {
+    let action = match tx_meta.to {
+        Some(to) => Action::Call(to),
+        None => Action::Create,
+    };
+    let nonce = tx_meta.nonce;
+    let gas = tx_meta.gas_limit[tx_part_indices.gas];
+    let value = tx_meta.value[tx_part_indices.value];
+    let data = tx_meta.data[tx_part_indices.data].0.clone();
+    let storage_limit = u64::MAX;
+
+    let gas_price = tx_meta.gas_price.unwrap_or_default();
+    let max_fee_per_gas = tx_meta.max_fee_per_gas.unwrap_or_default();
+    let max_priority_fee_per_gas =
+        tx_meta.max_priority_fee_per_gas.unwrap_or_default();
+
+    let access_list = tx_meta
+        .access_lists
+        .get(tx_part_indices.data)
+        .cloned()
+        .unwrap_or(Some(vec![]))
+        .unwrap_or_default();
+
+    let tx = match tx_meta.tx_type(tx_part_indices.data) {
+        Some(TransactionType::Legacy) => {
+            TypedNativeTransaction::Cip155(NativeTransaction {
+                nonce,
+                gas_price,
+                gas,
+                action,
+                value,
+                storage_limit,
+                epoch_height,
+                chain_id,
+                data,
+            })
+        }
+        Some(TransactionType::Eip2930) => {
+            TypedNativeTransaction::Cip2930(Cip2930Transaction {
+                nonce,
+                gas_price,
+                gas,
+                action,
+                value,
+                storage_limit,
+                epoch_height,
+                chain_id,
+                data,
+                access_list,
+            })
+        }
+        Some(TransactionType::Eip1559) => {
+            TypedNativeTransaction::Cip1559(Cip1559Transaction {
+                nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas,
+                action,
+                value,
+                storage_limit,
+                epoch_height,
+                chain_id,
+                data,
+                access_list,
+            })
+        }
+        // blob and set code transactions have no native counterpart
+        _ => {
+            return Err(unsupported_tx_type(
+                tx_meta,
+                txbytes,
+                tx_part_indices.data,
+            ));
         }
     };
 
     let secret = Secret::from(tx_meta.secret_key);
-    Some(Transaction::Ethereum(tx).sign(&secret))
+    Ok(Transaction::Native(tx).sign(&secret))
+}
+
+/// The type of the transaction of `tx_meta`, the first byte of `txbytes` if
+/// given. Otherwise as `TransactionParts::tx_type` reads it from its
+/// fields, also when it lacks the destination its type requires.
+fn unsupported_tx_type(
+    tx_meta: &TransactionParts, txbytes: &Option<Bytes>,
+    access_list_index: usize,
+) -> SkipReason {
+    if let Some(tx_type) = txbytes.as_ref().and_then(raw_tx_type) {
+        return SkipReason::UnsupportedTxType(tx_type);
+    }
+    let tx_type = tx_meta.tx_type(access_list_index).unwrap_or(
+        if tx_meta.authorization_list.is_some() {
+            TransactionType::Eip7702
+        } else {
+            TransactionType::Eip4844
+        },
+    );
+    SkipReason::UnsupportedTxType(tx_type as u8)
+}
+
+/// Returns why the test is skipped for a gas limit above `max_gas`, if it
+/// is.
+pub fn max_gas_skip_reason(
+    unit: &StateTestUnit, test: &StateTest, max_gas: U256,
+) -> Option<String> {
+    // A bad index is reported when the test runs
+    let indexes = test.indexes.resolve(&unit.transaction).ok()?;
+    let gas_limit = unit.transaction.gas_limit[indexes.gas];
+    (gas_limit > max_gas)
+        .then(|| format!("gas limit {} above max gas {}", gas_limit, max_gas))
+}
+
+/// Returns why the test has no native space analogue, if it has none. The
+/// native run keeps the fixture addresses, so they must all be valid native
+/// addresses, and the sender must own the same address in both spaces.
+pub fn native_skip_reason(
+    unit: &StateTestUnit, test: &StateTest,
+) -> Option<String> {
+    let tx_meta = &unit.transaction;
+    // Counted with the unsupported transaction types when run
+    if tx_meta.max_fee_per_blob_gas.is_some() {
+        return None;
+    }
+    // A bad index is reported when the test runs
+    let Ok(indexes) = test.indexes.resolve(tx_meta) else {
+        return None;
+    };
+    match tx_meta.tx_type(indexes.data) {
+        Some(
+            TransactionType::Legacy
+            | TransactionType::Eip2930
+            | TransactionType::Eip1559,
+        ) => {}
+        Some(tx_type) => {
+            return Some(format!("no native {:?} transaction", tx_type))
+        }
+        None => return Some("unknown transaction type".into()),
+    }
+
+    let Some(to) = tx_meta.to else {
+        return Some(
+            "contract creation derives another address in native space".into(),
+        );
+    };
+
+    let Ok(key_pair) = KeyPair::from_secret(Secret::from(tx_meta.secret_key))
+    else {
+        return Some("invalid secret key".into());
+    };
+    if key_pair.address() != key_pair.evm_address() {
+        return Some(format!(
+            "sender {:?} is {:?} in native space",
+            key_pair.evm_address(),
+            key_pair.address()
+        ));
+    }
+
+    // The smallest one, as the accounts are in hash maps
+    let invalid = std::iter::once(&to)
+        .chain(unit.pre.keys())
+        .chain(test.state.keys())
+        .filter(|address| !address.is_genesis_valid_address())
+        .min();
+    if let Some(address) = invalid {
+        return Some(format!("{:?} is not a valid native address", address));
+    }
+
+    None
+}
+
+/// Check that every EIP-7702 authorization recovers to the signer recorded
+/// in the fixture. Authorizations without a recorded signer are not checked,
+/// nor the ones the executor recovers no authority from, see
+/// `executor_authority`: the fixtures sign them invalidly on purpose, and
+/// `check_delegations` checks that they set no code.
+pub fn check_authorization_list(
+    tx_meta: &TransactionParts,
+) -> Result<(), TestErrorKind> {
+    let Some(authorization_list) = tx_meta.authorization_list.as_ref() else {
+        return Ok(());
+    };
+
+    for (index, auth) in authorization_list.iter().enumerate() {
+        let Some(expected) = auth.signer() else {
+            continue;
+        };
+        let Some(got) = executor_authority(auth.signed()) else {
+            continue;
+        };
+        if got != expected {
+            return Err(TestErrorKind::AuthorityMismatch {
+                index,
+                expected,
+                got: Some(got),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// The authority the executor recovers from `auth`, which, unlike the signer
+/// of the fixture, is not recovered from a high s.
+pub(super) fn executor_authority(
+    auth: &SignedAuthorization,
+) -> Option<Address> {
+    let signature = Signature::from_rsv(
+        &u256_to_h256_be(auth.r()),
+        &u256_to_h256_be(auth.s()),
+        auth.y_parity(),
+    );
+    if !signature.is_low_s() {
+        return None;
+    }
+    recover_authority(auth)
+}
+
+// authority = ecrecover(keccak(MAGIC || rlp([chain_id, address, nonce])),
+// y_parity, r, s)
+fn recover_authority(auth: &SignedAuthorization) -> Option<Address> {
+    const MAGIC: u8 = 0x05;
+
+    let signature = Signature::from_rsv(
+        &u256_to_h256_be(auth.r()),
+        &u256_to_h256_be(auth.s()),
+        auth.y_parity(),
+    );
+    if !signature.is_valid() {
+        return None;
+    }
+
+    let authorization_hash = {
+        let inner = auth.inner();
+        let mut rlp = RlpStream::new_list(3);
+        rlp.append(&inner.chain_id)
+            .append(&inner.address)
+            .append(&inner.nonce);
+
+        let mut hash_input = vec![MAGIC];
+        hash_input.extend_from_slice(rlp.as_raw());
+
+        keccak(hash_input)
+    };
+
+    let public = cfxkey::recover(&signature, &authorization_hash).ok()?;
+    Some(public_to_address(&public, /* type_nibble */ false))
 }
 
-pub fn make_transact_options(check_base_price: bool) -> TransactOptions<()> {
+/// In native space the storage collateral is only estimated, Ethereum has no
+/// such cost to compare the balances with.
+pub fn make_transact_options(
+    check_base_price: bool, observer: Observer, space: Space,
+) -> TransactOptions<Observer> {
+    let charge_collateral = match space {
+        Space::Native => ChargeCollateral::EstimateSender,
+        Space::Ethereum => ChargeCollateral::Normal,
+    };
     let settings = TransactSettings {
-        charge_collateral: ChargeCollateral::Normal,
+        charge_collateral,
         charge_gas: true,
         check_base_price,
         check_epoch_bound: false,
         forbid_eoa_with_code: true,
     };
-    TransactOptions {
-        observer: (),
-        settings,
-    }
+    TransactOptions { observer, settings }
 }
 
-pub fn make_state(pre_state: &HashMap<Address, AccountInfo>) -> State {
+pub fn make_state(
+    pre_state: &HashMap<Address, AccountInfo>, space: Space,
+) -> State {
     // step1: setup the state according the pre state
     let mut state_override = StateOverride::new();
     for (address, info) in pre_state {
+        // A zero slot is no slot, however the fixture encodes it
         let account_state: HashMap<H256, H256> = info
             .storage
             .iter()
+            .filter(|(_, v)| !v.is_zero())
             .map(|(k, v)| {
                 (u256_to_h256_be(k.clone()), u256_to_h256_be(v.clone()))
             })
@@ -184,17 +479,51 @@ pub fn make_state(pre_state: &HashMap<Address, AccountInfo>) -> State {
 
     let statedb = StateDb::new_for_unit_test();
 
-    let mut state =
-        State::new_with_override(statedb, &state_override, Space::Ethereum)
-            .expect("db error");
-    state.commit_cache(false);
+    let mut state = State::new_with_override(statedb, &state_override, space)
+        .expect("db error");
+    // Committed to the storage, so that the accounts of the pre-state are
+    // read back clean and only the ones written by a transaction are dirty
+    state.commit_for_test(H256::zero()).expect("db error");
     state
 }
 
+/// The pre-state of a unit, built once and restored from a snapshot before
+/// every test of the unit.
+#[derive(Default)]
+pub struct PreState {
+    state: Option<State>,
+    snapshot: Option<StateSnapshot>,
+}
+
+impl PreState {
+    /// Returns the state of `pre`, rebuilt from scratch if it can not be
+    /// restored from the snapshot.
+    pub fn checkout(
+        &mut self, pre: &HashMap<Address, AccountInfo>, space: Space,
+    ) -> &mut State {
+        match (&mut self.state, &self.snapshot) {
+            (Some(state), Some(snapshot)) if state.no_checkpoint() => {
+                state.restore_snapshot(snapshot)
+            }
+            _ => {
+                let state = make_state(pre, space);
+                self.snapshot = state.snapshot();
+                self.state = Some(state);
+            }
+        }
+        self.state.as_mut().expect("state is built")
+    }
+}
+
+/// Builds the block environment of `unit` under `spec`. The base fee only
+/// applies since London, the difficulty is replaced by the random since the
+/// Merge, and the blob base fee follows the blob schedule of the spec.
 pub fn make_block_env(
-    machine: &Machine, env: &StateTestEnv, evm_chain_id: u64,
+    machine: &Machine, unit: &StateTestUnit, spec: SpecName,
     transaction_hash: H256,
 ) -> Env {
+    let env = &unit.env;
+    let evm_chain_id = unit.config.chainid;
     let config_chain_id: AllChainID =
         machine.params().chain_id.read().get_chain_id(0);
     let mut chain_id = BTreeMap::new();
@@ -203,18 +532,19 @@ pub fn make_block_env(
 
     let base_gas_price = env
         .current_base_fee
+        .filter(|_| spec.to_spec_id() >= SpecId::LONDON)
         .map(|v| SpaceMap::new(v, v))
         .unwrap_or_default();
 
     let blob_gas = env.current_excess_blob_gas.unwrap_or_default().as_u64();
+    let (_, update_fraction) = blob_params(unit, spec);
 
     Env {
         chain_id,
         number: env.current_number.as_u64(),
         author: env.current_coinbase,
         timestamp: env.current_timestamp.as_u64(),
-        // After ETH2.0, the DIFFICULTY opcode is changed to PREVRANDAO
-        difficulty: h256_to_u256_be(env.current_random.unwrap_or_default()),
+        difficulty: block_difficulty(env, spec),
         gas_limit: env.current_gas_limit,
         last_hash: env.previous_hash.unwrap_or_default(),
         accumulated_gas_used: U256::zero(),
@@ -226,12 +556,42 @@ pub fn make_block_env(
         transaction_epoch_bound: 100000,           /* set to default
                                                     * epoch bound */
         // pos_view, finalized_epoch is not set
-        blob_gas_fee: calc_blob_gasprice(blob_gas),
+        blob_gas_fee: calc_blob_gasprice(blob_gas, update_fraction),
         ..Default::default()
     }
 }
 
-fn calc_blob_gasprice(excess_blob_gas: u64) -> U256 {
+/// The value read by the DIFFICULTY opcode, which is PREVRANDAO since the
+/// Merge and reads the `currentRandom` of the fixture.
+fn block_difficulty(env: &eest_types::Env, spec: SpecName) -> U256 {
+    if spec.to_spec_id() >= SpecId::MERGE {
+        h256_to_u256_be(env.current_random.unwrap_or_default())
+    } else {
+        env.current_difficulty
+    }
+}
+
+const BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN: u64 = 3338477;
+const BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE: u64 = 5007716;
+
+/// The max blob count and the blob base fee update fraction of `spec`, from
+/// the blob schedule of the fixture if given.
+fn blob_params(unit: &StateTestUnit, spec: SpecName) -> (u64, u64) {
+    let blob_config = unit
+        .config
+        .blob_schedule
+        .as_ref()
+        .and_then(|schedule| schedule.get(&spec));
+    match blob_config {
+        Some(config) => (config.max, config.base_fee_update_fraction),
+        None if spec.to_spec_id() >= SpecId::PRAGUE => {
+            (9, BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE)
+        }
+        None => (6, BLOB_BASE_FEE_UPDATE_FRACTION_CANCUN),
+    }
+}
+
+fn calc_blob_gasprice(excess_blob_gas: u64, update_fraction: u64) -> U256 {
     fn fake_exponential(factor: u64, numerator: u64, denominator: u64) -> u128 {
         assert_ne!(denominator, 0, "attempt to divide by zero");
         let factor = factor as u128;
@@ -251,14 +611,78 @@ fn calc_blob_gasprice(excess_blob_gas: u64) -> U256 {
         output / denominator
     }
 
-    const BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE: u64 = 5007716;
     const MIN_BLOB_GASPRICE: u64 = 1;
-    fake_exponential(
-        MIN_BLOB_GASPRICE,
-        excess_blob_gas,
-        BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE,
-    )
-    .into()
+    fake_exponential(MIN_BLOB_GASPRICE, excess_blob_gas, update_fraction).into()
+}
+
+fn blob_base_fee(unit: &StateTestUnit, update_fraction: u64) -> U256 {
+    let excess_blob_gas = unit
+        .env
+        .current_excess_blob_gas
+        .unwrap_or_default()
+        .as_u64();
+    calc_blob_gasprice(excess_blob_gas, update_fraction)
+}
+
+/// Conflux has no EIP-4844 transaction, so `make_tx` can not build one. This
+/// runs the validity checks of a blob transaction instead and returns the
+/// exception it fails with, if any, so that the tests expecting an invalid
+/// blob transaction can still be verified.
+pub fn check_blob_tx(
+    unit: &StateTestUnit, spec: SpecName,
+) -> Option<&'static str> {
+    const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+    let tx_meta = &unit.transaction;
+    let spec_id = spec.to_spec_id();
+    if spec_id < SpecId::CANCUN {
+        return Some("TransactionException.TYPE_3_TX_PRE_FORK");
+    }
+    if tx_meta.to.is_none() {
+        return Some("TransactionException.TYPE_3_TX_CONTRACT_CREATION");
+    }
+
+    let hashes = &tx_meta.blob_versioned_hashes;
+    if hashes.is_empty() {
+        return Some("TransactionException.TYPE_3_TX_ZERO_BLOBS");
+    }
+
+    let (max_blobs, update_fraction) = blob_params(unit, spec);
+    if hashes.len() as u64 > max_blobs {
+        return Some("TransactionException.TYPE_3_TX_BLOB_COUNT_EXCEEDED");
+    }
+    if hashes
+        .iter()
+        .any(|h| h.as_bytes()[0] != VERSIONED_HASH_VERSION_KZG)
+    {
+        return Some(
+            "TransactionException.TYPE_3_TX_INVALID_BLOB_VERSIONED_HASH",
+        );
+    }
+
+    if tx_meta.max_fee_per_blob_gas.unwrap_or_default()
+        < blob_base_fee(unit, update_fraction)
+    {
+        return Some("TransactionException.INSUFFICIENT_MAX_FEE_PER_BLOB_GAS");
+    }
+
+    None
+}
+
+/// Checks that the raw transaction of the fixture, if it is an EIP-155
+/// legacy one, is signed for `chain_id`, the one `make_tx` signs with.
+pub fn check_tx_chain_id(
+    txbytes: &Option<Bytes>, chain_id: u64,
+) -> Result<(), TestErrorKind> {
+    match extract_155_chain_id_from_raw_tx(txbytes) {
+        Some(txbytes) if txbytes != chain_id => {
+            Err(TestErrorKind::ChainIdMismatch {
+                txbytes,
+                config: chain_id,
+            })
+        }
+        _ => Ok(()),
+    }
 }
 
 pub fn check_tx_bytes(
@@ -273,27 +697,165 @@ pub fn check_tx_bytes(
     let raw_tx = rlp::encode(&tx.transaction.transaction);
 
     if raw_tx != txbytes {
-        // trace!(
-        //     "\tCheck txbytes failed expected vs actually: {} \n{} \n{}",
-        //     self.name.clone(),
-        //     hex::encode(txbytes.0.clone()),
-        //     hex::encode(raw_tx)
-        // );
-        return Err(TestErrorKind::Internal(
-            "txbytes check failed".to_string(),
-        ));
+        return Err(TestErrorKind::TxBytesMismatch(diff_tx_bytes(
+            txbytes, &raw_tx,
+        )));
     }
 
     Ok(())
 }
 
-pub fn check_tx_common(
-    machine: &Machine, env: &Env, transaction: &SignedTransaction,
-    verification: &VerificationConfig,
+/// The EIP-7623 calldata floor follows the fork of the fixture, not the
+/// transition heights of the configuration.
+pub fn eip7623_enabled(spec: SpecName) -> bool {
+    spec.to_spec_id() >= SpecId::PRAGUE
+}
+
+/// The EIP-3860 init code limit and metering follow the fork of the fixture,
+/// like the calldata floor.
+pub fn eip3860_enabled(spec: SpecName) -> bool {
+    spec.to_spec_id() >= SpecId::SHANGHAI
+}
+
+/// The EIP-2028 calldata cost, 16 instead of 68 per non-zero byte, follows
+/// the fork of the fixture as well.
+pub fn eip2028_enabled(spec: SpecName) -> bool {
+    spec.to_spec_id() >= SpecId::ISTANBUL
+}
+
+/// The machine of a `spec_name` test built from its fork, `machine` with the
+/// transitions of the Ethereum forks set from `spec_name`. The builtins are
+/// set up with the machine from the transitions, so the params alone would
+/// leave the precompiles of a fork at their configured heights. The
+/// configuration is the default, as it is what a node runs, and as
+/// `fork_params` can not move every transition to the fork, the CIP-645
+/// bundle being one.
+pub fn fork_machine(machine: &Machine, spec_name: SpecName) -> Machine {
+    Machine::new_with_builtin(
+        fork_params(machine.params(), spec_name),
+        machine.vm_factory(),
+    )
+}
+
+/// The machines of a run built from their fork, see `fork_machine`. Each
+/// one is built by the first test of its fork and shared by the next ones,
+/// in every thread.
+#[derive(Clone)]
+pub struct ForkMachines {
+    machine: Arc<Machine>,
+    forks: Arc<Mutex<BTreeMap<SpecName, Arc<Machine>>>>,
+}
+
+impl ForkMachines {
+    /// The fork machines of `machine`, the one of the run.
+    pub fn new(machine: Arc<Machine>) -> Self {
+        ForkMachines {
+            machine,
+            forks: Default::default(),
+        }
+    }
+
+    /// The machine of the `spec_name` tests.
+    pub fn get(&self, spec_name: SpecName) -> Arc<Machine> {
+        let mut forks = self.forks.lock().expect("fork machines poisoned");
+        forks
+            .entry(spec_name)
+            .or_insert_with(|| Arc::new(fork_machine(&self.machine, spec_name)))
+            .clone()
+    }
+}
+
+/// `params` with the transitions that follow an Ethereum fork active from
+/// the start if `spec_name` includes the fork, and never otherwise, so that
+/// the spec of a test does not depend on its block height. The CIP-645
+/// bundle is left as configured, its EIPs can not be enabled one by one.
+fn fork_params(params: &CommonParams, spec_name: SpecName) -> CommonParams {
+    let spec = spec_name.to_spec_id();
+    let from = |fork: SpecId| if spec >= fork { 0 } else { u64::MAX };
+    let mut params = params.clone();
+
+    let numbers = &mut params.transition_numbers;
+    // EIP-3855: PUSH0
+    numbers.cip119 = from(SpecId::SHANGHAI);
+    // EIP-1153 and EIP-5656: transient storage and MCOPY
+    numbers.cancun_opcodes = from(SpecId::CANCUN);
+    // EIP-4844: point evaluation precompile
+    numbers.cip144 = from(SpecId::CANCUN);
+
+    let heights = &mut params.transition_heights;
+    // EIP-3541: reject new contract code starting with 0xEF
+    heights.cip150 = from(SpecId::LONDON);
+    // EIP-6780: SELFDESTRUCT only in the same transaction
+    heights.cip151 = from(SpecId::CANCUN);
+    heights.cip7702 = from(SpecId::PRAGUE);
+    // EIP-2537: BLS12-381 precompiles
+    heights.eip2537 = from(SpecId::PRAGUE);
+    heights.eip2935 = from(SpecId::PRAGUE);
+    heights.eip7623 = from(SpecId::PRAGUE);
+    params
+}
+
+/// The spec of a `spec_name` test at the block of `env`. The intrinsic gas
+/// rules, the EIP-7623 floor, the EIP-3860 init code limit and the EIP-2028
+/// calldata cost, follow `spec_name` even when `params` are the ones of the
+/// block rather than of the fork: the fixtures of every fork check their
+/// gas boundaries, which the spec of a block only gets right for its own
+/// fork, and the two CIP-645 ones can not be moved by `fork_params`. The
+/// other activations are the ones of `params` at the block.
+pub fn make_spec(
+    params: &CommonParams, env: &Env, spec_name: SpecName,
+) -> Spec {
+    let mut spec = params.spec(env.number, env.epoch_height);
+    spec.eip7623 = eip7623_enabled(spec_name);
+    spec.cip645.eip3860 = eip3860_enabled(spec_name);
+    spec.cip645.eip2028 = eip2028_enabled(spec_name);
+    spec.tx_data_non_zero_gas = if spec.cip645.eip2028 { 16 } else { 68 };
+    spec
+}
+
+/// The verification leaves the intrinsic gas to the executor from the CIP-76
+/// transition height on, until the EIP-7623 one, so it is checked here with
+/// the per-byte calldata costs of the fork.
+fn check_intrinsic_gas(
+    transaction: &SignedTransaction, spec: &ConsensusGasSpec,
 ) -> Result<(), TransactionError> {
-    let spec = machine
-        .spec(env.number, env.epoch_height)
-        .to_consensus_spec();
+    let intrinsic_gas = gas_required_for(
+        transaction.action() == Action::Create,
+        &transaction.data(),
+        transaction.access_list(),
+        transaction.authorization_len(),
+        spec,
+    );
+    if *transaction.gas() < intrinsic_gas.into() {
+        return Err(TransactionError::NotEnoughBaseGas {
+            required: intrinsic_gas.into(),
+            got: *transaction.gas(),
+        });
+    }
+    Ok(())
+}
+
+/// The checks of `transaction` done before its execution, by the consensus
+/// rules and by Ethereum. The reads of the checks load the sender into the
+/// cache of `state`, which the executor expects empty, so it is committed
+/// whatever the result.
+pub fn check_tx_common(
+    params: &CommonParams, env: &Env, transaction: &SignedTransaction,
+    verification: &VerificationConfig, spec: &Spec, state: &mut State,
+) -> Result<(), TestErrorKind> {
+    let checked =
+        check_tx_validity(params, env, transaction, verification, spec, state);
+    state.commit_cache(false);
+    checked
+}
+
+fn check_tx_validity(
+    params: &CommonParams, env: &Env, transaction: &SignedTransaction,
+    verification: &VerificationConfig, spec: &Spec, state: &State,
+) -> Result<(), TestErrorKind> {
+    check_block_gas_limit(env, transaction)?;
+
+    let spec = spec.to_consensus_spec();
     let verify_mode = VerifyTxMode::Remote(&spec);
 
     let chain_id = AllChainID::new(
@@ -305,7 +867,536 @@ pub fn check_tx_common(
         &transaction.transaction,
         chain_id,
         env.epoch_height,
-        &machine.params().transition_heights,
+        &params.transition_heights,
         verify_mode,
-    )
+    )?;
+    check_sender_eoa(transaction, state)?;
+
+    // Nor the init code size before the CIP-645 transition height
+    if spec.cip645.eip3860
+        && transaction.action() == Action::Create
+        && transaction.data().len() > spec.init_code_data_limit
+    {
+        return Err(TransactionError::CreateInitCodeSizeLimit);
+    }
+
+    check_intrinsic_gas(transaction, &spec)?;
+
+    // The verification only checks the floor from the EIP-7623 transition
+    // height on
+    let floor_gas = eip7623_required_gas(&transaction.data(), &spec);
+    if *transaction.gas() < floor_gas.into() {
+        return Err(TransactionError::NotEnoughBaseGas {
+            required: floor_gas.into(),
+            got: *transaction.gas(),
+        });
+    }
+
+    // A wrong nonce is reported by the executor, like Ethereum checks it
+    // before the balance
+    let sender = transaction.sender();
+    if sender.space == Space::Ethereum
+        && state.nonce(&sender).map_err(db_error)? == *transaction.nonce()
+    {
+        let balance = state.balance(&sender).map_err(db_error)?;
+        check_sender_balance(balance, transaction)?;
+    }
+    Ok(())
+}
+
+/// Whether `coinbase` is warm at the start of the execution of `tx` in the
+/// Ethereum space under `spec`: once the executor warms it as the EVM does
+/// from Shanghai on (EIP-3651), or if the transaction warms it as its
+/// sender, its receiver or an entry of its access list (EIP-2929).
+pub fn coinbase_warm_at_start(
+    tx: &SignedTransaction, coinbase: Address, spec: &Spec,
+) -> bool {
+    spec.align_evm
+        || tx.sender().address == coinbase
+        || tx.action() == Action::Call(coinbase)
+        || tx.access_list().map_or(false, |list| {
+            list.iter().any(|item| item.address == coinbase)
+        })
+}
+
+/// Rejects a transaction whose gas limit is above the gas left in the block
+/// by the preceding transactions, which the block can not include. A gas
+/// limit of exactly the gas left is accepted.
+fn check_block_gas_limit(
+    env: &Env, transaction: &SignedTransaction,
+) -> Result<(), TransactionError> {
+    let limit = env.gas_limit.saturating_sub(env.accumulated_gas_used);
+    if *transaction.gas() > limit {
+        return Err(TransactionError::GasLimitExceeded {
+            limit,
+            got: *transaction.gas(),
+        });
+    }
+    Ok(())
+}
+
+/// Rejects a transaction whose sender has code (EIP-3607), on every fork as
+/// Ethereum applied it retroactively. An Ethereum sender delegated by
+/// EIP-7702 still originates transactions, its code being only the
+/// designator, as the executor allows; a native sender has no such exemption.
+fn check_sender_eoa(
+    transaction: &SignedTransaction, state: &State,
+) -> Result<(), TestErrorKind> {
+    let sender = transaction.sender();
+    let Some(code) = state.code(&sender).map_err(db_error)? else {
+        return Ok(());
+    };
+    let delegated = sender.space == Space::Ethereum
+        && extract_7702_payload(&code).is_some();
+    if code.is_empty() || delegated {
+        return Ok(());
+    }
+    Err(TestErrorKind::SenderNotEoa {
+        addr: sender.address,
+    })
+}
+
+fn db_error(e: impl ToString) -> TestErrorKind {
+    TestErrorKind::DbError(e.to_string())
+}
+
+/// Rejects a transaction whose sender can not pay the value and the gas
+/// limit at the max gas price, as Ethereum does before the execution. The
+/// executor would charge what the sender has instead, and bump its nonce.
+fn check_sender_balance(
+    balance: U256, transaction: &SignedTransaction,
+) -> Result<(), TransactionError> {
+    let cost = transaction.gas().full_mul(*transaction.gas_price())
+        + U512::from(*transaction.value());
+    if U512::from(balance) < cost {
+        return Err(TransactionError::InsufficientBalance {
+            balance,
+            cost: U256::try_from(cost).unwrap_or(U256::MAX),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            super::error::TestErrorKind,
+            state_root::compute_state_root,
+            test_tx::{signed_tx, tx_parts, FIRST},
+        },
+        block_difficulty, check_authorization_list, check_block_gas_limit,
+        check_intrinsic_gas, check_sender_balance, check_sender_eoa,
+        check_tx_chain_id, fork_machine, fork_params, make_native_tx,
+        make_spec, make_state, make_tx, ForkMachines, SkipReason,
+    };
+    use cfx_executor::{
+        executive::gas_required_for,
+        machine::{Machine, VmFactory},
+        spec::CommonParams,
+    };
+    use cfx_rpc_eth_types::Bytes;
+    use cfx_types::{Address, AddressSpaceUtil, Space, U256};
+    use cfx_vm_types::{ConsensusGasSpec, Env};
+    use eest_types::{AccountInfo, SpecName, TransactionParts, TxPartIndices};
+    use primitives::transaction::{Action, TransactionError};
+    use serde_json::{json, Value};
+    use std::{collections::HashMap, path::Path, sync::Arc};
+
+    #[test]
+    fn invalid_authorization_signatures_are_not_compared() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join("authorization_nonces.json");
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap())
+                .unwrap();
+        let tx = &fixture["authorization_nonces"]["transaction"];
+        let with_first = |field: &str, value: &str| {
+            let mut tx = tx.clone();
+            tx["authorizationList"][0][field] = value.into();
+            serde_json::from_value::<TransactionParts>(tx).unwrap()
+        };
+        // The order of secp256k1 minus the s of the first authorization
+        let high_s =
+            "0x8abd8d25d71fb8c5d51e22b1ebdb5d6eb32a522164ac37363b47c25718f71a21";
+
+        let valid = serde_json::from_value(tx.clone()).unwrap();
+        assert!(check_authorization_list(&valid).is_ok());
+        assert!(check_authorization_list(&with_first("s", "0x00")).is_ok());
+        assert!(check_authorization_list(&with_first("s", high_s)).is_ok());
+        assert!(
+            check_authorization_list(&with_first("yParity", "0x02")).is_ok()
+        );
+        assert!(matches!(
+            check_authorization_list(&with_first(
+                "signer",
+                "0x0000000000000000000000000000000000000bad"
+            )),
+            Err(TestErrorKind::AuthorityMismatch { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn access_list_charges_intrinsic_gas() {
+        // The access list only applies to the first data entry.
+        let tx_meta = tx_parts(json!({
+            "gasLimit": ["0x0186a0"],
+            "data": ["0x", "0x"],
+            "accessLists": [[{
+                "address": "0x0000000000000000000000000000000000001000",
+                "storageKeys": [
+                    "0x0000000000000000000000000000000000000000000000000000000000000000",
+                    "0x0000000000000000000000000000000000000000000000000000000000000001"
+                ]
+            }]]
+        }));
+
+        let spec = ConsensusGasSpec::genesis_spec();
+        let intrinsic_gas = |data: usize| {
+            let indexes = TxPartIndices { data, ..FIRST };
+            let tx = make_tx(&tx_meta, &None, &indexes, 1, false).unwrap();
+            gas_required_for(
+                tx.action() == Action::Create,
+                &tx.data(),
+                tx.access_list(),
+                tx.authorization_len(),
+                &spec,
+            )
+        };
+
+        let access_list_gas =
+            spec.access_list_address_gas + 2 * spec.access_list_storage_key_gas;
+        assert_eq!(intrinsic_gas(0), intrinsic_gas(1) + access_list_gas as u64);
+    }
+
+    #[test]
+    fn sender_balance_covers_value_and_gas() {
+        let tx = signed_tx(json!({ "value": ["0x01"] }));
+
+        // 21000 gas at 10 wei, and 1 wei of value
+        let cost = U256::from(21000 * 10 + 1);
+        assert!(check_sender_balance(cost, &tx).is_ok());
+        assert!(matches!(
+            check_sender_balance(cost - 1, &tx),
+            Err(TransactionError::InsufficientBalance { cost: c, .. }) if c == cost
+        ));
+    }
+
+    #[test]
+    fn gas_limit_fits_in_the_block() {
+        let tx = signed_tx(json!({}));
+        let env = |gas_limit: u64, accumulated_gas_used: u64| Env {
+            gas_limit: gas_limit.into(),
+            accumulated_gas_used: accumulated_gas_used.into(),
+            ..Env::default()
+        };
+
+        assert!(check_block_gas_limit(&env(21000, 0), &tx).is_ok());
+        assert!(matches!(
+            check_block_gas_limit(&env(20999, 0), &tx),
+            Err(TransactionError::GasLimitExceeded { .. })
+        ));
+        // The preceding transactions used part of the block
+        assert!(check_block_gas_limit(&env(42000, 21000), &tx).is_ok());
+        assert!(matches!(
+            check_block_gas_limit(&env(42000, 21001), &tx),
+            Err(TransactionError::GasLimitExceeded { limit, .. })
+                if limit == 20999.into()
+        ));
+    }
+
+    #[test]
+    fn prevrandao_reads_current_random_since_merge() {
+        let env: eest_types::Env = serde_json::from_str(
+            r#"{
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x016345785d8a0000",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8",
+                "currentRandom": "0x00000000000000000000000000000000000000000000000000000000000a11ce",
+                "currentDifficulty": "0x020000",
+                "currentBaseFee": "0x07"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            block_difficulty(&env, SpecName::Berlin),
+            U256::from(0x020000)
+        );
+        assert_eq!(
+            block_difficulty(&env, SpecName::Paris),
+            U256::from(0xa11ce)
+        );
+        assert_eq!(
+            block_difficulty(&env, SpecName::Cancun),
+            U256::from(0xa11ce)
+        );
+    }
+
+    #[test]
+    fn only_eoas_and_delegated_accounts_send() {
+        let tx = signed_tx(json!({}));
+        let check = |code: &str| {
+            let pre: HashMap<Address, AccountInfo> =
+                serde_json::from_str(&format!(
+                    r#"{{
+                        "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {{
+                            "nonce": "0x00",
+                            "balance": "0x0de0b6b3a7640000",
+                            "code": "{}",
+                            "storage": {{}}
+                        }}
+                    }}"#,
+                    code
+                ))
+                .unwrap();
+            check_sender_eoa(&tx, &make_state(&pre, Space::Ethereum))
+        };
+
+        assert!(check("0x").is_ok());
+        assert!(
+            check("0xef01000000000000000000000000000000000000001000").is_ok()
+        );
+        assert!(matches!(
+            check("0x4460005500"),
+            Err(TestErrorKind::SenderNotEoa { addr })
+                if addr == tx.sender().address
+        ));
+        // A designator cut short is code like any other
+        assert!(
+            check("0xef010000000000000000000000000000000000001000").is_err()
+        );
+    }
+
+    #[test]
+    fn mixed_pre_state_encodings_build_the_same_state() {
+        let pre = |json: &str| -> HashMap<Address, AccountInfo> {
+            serde_json::from_str(json).unwrap()
+        };
+        let hex = pre(r#"{
+            "0x0000000000000000000000000000000000001000": {
+                "nonce": "0x01",
+                "balance": "0x0de0b6b3a7640000",
+                "code": "0x4460005500",
+                "storage": {
+                    "0x00": "0x000000000000000000000000000000000000000000000000000000000000002a"
+                }
+            }
+        }"#);
+        let mixed = pre(r#"{
+            "0x0000000000000000000000000000000000001000": {
+                "nonce": "1",
+                "balance": "1000000000000000000",
+                "code": "0x4460005500",
+                "storage": {
+                    "0x0000000000000000000000000000000000000000000000000000000000000000": "42",
+                    "0x01": "0x00"
+                }
+            }
+        }"#);
+
+        let root = |pre: &HashMap<Address, AccountInfo>| {
+            let mut state = make_state(pre, Space::Ethereum);
+            compute_state_root(&mut state, pre.keys().copied(), true)
+        };
+        assert_eq!(root(&hex), root(&mixed));
+    }
+
+    #[test]
+    fn fork_spec_does_not_depend_on_the_height() {
+        let mut config = CommonParams::default();
+        config.transition_numbers.cip119 = u64::MAX;
+        config.transition_heights.cip151 = 0;
+        let env = Env {
+            number: 10,
+            epoch_height: 10,
+            ..Default::default()
+        };
+
+        let shanghai = make_spec(
+            &fork_params(&config, SpecName::Shanghai),
+            &env,
+            SpecName::Shanghai,
+        );
+        assert!(shanghai.cip119);
+        assert!(!shanghai.cip151);
+        assert!(!shanghai.cip7702);
+
+        let prague = make_spec(
+            &fork_params(&config, SpecName::Prague),
+            &env,
+            SpecName::Prague,
+        );
+        assert!(prague.cip151 && prague.cip7702 && prague.eip7623);
+    }
+
+    #[test]
+    fn block_spec_takes_the_gas_rules_of_the_fork() {
+        // Everything active at the block, as in the default configuration
+        let mut config = CommonParams::default();
+        config.transition_numbers.cip119 = 0;
+        config.transition_heights.eip2935 = 0;
+        config.transition_heights.eip7623 = 0;
+        let env = Env {
+            number: 10,
+            epoch_height: 10,
+            ..Default::default()
+        };
+
+        let spec = make_spec(&config, &env, SpecName::ConstantinopleFix);
+        assert!(spec.cip119 && spec.eip2935);
+        assert!(!spec.eip7623);
+        assert!(!spec.cip645.eip3860 && !spec.cip645.eip2028);
+        assert_eq!(spec.tx_data_non_zero_gas, 68);
+    }
+
+    #[test]
+    fn fork_machine_sets_up_the_builtins_of_the_fork() {
+        let mut config = CommonParams::default();
+        config.transition_numbers.cip144 = u64::MAX;
+        config.transition_heights.eip2537 = u64::MAX;
+        let machine = Machine::new_with_builtin(config, VmFactory::new(1024));
+        let active = |spec: SpecName, address: u64| {
+            let address = Address::from_low_u64_be(address).with_evm_space();
+            fork_machine(&machine, spec).builtin(&address, 10).is_some()
+        };
+
+        // The point evaluation from Cancun, the BLS12-381 G1ADD from Prague
+        assert!(!active(SpecName::Shanghai, 0x0a));
+        assert!(active(SpecName::Cancun, 0x0a));
+        assert!(!active(SpecName::Cancun, 0x0b));
+        assert!(active(SpecName::Prague, 0x0b));
+    }
+
+    #[test]
+    fn fork_machines_are_built_once_per_fork() {
+        let machine = Machine::new_with_builtin(
+            CommonParams::default(),
+            VmFactory::new(1024),
+        );
+        let machines = ForkMachines::new(Arc::new(machine));
+        let cancun = machines.get(SpecName::Cancun);
+        assert!(Arc::ptr_eq(&cancun, &machines.get(SpecName::Cancun)));
+        assert!(Arc::ptr_eq(
+            &cancun,
+            &machines.clone().get(SpecName::Cancun)
+        ));
+        assert!(!Arc::ptr_eq(&cancun, &machines.get(SpecName::Prague)));
+    }
+
+    #[test]
+    fn calldata_costs_follow_the_fork() {
+        let mut tx_meta = tx_parts(json!({ "data": ["0x00010002"] }));
+        let env = Env::default();
+
+        for (fork, non_zero_gas) in [
+            (SpecName::Byzantium, 68),
+            (SpecName::ConstantinopleFix, 68),
+            (SpecName::Istanbul, 16),
+            (SpecName::Berlin, 16),
+            (SpecName::London, 16),
+            (SpecName::Shanghai, 16),
+            (SpecName::Cancun, 16),
+            (SpecName::Prague, 16),
+        ] {
+            let spec = make_spec(
+                &fork_params(&CommonParams::default(), fork),
+                &env,
+                fork,
+            )
+            .to_consensus_spec();
+            // Two zero bytes and two non-zero ones
+            let intrinsic_gas = 21000 + 2 * 4 + 2 * non_zero_gas;
+            let mut check = |gas: u64| {
+                tx_meta.gas_limit = vec![U256::from(gas)];
+                let tx = make_tx(&tx_meta, &None, &FIRST, 1, false).unwrap();
+                check_intrinsic_gas(&tx, &spec)
+            };
+            assert!(check(intrinsic_gas).is_ok(), "{:?}", fork);
+            assert!(
+                matches!(
+                    check(intrinsic_gas - 1),
+                    Err(TransactionError::NotEnoughBaseGas { required, .. })
+                        if required == U256::from(intrinsic_gas)
+                ),
+                "{:?}",
+                fork
+            );
+        }
+    }
+
+    #[test]
+    fn txbytes_are_signed_for_the_config_chain_id() {
+        // A legacy transaction, only its `v` matters
+        let legacy = |v: u64| {
+            let mut stream = rlp::RlpStream::new_list(9);
+            for _ in 0..6 {
+                stream.append(&0u64);
+            }
+            stream.append(&v).append(&1u64).append(&1u64);
+            Some(Bytes(stream.out().to_vec()))
+        };
+
+        // v = 35 + 2 * chain_id + parity
+        assert!(check_tx_chain_id(&legacy(37), 1).is_ok());
+        assert!(matches!(
+            check_tx_chain_id(&legacy(37), 2),
+            Err(TestErrorKind::ChainIdMismatch {
+                txbytes: 1,
+                config: 2
+            })
+        ));
+        // Unprotected, or not given
+        assert!(check_tx_chain_id(&legacy(27), 2).is_ok());
+        assert!(check_tx_chain_id(&None, 2).is_ok());
+    }
+
+    #[test]
+    fn unsupported_tx_types_are_skipped_with_their_type() {
+        let tx_meta = |fields: Value| {
+            let mut overrides = json!({
+                "gasPrice": null,
+                "maxFeePerGas": "0x0a",
+                "maxPriorityFeePerGas": "0x00"
+            });
+            let fields = fields.as_object().unwrap().clone();
+            overrides.as_object_mut().unwrap().extend(fields);
+            tx_parts(overrides)
+        };
+
+        let blob = tx_meta(json!({
+            "maxFeePerBlobGas": "0x01",
+            "blobVersionedHashes": []
+        }));
+        assert_eq!(
+            make_tx(&blob, &None, &FIRST, 1, false).unwrap_err(),
+            SkipReason::UnsupportedTxType(3)
+        );
+        // Without the destination a set code transaction has no type
+        let set_code = tx_meta(json!({ "to": null, "authorizationList": [] }));
+        assert_eq!(
+            make_tx(&set_code, &None, &FIRST, 1, false).unwrap_err(),
+            SkipReason::UnsupportedTxType(4)
+        );
+        // The raw transaction gives its type, whatever the fields read as
+        let txbytes = Some(Bytes(vec![0x05, 0xc0]));
+        assert_eq!(
+            make_tx(&set_code, &txbytes, &FIRST, 1, false).unwrap_err(),
+            SkipReason::UnsupportedTxType(5)
+        );
+        assert_eq!(
+            make_native_tx(&blob, &txbytes, &FIRST, 1, 0).unwrap_err(),
+            SkipReason::UnsupportedTxType(5)
+        );
+
+        let dynamic_fee = tx_meta(json!({}));
+        assert!(make_tx(&dynamic_fee, &None, &FIRST, 1, false).is_ok());
+        assert!(make_native_tx(&dynamic_fee, &None, &FIRST, 1, 0).is_ok());
+        assert_eq!(
+            make_native_tx(&blob, &None, &FIRST, 1, 0).unwrap_err(),
+            SkipReason::UnsupportedTxType(3)
+        );
+    }
 }
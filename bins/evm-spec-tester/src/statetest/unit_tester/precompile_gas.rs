@@ -0,0 +1,212 @@
+//! The gas of the precompiles priced by their input, computed from the
+//! formulas of the fork rather than by the pricers of the executor, which
+//! follow the CIP-645 bundle.
+
+use super::super::{error::TestErrorKind, observer::PrecompileCalls};
+use cfx_executor::executive::Executed;
+use cfx_types::{Address, U256};
+use eest_types::SpecId;
+use std::cmp::max;
+
+const MODEXP: u64 = 5;
+const ECPAIRING: u64 = 8;
+
+/// Checks the gas charged by the successful MODEXP and ECPAIRING calls of
+/// the transaction.
+pub fn check_precompile_gas(
+    executed: &Executed, spec: SpecId,
+) -> Result<(), TestErrorKind> {
+    for call in PrecompileCalls::of(Some(executed)).0 {
+        let Some(input) = &call.input else {
+            continue;
+        };
+        let Some(expected) = expected_gas(&call.address, input, spec) else {
+            continue;
+        };
+        if call.gas_used != expected {
+            return Err(TestErrorKind::PrecompileGasMismatch {
+                addr: call.address,
+                expected,
+                got: call.gas_used,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The gas of a call to the precompile at `address` with `input` under
+/// `spec`, if it is priced by its input.
+fn expected_gas(address: &Address, input: &[u8], spec: SpecId) -> Option<U256> {
+    if spec < SpecId::BYZANTIUM {
+        return None;
+    }
+    if *address == Address::from_low_u64_be(MODEXP) {
+        Some(modexp_gas(input, spec >= SpecId::BERLIN))
+    } else if *address == Address::from_low_u64_be(ECPAIRING) {
+        // EIP-1108 from Istanbul
+        let (base, pair) = if spec >= SpecId::ISTANBUL {
+            (45_000, 34_000)
+        } else {
+            (100_000, 80_000)
+        };
+        Some(
+            U256::from(base) + U256::from(pair) * U256::from(input.len() / 192),
+        )
+    } else {
+        None
+    }
+}
+
+/// EIP-198, repriced by EIP-2565 from Berlin, with the bounds of the
+/// `ModexpPricer` of the executor: the operands of no length are free before
+/// Berlin, and the lengths above `u32::MAX / 2` or a gas above `u64::MAX`
+/// saturate.
+fn modexp_gas(input: &[u8], eip2565: bool) -> U256 {
+    // The input is read as if followed by zeros
+    let word = |offset: usize, len: usize| {
+        let mut buf = [0u8; 32];
+        let start = offset.min(input.len());
+        let end = offset.saturating_add(len).min(input.len());
+        buf[32 - len..][..end - start].copy_from_slice(&input[start..end]);
+        U256::from_big_endian(&buf)
+    };
+    let (base_len, exp_len, mod_len) =
+        (word(0, 32), word(32, 32), word(64, 32));
+    if base_len.is_zero() && mod_len.is_zero() {
+        return U256::from(if eip2565 { 200 } else { 0 });
+    }
+    let limit = U256::from(u32::MAX / 2);
+    if base_len > limit || exp_len > limit || mod_len > limit {
+        return U256::max_value();
+    }
+    let (base_len, exp_len, mod_len) =
+        (base_len.as_u64(), exp_len.as_u64(), mod_len.as_u64());
+
+    let exp_head = word(96 + base_len as usize, exp_len.min(32) as usize);
+    let head_bits = match exp_head.bits() {
+        0 => 0,
+        bits => bits as u64 - 1,
+    };
+    let adjusted_exp_len = exp_len.saturating_sub(32) * 8 + head_bits;
+    let iterations = max(adjusted_exp_len, 1);
+
+    let len = max(base_len, mod_len);
+    if eip2565 {
+        let words = U256::from((len + 7) / 8);
+        let gas = words * words * U256::from(iterations) / U256::from(3);
+        max(U256::from(200), gas.min(U256::from(u64::MAX)))
+    } else {
+        let complexity = match len {
+            0..=64 => len * len,
+            65..=1024 => len * len / 4 + 96 * len - 3072,
+            _ => len * len / 16 + 480 * len - 199_680,
+        };
+        match complexity.checked_mul(iterations) {
+            Some(gas) => U256::from(gas / 20),
+            None => U256::max_value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expected_gas, ECPAIRING, MODEXP};
+    use cfx_types::{Address, U256};
+    use eest_types::SpecId;
+
+    /// The lengths of a MODEXP input, the operands being read as zeros.
+    fn modexp_lengths(base_len: u64, exp_len: u64, mod_len: u64) -> Vec<u8> {
+        let mut input = vec![];
+        for len in [base_len, exp_len, mod_len] {
+            let mut word = [0u8; 32];
+            U256::from(len).to_big_endian(&mut word);
+            input.extend_from_slice(&word);
+        }
+        input
+    }
+
+    /// The MODEXP input of the lengths and the first bytes of the exponent.
+    fn modexp_input(
+        base_len: u64, exp_len: u64, mod_len: u64, exp: &[u8],
+    ) -> Vec<u8> {
+        let mut input = modexp_lengths(base_len, exp_len, mod_len);
+        input.extend(std::iter::repeat(0xff).take(base_len as usize));
+        input.extend_from_slice(exp);
+        input
+    }
+
+    #[test]
+    fn precompile_gas_follows_the_fork() {
+        let modexp = Address::from_low_u64_be(MODEXP);
+        let gas = |input: &[u8], spec| {
+            expected_gas(&modexp, input, spec).unwrap().as_u64()
+        };
+
+        // 0xff^3 mod 5: the exponent has its highest bit at index 1
+        let small = modexp_input(1, 1, 1, &[3, 5]);
+        assert_eq!(gas(&small, SpecId::BYZANTIUM), 0);
+        assert_eq!(gas(&small, SpecId::BERLIN), 200);
+
+        // 64 byte operands and a 64 byte exponent whose first 32 bytes are 1
+        let mut exp = vec![0; 64];
+        exp[31] = 0x01;
+        let large = modexp_input(64, 64, 64, &exp);
+        // iterations = 8 * (64 - 32) + 0 = 256
+        assert_eq!(gas(&large, SpecId::ISTANBUL), 64 * 64 * 256 / 20);
+        assert_eq!(gas(&large, SpecId::PRAGUE), 8 * 8 * 256 / 3);
+
+        // A missing exponent is read as zero, so one iteration
+        let empty_exp = modexp_input(1000, 1, 1000, &[]);
+        assert_eq!(
+            gas(&empty_exp, SpecId::CONSTANTINOPLE),
+            (1000 * 1000 / 4 + 96 * 1000 - 3072) / 20
+        );
+
+        let pairing = Address::from_low_u64_be(ECPAIRING);
+        let input = vec![0; 2 * 192];
+        let pairing_gas =
+            |spec| expected_gas(&pairing, &input, spec).unwrap().as_u64();
+        assert_eq!(pairing_gas(SpecId::BYZANTIUM), 100_000 + 2 * 80_000);
+        assert_eq!(pairing_gas(SpecId::ISTANBUL), 45_000 + 2 * 34_000);
+
+        assert!(expected_gas(&modexp, &small, SpecId::HOMESTEAD).is_none());
+        let identity = Address::from_low_u64_be(4);
+        assert!(expected_gas(&identity, &small, SpecId::PRAGUE).is_none());
+    }
+
+    #[test]
+    fn modexp_gas_saturates_as_the_executor_does() {
+        let modexp = Address::from_low_u64_be(MODEXP);
+        let gas =
+            |input: &[u8], spec| expected_gas(&modexp, input, spec).unwrap();
+
+        // No base and no modulus, whatever the exponent
+        let empty = modexp_lengths(0, 1 << 40, 0);
+        assert_eq!(gas(&empty, SpecId::ISTANBUL), U256::zero());
+        assert_eq!(gas(&empty, SpecId::BERLIN), U256::from(200));
+
+        // The longest operand priced
+        let longest = modexp_lengths(u32::MAX as u64 / 2, 0, 0);
+        assert_eq!(
+            gas(&longest, SpecId::ISTANBUL),
+            U256::from(14_411_570_333_761_358u64)
+        );
+        assert_eq!(
+            gas(&longest, SpecId::BERLIN),
+            U256::from(24_019_198_012_642_645u64)
+        );
+        let too_long = modexp_lengths(0, 0, u32::MAX as u64 / 2 + 1);
+        assert_eq!(gas(&too_long, SpecId::BERLIN), U256::max_value());
+        let exp_too_long = modexp_lengths(1, u32::MAX as u64 / 2 + 1, 1);
+        assert_eq!(gas(&exp_too_long, SpecId::ISTANBUL), U256::max_value());
+
+        // A gas above 64 bits
+        let longest_exp = modexp_lengths(
+            u32::MAX as u64 / 2,
+            u32::MAX as u64 / 2,
+            u32::MAX as u64 / 2,
+        );
+        assert_eq!(gas(&longest_exp, SpecId::ISTANBUL), U256::max_value());
+        assert_eq!(gas(&longest_exp, SpecId::BERLIN), U256::from(u64::MAX));
+    }
+}
@@ -0,0 +1,150 @@
+use cfx_executor::state::State;
+use cfx_types::{Address, AddressSpaceUtil, Space, H256};
+use keccak_hash::keccak;
+use rlp::RlpStream;
+use std::{cmp::min, collections::BTreeSet};
+
+/// Computes the Ethereum state root of the eSpace accounts in `state`.
+///
+/// The accounts are collected from the state cache and `addresses`, so the
+/// caller should pass every account of the pre and post state. Empty accounts
+/// are left out if `clear_empty` (EIP-161) is set.
+///
+/// All the cached changes are applied to the statedb, so `state` must not be
+/// used for further execution.
+pub fn compute_state_root(
+    state: &mut State, addresses: impl IntoIterator<Item = Address>,
+    clear_empty: bool,
+) -> H256 {
+    let mut accounts: BTreeSet<Address> = addresses.into_iter().collect();
+    accounts.extend(
+        state
+            .committed_cache
+            .keys()
+            .filter(|addr| addr.space == Space::Ethereum)
+            .map(|addr| addr.address),
+    );
+
+    state.apply_changes_to_statedb(None).expect("db error");
+
+    let mut entries = vec![];
+    for address in accounts {
+        let user_addr = address.with_evm_space();
+        if !state.exists(&user_addr).expect("db error") {
+            continue;
+        }
+
+        let storage: Vec<_> = state
+            .storage_entries_in_db(&user_addr)
+            .expect("db error")
+            .into_iter()
+            .map(|(key, value)| (keccak(key).0.to_vec(), rlp::encode(&value)))
+            .collect();
+
+        if clear_empty
+            && storage.is_empty()
+            && state.is_eip158_empty(&user_addr).expect("db error")
+        {
+            continue;
+        }
+
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&state.nonce(&user_addr).expect("db error"));
+        stream.append(&state.balance(&user_addr).expect("db error"));
+        stream.append(&trie_root(storage));
+        stream.append(&state.code_hash(&user_addr).expect("db error"));
+
+        entries.push((keccak(address).0.to_vec(), stream.out()));
+    }
+
+    trie_root(entries)
+}
+
+/// Merkle Patricia trie root of the given key-value pairs, the keys must be
+/// distinct.
+fn trie_root(mut input: Vec<(Vec<u8>, Vec<u8>)>) -> H256 {
+    input.sort_by(|a, b| a.0.cmp(&b.0));
+    let nibbles: Vec<_> = input
+        .into_iter()
+        .map(|(key, value)| (to_nibbles(&key), value))
+        .collect();
+
+    let mut stream = RlpStream::new();
+    encode_node(&nibbles, 0, &mut stream);
+    keccak(stream.out())
+}
+
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+fn hex_prefix_encode(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let odd = nibbles.len() % 2 == 1;
+    let mut flag = (leaf as u8) << 5;
+    if odd {
+        flag |= 0x10 | nibbles[0];
+    }
+
+    let mut res = vec![flag];
+    let rest = if odd { &nibbles[1..] } else { nibbles };
+    res.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    res
+}
+
+/// Encodes the node holding `input`, whose keys share the first `pre_len`
+/// nibbles.
+fn encode_node(
+    input: &[(Vec<u8>, Vec<u8>)], pre_len: usize, stream: &mut RlpStream,
+) {
+    let Some((key, value)) = input.first() else {
+        stream.append_empty_data();
+        return;
+    };
+
+    if input.len() == 1 {
+        stream.begin_list(2);
+        stream.append(&hex_prefix_encode(&key[pre_len..], true));
+        stream.append(value);
+        return;
+    }
+
+    let shared = input[1..].iter().fold(key.len(), |acc, (k, _)| {
+        min(acc, key.iter().zip(k).take_while(|(a, b)| a == b).count())
+    });
+    if shared > pre_len {
+        stream.begin_list(2);
+        stream.append(&hex_prefix_encode(&key[pre_len..shared], false));
+        encode_child(input, shared, stream);
+        return;
+    }
+
+    stream.begin_list(17);
+    let branch_value = (key.len() == pre_len).then_some(value);
+    let mut begin = branch_value.is_some() as usize;
+    for nibble in 0..16 {
+        let len = input[begin..]
+            .iter()
+            .take_while(|(k, _)| k[pre_len] == nibble)
+            .count();
+        encode_child(&input[begin..begin + len], pre_len + 1, stream);
+        begin += len;
+    }
+    match branch_value {
+        Some(value) => stream.append(value),
+        None => stream.append_empty_data(),
+    };
+}
+
+/// Embeds a child node, by hash if its encoding is longer than 31 bytes.
+fn encode_child(
+    input: &[(Vec<u8>, Vec<u8>)], pre_len: usize, stream: &mut RlpStream,
+) {
+    let mut child = RlpStream::new();
+    encode_node(input, pre_len, &mut child);
+    let out = child.out();
+    if out.len() < 32 {
+        stream.append_raw(&out, 1);
+    } else {
+        stream.append(&keccak(&out));
+    }
+}
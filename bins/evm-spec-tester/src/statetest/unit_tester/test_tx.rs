@@ -0,0 +1,45 @@
+use super::pre_transact::make_tx;
+use eest_types::{TransactionParts, TxPartIndices};
+use primitives::SignedTransaction;
+use serde_json::{json, Value};
+
+/// The indexes of the first data, gas limit and value.
+pub const FIRST: TxPartIndices = TxPartIndices {
+    data: 0,
+    gas: 0,
+    value: 0,
+};
+
+/// The parts of a legacy transaction of 21000 gas at 10 wei, sending nothing
+/// to 0x1000 from 0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b, with the fields
+/// of `overrides` set on top. A null field is removed.
+pub fn tx_json(overrides: Value) -> Value {
+    let mut tx = json!({
+        "nonce": "0x00",
+        "gasPrice": "0x0a",
+        "gasLimit": ["0x5208"],
+        "to": "0x0000000000000000000000000000000000001000",
+        "value": ["0x00"],
+        "data": ["0x"],
+        "secretKey": "0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8"
+    });
+    let fields = tx.as_object_mut().unwrap();
+    for (field, value) in overrides.as_object().expect("overrides an object") {
+        match value {
+            Value::Null => fields.remove(field),
+            value => fields.insert(field.clone(), value.clone()),
+        };
+    }
+    tx
+}
+
+/// The parts of `tx_json(overrides)`.
+pub fn tx_parts(overrides: Value) -> TransactionParts {
+    serde_json::from_value(tx_json(overrides)).unwrap()
+}
+
+/// The transaction of the first parts of `tx_parts(overrides)`, signed for
+/// chain id 1.
+pub fn signed_tx(overrides: Value) -> SignedTransaction {
+    make_tx(&tx_parts(overrides), &None, &FIRST, 1, false).unwrap()
+}
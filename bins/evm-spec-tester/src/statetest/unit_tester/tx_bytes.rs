@@ -0,0 +1,226 @@
+use super::super::error::TxBytesDiff;
+use rlp::Rlp;
+
+/// Fields of the legacy transactions and of the EIP-2718 typed ones, in
+/// encoding order.
+const LEGACY_FIELDS: &[&str] = &[
+    "nonce", "gasPrice", "gas", "to", "value", "data", "v", "r", "s",
+];
+const EIP2930_FIELDS: &[&str] = &[
+    "chainId",
+    "nonce",
+    "gasPrice",
+    "gas",
+    "to",
+    "value",
+    "data",
+    "accessList",
+    "yParity",
+    "r",
+    "s",
+];
+const EIP1559_FIELDS: &[&str] = &[
+    "chainId",
+    "nonce",
+    "maxPriorityFeePerGas",
+    "maxFeePerGas",
+    "gas",
+    "to",
+    "value",
+    "data",
+    "accessList",
+    "yParity",
+    "r",
+    "s",
+];
+const EIP4844_FIELDS: &[&str] = &[
+    "chainId",
+    "nonce",
+    "maxPriorityFeePerGas",
+    "maxFeePerGas",
+    "gas",
+    "to",
+    "value",
+    "data",
+    "accessList",
+    "maxFeePerBlobGas",
+    "blobVersionedHashes",
+    "yParity",
+    "r",
+    "s",
+];
+const EIP7702_FIELDS: &[&str] = &[
+    "chainId",
+    "nonce",
+    "maxPriorityFeePerGas",
+    "maxFeePerGas",
+    "gas",
+    "to",
+    "value",
+    "data",
+    "accessList",
+    "authorizationList",
+    "yParity",
+    "r",
+    "s",
+];
+
+/// A transaction split into its type, `None` for a legacy one, and the raw
+/// RLP of its fields.
+struct Envelope<'a> {
+    tx_type: Option<u8>,
+    fields: Vec<&'a [u8]>,
+}
+
+impl<'a> Envelope<'a> {
+    fn decode(bytes: &'a [u8]) -> Result<Self, String> {
+        let (tx_type, payload) = match bytes.first() {
+            None => return Err("empty".into()),
+            Some(&b) if b >= 0xc0 => (None, bytes),
+            Some(&b) if b < 0x80 => (Some(b), &bytes[1..]),
+            Some(&b) => {
+                return Err(format!("unknown transaction type {:#04x}", b))
+            }
+        };
+
+        let rlp = Rlp::new(payload);
+        let info = rlp.payload_info().map_err(|e| e.to_string())?;
+        if !rlp.is_list() || info.total() != payload.len() {
+            return Err("not a single RLP list".into());
+        }
+        let fields = rlp.iter().map(|item| item.as_raw()).collect();
+        Ok(Envelope { tx_type, fields })
+    }
+
+    fn field_names(&self) -> &'static [&'static str] {
+        match self.tx_type {
+            None => LEGACY_FIELDS,
+            Some(0x01) => EIP2930_FIELDS,
+            Some(0x02) => EIP1559_FIELDS,
+            Some(0x03) => EIP4844_FIELDS,
+            Some(0x04) => EIP7702_FIELDS,
+            Some(_) => &[],
+        }
+    }
+}
+
+fn type_name(tx_type: Option<u8>) -> String {
+    tx_type.map_or("legacy".into(), |t| format!("{:#04x}", t))
+}
+
+/// A field as printed in a mismatch, `None` if the transaction has no such
+/// field.
+fn describe(raw: Option<&[u8]>) -> String {
+    let Some(raw) = raw else {
+        return "nothing".into();
+    };
+    let rlp = Rlp::new(raw);
+    match rlp.data() {
+        Ok(data) if !rlp.is_list() => format!("0x{}", hex::encode(data)),
+        _ => format!("a list of {} items", rlp.item_count().unwrap_or(0)),
+    }
+}
+
+/// Decodes the `expected` and `got` encodings of a transaction as far as
+/// possible to tell their first differing field.
+pub fn diff_tx_bytes(expected: &[u8], got: &[u8]) -> TxBytesDiff {
+    let field = match (Envelope::decode(expected), Envelope::decode(got)) {
+        (Err(e), _) => format!("can not decode the fixture txbytes: {}", e),
+        (_, Err(e)) => format!("can not decode the transaction: {}", e),
+        (Ok(e), Ok(g)) => first_difference(&e, &g),
+    };
+    TxBytesDiff {
+        field,
+        expected: expected.to_vec(),
+        got: got.to_vec(),
+    }
+}
+
+fn first_difference(expected: &Envelope, got: &Envelope) -> String {
+    if expected.tx_type != got.tx_type {
+        return format!(
+            "type: expected {}, got {}",
+            type_name(expected.tx_type),
+            type_name(got.tx_type)
+        );
+    }
+
+    let names = expected.field_names();
+    let len = expected.fields.len().max(got.fields.len());
+    for i in 0..len {
+        let (e, g) = (expected.fields.get(i), got.fields.get(i));
+        if e == g {
+            continue;
+        }
+        let name = names
+            .get(i)
+            .map_or_else(|| format!("field #{}", i), |name| name.to_string());
+
+        // The data is told apart by its length, which is often enough
+        if let (Some("data"), Some(e), Some(g)) = (names.get(i).copied(), e, g)
+        {
+            let (e, g) = (Rlp::new(e).data(), Rlp::new(g).data());
+            if let (Ok(e), Ok(g)) = (e, g) {
+                if e.len() != g.len() {
+                    return format!(
+                        "data length: expected {}, got {}",
+                        e.len(),
+                        g.len()
+                    );
+                }
+                let offset = e.iter().zip(g).position(|(a, b)| a != b);
+                if let Some(offset) = offset {
+                    return format!(
+                        "data at byte {}: expected {:#04x}, got {:#04x}",
+                        offset, e[offset], g[offset]
+                    );
+                }
+            }
+        }
+
+        return format!(
+            "{}: expected {}, got {}",
+            name,
+            describe(e.copied()),
+            describe(g.copied())
+        );
+    }
+    "same fields in a different encoding".into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_tx_bytes;
+    use rlp::RlpStream;
+
+    fn legacy(nonce: u64, data: &[u8]) -> Vec<u8> {
+        let mut s = RlpStream::new_list(9);
+        s.append(&nonce).append(&10u64).append(&21000u64);
+        s.append(&vec![0x11u8; 20])
+            .append(&0u64)
+            .append(&data.to_vec());
+        s.append(&27u64).append(&1u64).append(&2u64);
+        s.out().to_vec()
+    }
+
+    #[test]
+    fn reports_the_first_differing_field() {
+        let diff = diff_tx_bytes(&legacy(1, b"ab"), &legacy(2, b"abc"));
+        assert_eq!(diff.field, "nonce: expected 0x01, got 0x02");
+
+        let diff = diff_tx_bytes(&legacy(1, b"ab"), &legacy(1, b"abc"));
+        assert_eq!(diff.field, "data length: expected 2, got 3");
+
+        let diff = diff_tx_bytes(&legacy(1, b"ab"), &legacy(1, b"ac"));
+        assert_eq!(diff.field, "data at byte 1: expected 0x62, got 0x63");
+
+        let mut typed = vec![0x02];
+        typed.extend(legacy(1, b"ab"));
+        let diff = diff_tx_bytes(&typed, &legacy(1, b"ab"));
+        assert_eq!(diff.field, "type: expected 0x02, got legacy");
+
+        let diff = diff_tx_bytes(&[0x90, 0x01], &legacy(1, b"ab"));
+        assert!(diff.field.starts_with("can not decode the fixture txbytes"));
+        assert_eq!(diff.expected, vec![0x90, 0x01]);
+    }
+}
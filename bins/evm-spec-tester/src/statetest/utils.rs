@@ -1,7 +1,22 @@
 use cfx_rpc_eth_types::Bytes;
 use primitives::transaction::eth_transaction::eip155_signature;
-use std::path::{Path, PathBuf};
-use walkdir::{DirEntry, WalkDir};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A seed differing between runs, from the random keys of the std hash maps
+/// and the time.
+pub(crate) fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(nanos);
+    hasher.finish()
+}
 
 pub(crate) fn skip_test(path: &Path) -> bool {
     if contains_meta_dir(path) {
@@ -9,6 +24,11 @@ pub(crate) fn skip_test(path: &Path) -> bool {
     }
 
     let name = path.file_name().unwrap().to_str().unwrap();
+    // a compressed fixture is skipped like the plain one
+    let name = name
+        .strip_suffix(".gz")
+        .or_else(|| name.strip_suffix(".zst"))
+        .unwrap_or(name);
 
     matches!(
         name,
@@ -31,6 +51,42 @@ pub(crate) fn skip_test(path: &Path) -> bool {
     )
 }
 
+/// Whether `suffix` is `path` or its trailing components, so that an
+/// identifier does not depend on where the fixtures are checked out.
+pub(crate) fn path_has_suffix(path: &str, suffix: &str) -> bool {
+    let path = path.replace('\\', "/");
+    path == suffix || path.ends_with(&format!("/{}", suffix))
+}
+
+/// The `n` candidates closest to `target` by edit distance, closest first.
+pub(crate) fn closest(
+    target: &str, candidates: impl IntoIterator<Item = String>, n: usize,
+) -> Vec<String> {
+    let mut candidates: Vec<(usize, String)> = candidates
+        .into_iter()
+        .map(|c| (edit_distance(target, &c), c))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates.into_iter().take(n).map(|(_, c)| c).collect()
+}
+
+/// Levenshtein distance between `a` and `b`, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 /// Check if the path matches `.meta/**`.
 fn contains_meta_dir(path: &Path) -> bool {
     path.iter()
@@ -52,20 +108,6 @@ pub(crate) fn allowed_test(path: &Path, matches: Option<&str>) -> bool {
     false
 }
 
-pub(crate) fn find_all_json_tests(path: &Path) -> Vec<PathBuf> {
-    if path.is_file() {
-        vec![path.to_path_buf()]
-    } else {
-        WalkDir::new(path)
-            .follow_links(true)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|e| e.path().extension() == Some("json".as_ref()))
-            .map(DirEntry::into_path)
-            .collect()
-    }
-}
-
 // 1. Check if the input bytes is a rlp list
 // 2. If it is, rlp decode the raw tx
 // 3. Check the v value (the third from the last), if it is bigger than 28, then
@@ -87,4 +129,58 @@ pub(crate) fn extract_155_chain_id_from_raw_tx(
     }
 }
 
+/// Quotes `s` as a single shell word, only if it has characters the shell
+/// would interpret.
+pub(crate) fn shell_quote(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '_' | '.' | '/' | ':')
+        });
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
 fn is_rlp_list(raw: &[u8]) -> bool { !raw.is_empty() && raw[0] >= 0xc0 }
+
+/// The EIP-2718 type of the raw transaction, its first byte, or 0 for a
+/// legacy one.
+pub(crate) fn raw_tx_type(raw_tx: &Bytes) -> Option<u8> {
+    match raw_tx.0.first() {
+        Some(&tx_type) if tx_type <= 0x7f => Some(tx_type),
+        Some(_) if is_rlp_list(&raw_tx.0) => Some(0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, edit_distance, path_has_suffix, shell_quote};
+
+    #[test]
+    fn closest_candidates_come_first() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let candidates =
+            ["test_b", "test_a", "other", "test_a"].map(str::to_string);
+        assert_eq!(closest("test_c", candidates, 2), vec!["test_a", "test_b"]);
+
+        assert!(path_has_suffix("/data/prague/a.json", "prague/a.json"));
+        assert!(!path_has_suffix("/data/xprague/a.json", "prague/a.json"));
+    }
+
+    #[test]
+    fn shell_words_are_quoted_when_needed() {
+        assert_eq!(shell_quote("prague/a.json"), "prague/a.json");
+        assert_eq!(
+            shell_quote("a::test[fork_Prague]"),
+            "'a::test[fork_Prague]'"
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,109 @@
+use super::utils::path_has_suffix;
+use eest_types::SpecName;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+/// Tests known to fail, read from a JSON object mapping `path::name::spec`
+/// to the reason. The path is matched as a suffix of the fixture path, so
+/// the list does not depend on where the fixtures are checked out.
+#[derive(Debug, Default)]
+pub struct XfailList {
+    /// `(name, spec)` to the `(path, reason)` of its entries.
+    entries: HashMap<(String, String), Vec<(String, String)>>,
+}
+
+impl XfailList {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        // Ordered, so that the first of the entries matching a test is
+        // the same on every run
+        let list: BTreeMap<String, String> =
+            serde_json::from_str(&s).map_err(|e| e.to_string())?;
+        Self::from_entries(list)
+    }
+
+    fn from_entries(
+        list: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        let mut xfail = XfailList::default();
+        for (id, reason) in list {
+            // Test names may contain `::`, fixture paths and specs do not
+            let parsed = id.split_once("::").and_then(|(path, rest)| {
+                rest.rsplit_once("::")
+                    .map(|(name, spec)| (path, name, spec))
+            });
+            let Some((path, name, spec)) = parsed else {
+                return Err(format!("Expect path::name::spec, got: {}", id));
+            };
+            xfail
+                .entries
+                .entry((name.to_string(), spec.to_string()))
+                .or_default()
+                .push((path.to_string(), reason));
+        }
+        Ok(xfail)
+    }
+
+    /// The reason `name` is expected to fail under `spec`, if listed.
+    pub fn reason(
+        &self, path: &str, name: &str, spec: SpecName,
+    ) -> Option<&str> {
+        let entries = self
+            .entries
+            .get(&(name.to_string(), format!("{:?}", spec)))?;
+        entries
+            .iter()
+            .find(|(suffix, _)| path_has_suffix(path, suffix))
+            .map(|(_, reason)| reason.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XfailList;
+    use eest_types::SpecName;
+
+    #[test]
+    fn path_is_matched_as_suffix() {
+        let xfail = XfailList::from_entries([(
+            "prague/eip7702/set_code.json::tests/set_code.py::test_a[fork_Prague]::Prague"
+                .to_string(),
+            "not implemented".to_string(),
+        )])
+        .unwrap();
+
+        let name = "tests/set_code.py::test_a[fork_Prague]";
+        assert_eq!(
+            xfail.reason(
+                "/data/fixtures/prague/eip7702/set_code.json",
+                name,
+                SpecName::Prague
+            ),
+            Some("not implemented")
+        );
+        assert_eq!(
+            xfail.reason(
+                "/data/fixtures/other_prague/eip7702/set_code.json",
+                name,
+                SpecName::Prague
+            ),
+            None
+        );
+        assert_eq!(
+            xfail.reason(
+                "/data/fixtures/prague/eip7702/set_code.json",
+                name,
+                SpecName::Cancun
+            ),
+            None
+        );
+        assert!(XfailList::from_entries([(
+            "no_spec".to_string(),
+            "".to_string()
+        )])
+        .is_err());
+    }
+}
@@ -26,6 +26,15 @@ pub struct Executed {
     /// Gas used during execution of transaction.
     pub gas_used: U256,
 
+    /// Gas consumed by the execution of transaction, before the refund.
+    pub gross_gas_used: U256,
+
+    /// Refund counter accrued during execution, before it is capped.
+    pub refund_counter: U256,
+
+    /// Refund actually deducted from `gross_gas_used`.
+    pub refund_applied: U256,
+
     /// Fee that need to be paid by execution of this transaction.
     pub fee: U256,
 
@@ -96,6 +105,9 @@ impl Executed {
 
         Self {
             gas_used: *tx.gas(),
+            gross_gas_used: *tx.gas(),
+            refund_counter: U256::zero(),
+            refund_applied: U256::zero(),
             gas_charged,
             fee: *actual_gas_cost,
             burnt_fee,
@@ -134,6 +146,9 @@ impl Executed {
 
         Self {
             gas_used: *tx.gas(),
+            gross_gas_used: *tx.gas(),
+            refund_counter: U256::zero(),
+            refund_applied: U256::zero(),
             gas_charged: *tx.gas(),
             fee,
             burnt_fee,
@@ -165,6 +180,9 @@ impl Executed {
         };
 
         let RefundInfo {
+            gross_gas_used,
+            refund_counter,
+            refund_applied,
             gas_used,
             gas_charged,
             fees_value: fee,
@@ -185,6 +203,9 @@ impl Executed {
 
         Executed {
             gas_used,
+            gross_gas_used,
+            refund_counter,
+            refund_applied,
             gas_charged,
             fee,
             burnt_fee,
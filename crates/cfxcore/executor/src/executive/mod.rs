@@ -28,7 +28,8 @@ pub use transact_options::{
 };
 
 use crate::{
-    executive_observer::ExecutiveObserver, machine::Machine, state::State,
+    executive_observer::ExecutiveObserver, machine::Machine,
+    macros::perf_scope, state::State,
 };
 
 /// Transaction executor.
@@ -58,6 +59,7 @@ impl<'a> ExecutiveContext<'a> {
     ) -> DbResult<ExecutionOutcome> {
         // Transaction should execute on an empty cache
         assert!(self.state.cache.get_mut().is_empty());
+        perf_scope!(Transact);
 
         let fresh_exec = FreshExecutive::new(self, tx, options);
 
@@ -24,7 +24,8 @@ use cfx_vm_types::{
 
 use cfx_statedb::Result as DbResult;
 use cfx_types::{
-    Address, AddressSpaceUtil, BigEndianHash, Space, H256, U256, U512,
+    Address, AddressSpaceUtil, AddressWithSpace, BigEndianHash, Space, H256,
+    U256, U512,
 };
 use cfxkey::{public_to_address, Signature};
 use keccak_hash::keccak;
@@ -78,6 +79,17 @@ impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
             self.context.state.touch(&coinbase)?;
         }
 
+        let mut record_warm_accounts = false;
+        self.observer
+            .as_tracer()
+            .do_record_warm_accounts(&mut record_warm_accounts);
+        if record_warm_accounts {
+            let warm_accounts = self.initial_warm_accounts();
+            self.observer
+                .as_tracer()
+                .record_warm_accounts(&warm_accounts);
+        }
+
         if self.check_conflict_create_address(&params)? {
             return self.finalize_on_conflict_address(params.address);
         }
@@ -101,6 +113,25 @@ impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
 }
 
 impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
+    /// The accounts warm once the transaction addresses and the coinbase are
+    /// touched, see `CallTracer::record_warm_accounts`.
+    fn initial_warm_accounts(&self) -> Vec<AddressWithSpace> {
+        let space = self.tx.space();
+        let mut accounts = self.context.state.warm_accounts();
+        // The builtins and the native coinbase are warm in
+        // `Context::is_warm_account`
+        accounts.extend(
+            self.context
+                .machine
+                .active_builtins(space, self.context.env.number)
+                .map(|address| address.with_space(space)),
+        );
+        if space == Space::Native {
+            accounts.push(self.context.env.author.with_native_space());
+        }
+        accounts
+    }
+
     fn inc_sender_nonce(&mut self) -> DbResult<bool> {
         self.context.state.inc_nonce(&self.tx.sender())
     }
@@ -511,9 +542,12 @@ impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
             Ok(ExecutiveReturn { gas_left, .. }) => *gas_left,
             _ => 0.into(),
         };
+        let gross_gas_used = tx.gas() - gas_left;
         // gas_used is only used to estimate gas needed
-        let mut gas_used = tx.gas() - gas_left;
+        let mut gas_used = gross_gas_used;
 
+        let mut refund_counter = U256::zero();
+        let mut refund_applied = U256::zero();
         if spec.cip645.eip_sstore_and_refund_gas {
             let substate_refund = if self.substate.refund_gas > 0 {
                 self.substate.refund_gas as u128
@@ -525,10 +559,9 @@ impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
             } else {
                 0
             };
-            gas_used -= std::cmp::min(
-                gas_used / 5,
-                U256::from(substate_refund + eip7702_refund),
-            );
+            refund_counter = U256::from(substate_refund + eip7702_refund);
+            refund_applied = std::cmp::min(gas_used / 5, refund_counter);
+            gas_used -= refund_applied;
         }
 
         if gas_used < cost.floor_gas.into() {
@@ -555,6 +588,9 @@ impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
         let refund_value = gas_refunded.saturating_mul(cost.gas_price);
 
         RefundInfo {
+            gross_gas_used,
+            refund_counter,
+            refund_applied,
             gas_used,
             gas_charged,
             fees_value,
@@ -789,6 +825,9 @@ impl<'a, O: ExecutiveObserver> PreCheckedExecutive<'a, O> {
 }
 
 pub(super) struct RefundInfo {
+    pub gross_gas_used: U256,
+    pub refund_counter: U256,
+    pub refund_applied: U256,
     pub gas_used: U256,
     pub gas_charged: U256,
 
@@ -35,6 +35,11 @@ pub mod machine;
 /// Tool Macros
 mod macros;
 
+/// Execution Time Counters: Attributes the time of a transaction to the
+/// interpreter, the storage accesses and the account loads, with the `perf`
+/// feature.
+pub mod perf;
+
 /// Observability Interface: Defines a trait for extending functionality.
 /// Extensions can implement this trait to observe detailed aspects of the
 /// execution process.
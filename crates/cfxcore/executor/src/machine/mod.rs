@@ -78,6 +78,21 @@ impl Machine {
         })
     }
 
+    /// The addresses of the builtin contracts of `space` active at
+    /// `block_number`.
+    pub fn active_builtins(
+        &self, space: Space, block_number: BlockNumber,
+    ) -> impl Iterator<Item = &Address> {
+        let builtins = match space {
+            Space::Native => &self.builtins,
+            Space::Ethereum => &self.builtins_evm,
+        };
+        builtins
+            .iter()
+            .filter(move |(_, b)| b.is_active(block_number))
+            .map(|(address, _)| address)
+    }
+
     /// Get the general parameters of the chain.
     pub fn params(&self) -> &CommonParams { &self.params }
 
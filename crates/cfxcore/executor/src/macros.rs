@@ -37,3 +37,14 @@ macro_rules! unwrap_or_return {
         }
     };
 }
+
+/// Times the rest of the enclosing block as the given `PerfKind` with the
+/// `perf` feature, see `crate::perf`. It expands to nothing without it.
+macro_rules! perf_scope {
+    ($kind:ident) => {
+        #[cfg(feature = "perf")]
+        let _perf_scope =
+            $crate::perf::PerfScope::enter($crate::perf::PerfKind::$kind);
+    };
+}
+pub(crate) use perf_scope;
@@ -1,4 +1,5 @@
 use crate::stack::FrameResult;
+use cfx_types::{Address, AddressWithSpace};
 use cfx_vm_types::ActionParams;
 
 use impl_tools::autoimpl;
@@ -14,6 +15,21 @@ pub trait CallTracer {
     /// Prepares call result trace
     fn record_call_result(&mut self, result: &FrameResult) {}
 
+    /// Marks the call just recorded by `record_call` as a call to the builtin
+    /// contract (precompile) at `address`, its gas charge is the gas spent by
+    /// the call.
+    fn record_builtin_call(&mut self, address: &Address) {}
+
+    /// Whether `record_warm_accounts` is called, the warm accounts are not
+    /// collected otherwise.
+    fn do_record_warm_accounts(&self, enabled: &mut bool) {}
+
+    /// Records the accounts warm before the transaction runs its first frame
+    /// (EIP-2929): the ones the state marked warm, such as the sender, the
+    /// receiver, the access list and the coinbase of the EVM space once
+    /// aligned with the EVM, and the active precompiles.
+    fn record_warm_accounts(&mut self, accounts: &[AddressWithSpace]) {}
+
     /// Prepares create trace for given params.
     fn record_create(&mut self, params: &ActionParams) {}
 
@@ -0,0 +1,164 @@
+//! Execution Time Counters: Splits the time of a transaction between the
+//! interpreter, the storage accesses and the account loads. The counters are
+//! only kept with the `perf` feature, without it `perf_scope!` expands to
+//! nothing.
+//!
+//! The time of a scope excludes the one of the scopes opened inside it, so an
+//! account loaded by a storage read counts as an account load, and the
+//! interpreter time is the one of the opcode dispatch. The counters are per
+//! thread, as a transaction is executed on a single thread.
+
+use std::{cell::Cell, time::Instant};
+
+/// What a scope is timing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerfKind {
+    /// The rest of `ExecutiveContext::transact`.
+    Transact,
+    /// The interpreter running the frames, up to their sub-calls.
+    Interpreter,
+    /// Reads and writes of the storage.
+    Storage,
+    /// Loads of the accounts through the state cache.
+    AccountLoad,
+}
+
+/// Nanoseconds spent by the current thread in each kind of scope.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+    pub transact_ns: u64,
+    pub interpreter_ns: u64,
+    pub storage_ns: u64,
+    pub account_load_ns: u64,
+}
+
+impl PerfCounters {
+    const ZERO: Self = PerfCounters {
+        transact_ns: 0,
+        interpreter_ns: 0,
+        storage_ns: 0,
+        account_load_ns: 0,
+    };
+
+    fn add(&mut self, kind: PerfKind, ns: u64) {
+        let counter = match kind {
+            PerfKind::Transact => &mut self.transact_ns,
+            PerfKind::Interpreter => &mut self.interpreter_ns,
+            PerfKind::Storage => &mut self.storage_ns,
+            PerfKind::AccountLoad => &mut self.account_load_ns,
+        };
+        *counter += ns;
+    }
+}
+
+thread_local! {
+    static COUNTERS: Cell<PerfCounters> =
+        const { Cell::new(PerfCounters::ZERO) };
+    /// The innermost open scope and since when its time is counted.
+    static ACTIVE: Cell<Option<(PerfKind, Instant)>> =
+        const { Cell::new(None) };
+}
+
+/// The clock of the scopes, which the tests move by hand.
+#[cfg(not(test))]
+fn now() -> Instant { Instant::now() }
+
+#[cfg(test)]
+thread_local! {
+    static TEST_CLOCK: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Stopped, from the first time it is read.
+#[cfg(test)]
+fn now() -> Instant {
+    TEST_CLOCK.with(|clock| {
+        let now = clock.get().unwrap_or_else(Instant::now);
+        clock.set(Some(now));
+        now
+    })
+}
+
+fn charge(kind: PerfKind, since: Instant, now: Instant) {
+    let ns = now.saturating_duration_since(since).as_nanos() as u64;
+    COUNTERS.with(|c| {
+        let mut counters = c.get();
+        counters.add(kind, ns);
+        c.set(counters);
+    });
+}
+
+/// Returns the counters of the current thread and resets them.
+pub fn take() -> PerfCounters {
+    COUNTERS.with(|c| c.replace(PerfCounters::ZERO))
+}
+
+/// Counts the time until it is dropped to its kind, pausing the enclosing
+/// scope meanwhile.
+pub struct PerfScope {
+    parent: Option<PerfKind>,
+}
+
+impl PerfScope {
+    pub fn enter(kind: PerfKind) -> Self {
+        let now = now();
+        let parent = ACTIVE.with(|active| {
+            active.replace(Some((kind, now))).map(|(parent, since)| {
+                charge(parent, since, now);
+                parent
+            })
+        });
+        PerfScope { parent }
+    }
+}
+
+impl Drop for PerfScope {
+    fn drop(&mut self) {
+        let now = now();
+        let resumed = self.parent.map(|parent| (parent, now));
+        ACTIVE.with(|active| {
+            // Since when the scope is counted, after its inner scopes
+            if let Some((kind, since)) = active.replace(resumed) {
+                charge(kind, since, now);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{now, take, PerfCounters, PerfKind, PerfScope, TEST_CLOCK};
+    use std::time::Duration;
+
+    fn advance(ns: u64) {
+        let later = now() + Duration::from_nanos(ns);
+        TEST_CLOCK.with(|clock| clock.set(Some(later)));
+    }
+
+    #[test]
+    fn nested_scopes_are_not_counted_twice() {
+        take();
+        {
+            let _outer = PerfScope::enter(PerfKind::Interpreter);
+            advance(5);
+            {
+                let _inner = PerfScope::enter(PerfKind::Storage);
+                advance(20);
+                let _load = PerfScope::enter(PerfKind::AccountLoad);
+                advance(7);
+            }
+            // Resumed after the inner scopes
+            advance(3);
+        }
+        advance(100);
+        assert_eq!(
+            take(),
+            PerfCounters {
+                transact_ns: 0,
+                interpreter_ns: 8,
+                storage_ns: 20,
+                account_load_ns: 7,
+            }
+        );
+        assert_eq!(take(), PerfCounters::default());
+    }
+}
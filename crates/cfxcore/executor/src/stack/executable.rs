@@ -1,7 +1,7 @@
 use super::{FrameLocal, Resumable};
 use crate::{
     builtin::BuiltinExec, context::Context, executive_observer::TracerTrait,
-    internal_contract::InternalContractExec,
+    internal_contract::InternalContractExec, macros::perf_scope,
 };
 use cfx_statedb::Result as DbResult;
 use cfx_types::{AddressSpaceUtil, U256};
@@ -58,6 +58,7 @@ pub fn make_executable<'a>(
         .builtin(&code_address, frame_local.env.number)
     {
         trace!("CallBuiltin");
+        tracer.record_builtin_call(&params.code_address);
         return Box::new(BuiltinExec { builtin, params });
     }
 
@@ -90,7 +91,11 @@ impl Executable for Box<dyn Exec> {
     fn execute(
         self: Box<Self>, mut context: Context,
     ) -> DbResult<ExecutableOutcome> {
-        Ok(match self.exec(&mut context) {
+        let trap = {
+            perf_scope!(Interpreter);
+            self.exec(&mut context)
+        };
+        Ok(match trap {
             TrapResult::Return(result) => {
                 let result = separate_out_db_error(result)?;
                 // Backward compatible for a strange behaviour. If the contract
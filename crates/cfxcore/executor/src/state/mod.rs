@@ -26,5 +26,7 @@ pub use state_object::{
     update_pos_status, State, StateCommitResult,
     COMMISSION_PRIVILEGE_SPECIAL_KEY,
 };
+#[cfg(any(test, feature = "testonly_code"))]
+pub use state_object::StateSnapshot;
 #[cfg(test)]
 pub use state_object::{get_state_by_epoch_id, get_state_for_genesis_write};
@@ -3,7 +3,10 @@
 //! the logic for loading extension fields of an account.
 
 use super::{AccountEntry, OverlayAccount, RequireFields, State};
-use crate::{state::overlay_account::AccountEntryWithWarm, unwrap_or_return};
+use crate::{
+    macros::perf_scope, state::overlay_account::AccountEntryWithWarm,
+    unwrap_or_return,
+};
 use cfx_statedb::{
     Error as DbErrorKind, Result as DbResult, StateDb, StateDbExt,
 };
@@ -205,6 +208,7 @@ impl State {
         committed_cache: &'a HashMap<AddressWithSpace, AccountEntry>,
         db: &StateDb, address: &AddressWithSpace, require: RequireFields,
     ) -> DbResult<&'a mut AccountEntryWithWarm> {
+        perf_scope!(AccountLoad);
         let account_entry = match cache.entry(*address) {
             Occupied(e) => e.into_mut(),
             Vacant(e) => {
@@ -137,10 +137,37 @@ impl State {
 impl State {
     // Some test code will reuse state incorrectly, so we implement a version
     // which does not take ownership when committing.
-    #[cfg(test)]
+    #[cfg(any(test, feature = "testonly_code"))]
     pub fn commit_for_test(&mut self, epoch_id: EpochId) -> DbResult<()> {
         self.apply_changes_to_statedb(None)?;
         self.db.commit(epoch_id, None)?;
         Ok(())
     }
 }
+
+impl State {
+    /// Read all the non-zero storage entries of an account from the statedb,
+    /// keyed by the storage key without the account prefix. Changes not yet
+    /// applied to the statedb are not visible.
+    #[cfg(any(test, feature = "testonly_code"))]
+    pub fn storage_entries_in_db(
+        &mut self, address: &AddressWithSpace,
+    ) -> DbResult<Vec<(Vec<u8>, cfx_types::U256)>> {
+        use primitives::StorageValue;
+
+        let key_prefix = StorageKey::new_storage_root_key(&address.address)
+            .with_space(address.space);
+        let prefix_len = key_prefix.to_key_bytes().len();
+
+        let mut entries = vec![];
+        for (key, value) in
+            self.db.delete_all::<access_mode::Read>(key_prefix, None)?
+        {
+            let value: StorageValue = rlp::decode(&value)?;
+            if !value.value.is_zero() {
+                entries.push((key[prefix_len..].to_vec(), value.value));
+            }
+        }
+        Ok(entries)
+    }
+}
@@ -47,6 +47,11 @@ mod reward;
 
 mod state_override;
 
+/// Snapshots: Saves and restores the committed state, for test code running
+/// several transactions from the same pre-state.
+#[cfg(any(test, feature = "testonly_code"))]
+mod snapshot;
+
 #[cfg(test)]
 mod tests;
 
@@ -58,6 +63,8 @@ pub use self::{
     sponsor::COMMISSION_PRIVILEGE_SPECIAL_KEY,
     staking::initialize_or_update_dao_voted_params,
 };
+#[cfg(any(test, feature = "testonly_code"))]
+pub use self::snapshot::StateSnapshot;
 #[cfg(test)]
 pub use tests::{get_state_by_epoch_id, get_state_for_genesis_write};
 
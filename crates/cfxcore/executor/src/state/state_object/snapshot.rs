@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use cfx_types::AddressWithSpace;
+
+use super::{AccountEntry, GlobalStat, State};
+
+/// A copy of the committed accounts and global statistics of a `State`, used
+/// by test code to run several transactions from the same pre-state.
+pub struct StateSnapshot {
+    committed_cache: HashMap<AddressWithSpace, AccountEntry>,
+    global_stat: GlobalStat,
+}
+
+impl State {
+    /// Takes a snapshot of the committed state. Returns `None` if the state
+    /// has uncommitted cache entries or open checkpoints.
+    ///
+    /// The statedb is not part of the snapshot, so it must hold no changes
+    /// applied by `apply_changes_to_statedb` yet.
+    pub fn snapshot(&self) -> Option<StateSnapshot> {
+        if !self.no_checkpoint() || !self.cache.read().is_empty() {
+            return None;
+        }
+        let committed_cache = self
+            .committed_cache
+            .iter()
+            .map(|(addr, entry)| (*addr, entry.clone_account()))
+            .collect();
+        Some(StateSnapshot {
+            committed_cache,
+            global_stat: self.global_stat,
+        })
+    }
+
+    /// Reverts the state to `snapshot`, discarding every change made since,
+    /// including the changes applied to the statedb but not committed.
+    pub fn restore_snapshot(&mut self, snapshot: &StateSnapshot) {
+        assert!(self.no_checkpoint());
+        self.cache.get_mut().clear();
+        self.committed_cache = snapshot
+            .committed_cache
+            .iter()
+            .map(|(addr, entry)| (*addr, entry.clone_account()))
+            .collect();
+        self.global_stat = snapshot.global_stat;
+        self.tx_access_list = None;
+        self.db.discard_changes();
+    }
+}
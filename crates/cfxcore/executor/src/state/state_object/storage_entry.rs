@@ -1,5 +1,5 @@
 use super::{State, Substate};
-use crate::{return_if, try_loaded};
+use crate::{macros::perf_scope, return_if, try_loaded};
 use cfx_parameters::internal_contract_addresses::{
     SPONSOR_WHITELIST_CONTROL_CONTRACT_ADDRESS, SYSTEM_STORAGE_ADDRESS,
 };
@@ -72,6 +72,7 @@ impl State {
     pub fn storage_at(
         &self, address: &AddressWithSpace, key: &[u8],
     ) -> DbResult<U256> {
+        perf_scope!(Storage);
         let acc = try_loaded!(self.read_account_lock(address));
         acc.storage_at(&self.db, key)
     }
@@ -96,6 +97,7 @@ impl State {
     pub fn storage_entry_at(
         &self, address: &AddressWithSpace, key: &[u8],
     ) -> DbResult<StorageValue> {
+        perf_scope!(Storage);
         let acc = try_loaded!(self.read_account_lock(address));
         acc.storage_entry_at(&self.db, key)
     }
@@ -105,6 +107,7 @@ impl State {
         &mut self, address: &AddressWithSpace, key: Vec<u8>, value: U256,
         owner: Address, substate: &mut Substate,
     ) -> DbResult<()> {
+        perf_scope!(Storage);
         let old_value = self.storage_entry_at(address, &key)?;
         return_if!(
             old_value.value == value && !Self::force_reset_owner(address)
@@ -11,10 +11,11 @@ use cfx_parameters::{
 };
 use cfx_statedb::StateDb;
 use cfx_types::{
-    address_util::AddressUtil, Address, AddressSpaceUtil, BigEndianHash, U256,
+    address_util::AddressUtil, Address, AddressSpaceUtil, BigEndianHash, Space,
+    U256,
 };
 use keccak_hash::{keccak, KECCAK_EMPTY};
-use primitives::{EpochId, StorageKey, StorageLayout};
+use primitives::{AccessListItem, EpochId, StorageKey, StorageLayout};
 
 pub fn get_state_by_epoch_id(epoch_id: &EpochId) -> State {
     State::new(StateDb::new_for_unit_test_with_epoch(epoch_id)).unwrap()
@@ -995,6 +996,29 @@ fn create_contract_fail_previous_storage() {
     // TODO(69): checking ownership
 }
 
+#[test]
+fn warm_accounts_are_the_access_list_and_the_loaded_ones() {
+    let mut state = get_state_for_genesis_write();
+    let [previous, listed, loaded] =
+        [1, 2, 3].map(|i| Address::from_low_u64_be(i).with_evm_space());
+
+    // Loaded by a previous transaction
+    state.touch(&previous).unwrap();
+    state.update_state_post_tx_execution(false);
+
+    state.set_tx_access_list(
+        Space::Ethereum,
+        &[AccessListItem {
+            address: listed.address,
+            storage_keys: vec![],
+        }],
+    );
+    state.touch(&loaded).unwrap();
+    let mut warm = state.warm_accounts();
+    warm.sort();
+    assert_eq!(warm, vec![listed, loaded]);
+}
+
 #[test]
 fn test_automatic_collateral_normal_account() {
     let mut state = get_state_for_genesis_write();
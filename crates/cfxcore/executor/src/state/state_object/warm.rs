@@ -18,6 +18,24 @@ impl State {
         self.cache.read().get(address).map_or(false, |x| x.warm)
     }
 
+    /// The accounts warm in the transaction so far: the ones of its access
+    /// list and the ones it loaded.
+    pub fn warm_accounts(&self) -> Vec<AddressWithSpace> {
+        let mut accounts: Vec<_> = self
+            .tx_access_list
+            .iter()
+            .flat_map(|access_list| access_list.keys().cloned())
+            .collect();
+        accounts.extend(
+            self.cache
+                .read()
+                .iter()
+                .filter(|(_, entry)| entry.warm)
+                .map(|(address, _)| *address),
+        );
+        accounts
+    }
+
     pub fn is_warm_storage_entry(
         &self, address: &AddressWithSpace, key: &H256,
     ) -> DbResult<bool> {
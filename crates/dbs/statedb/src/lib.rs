@@ -113,6 +113,12 @@ mod impls {
             self.get_raw(key)
         }
 
+        /// Discards the changes not committed to the storage yet.
+        #[cfg(feature = "testonly_code")]
+        pub fn discard_changes(&mut self) {
+            *self.accessed_entries.get_mut() = Default::default();
+        }
+
         /// Set the value under `key` to `value` in `accessed_entries`.
         /// This method will read from db if `key` is not present.
         /// This method will also update the latest checkpoint if necessary.
@@ -3,16 +3,21 @@ use cfx_types::U256;
 use serde::Deserialize;
 use std::collections::HashMap;
 
-use super::deserializer::deserialize_str_as_u64;
+use super::deserializer::{
+    deserialize_str_as_u256, deserialize_str_as_u64, deserialize_u256_map,
+};
 
 /// Account information
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct AccountInfo {
+    /// `0x`-prefixed hex or decimal, as the nonce and the storage.
+    #[serde(deserialize_with = "deserialize_str_as_u256")]
     pub balance: U256,
     pub code: Bytes,
     #[serde(deserialize_with = "deserialize_str_as_u64")]
     pub nonce: u64,
+    #[serde(deserialize_with = "deserialize_u256_map")]
     pub storage: HashMap<U256, U256>,
 }
 
@@ -46,4 +51,46 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn mixed_encodings_are_normalized() {
+        let hex = r#"
+        {
+            "balance": "0x0de0b6b3a7640000",
+            "code": "0x",
+            "nonce": "0x0a",
+            "storage": {
+                "0x01": "0x0000000000000000000000000000000000000000000000000000000000000003",
+                "0x00000000000000000000000000000000000000000000000000000000000000ff": "0xff"
+            }
+        }
+        "#;
+        let mixed = r#"
+        {
+            "balance": "1000000000000000000",
+            "code": "0x",
+            "nonce": "10",
+            "storage": {
+                "1": "0x3",
+                "0xff": "255"
+            }
+        }
+        "#;
+        let hex: AccountInfo = serde_json::from_str(hex).unwrap();
+        let mixed: AccountInfo = serde_json::from_str(mixed).unwrap();
+        assert_eq!(hex, mixed);
+        assert_eq!(hex.balance, U256::exp10(18));
+
+        let duplicate = r#"
+        {
+            "balance": "0x",
+            "code": "0x",
+            "nonce": "0x0",
+            "storage": { "0x1": "0x1", "0x01": "0x2" }
+        }
+        "#;
+        assert!(serde_json::from_str::<AccountInfo>(duplicate).is_err());
+        assert!(crate::deserializer::parse_u256("0xzz").is_err());
+        assert!(crate::deserializer::parse_u256("12a").is_err());
+    }
 }
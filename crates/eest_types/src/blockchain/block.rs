@@ -41,13 +41,14 @@ pub struct Block {
     pub blocknumber: Option<String>, // TODO deserialize to u64
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Withdrawal {
-    index: U256,
-    validator_index: U256,
-    address: Address,
-    amount: U256,
+    pub index: U256,
+    pub validator_index: U256,
+    pub address: Address,
+    /// Amount in Gwei.
+    pub amount: U256,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -1,9 +1,18 @@
 use super::spec_id::SpecId;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Ethereum specification names
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Hash,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    Hash,
 )]
 pub enum SpecName {
     Frontier,
@@ -34,6 +43,33 @@ pub enum SpecName {
 }
 
 impl SpecName {
+    /// Every known spec name, in fork order.
+    pub const ALL: &'static [SpecName] = &[
+        Self::Frontier,
+        Self::FrontierToHomesteadAt5,
+        Self::Homestead,
+        Self::HomesteadToDaoAt5,
+        Self::HomesteadToEIP150At5,
+        Self::EIP150,
+        Self::EIP158,
+        Self::EIP158ToByzantiumAt5,
+        Self::Byzantium,
+        Self::ByzantiumToConstantinopleAt5,
+        Self::ByzantiumToConstantinopleFixAt5,
+        Self::Constantinople,
+        Self::ConstantinopleFix,
+        Self::Istanbul,
+        Self::Berlin,
+        Self::BerlinToLondonAt5,
+        Self::London,
+        Self::Paris,
+        Self::Merge,
+        Self::Shanghai,
+        Self::Cancun,
+        Self::Prague,
+        Self::Osaka,
+    ];
+
     /// Converts to a [SpecId].
     pub fn to_spec_id(&self) -> SpecId {
         match self {
@@ -78,4 +114,13 @@ mod tests {
         let spec = SpecName::Unknown;
         assert_eq!(format!("{:?}", spec), "Unknown");
     }
+
+    #[test]
+    fn all_names_are_known() {
+        for spec in SpecName::ALL {
+            let name = serde_json::to_value(spec).unwrap();
+            let parsed: SpecName = serde_json::from_value(name).unwrap();
+            assert_eq!(parsed, *spec);
+        }
+    }
 }
@@ -1,8 +1,9 @@
+use crate::Withdrawal;
 use cfx_types::{Address, H256, U256};
 use serde::Deserialize;
 
 /// Environment variables
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct Env {
     pub current_coinbase: Address,
@@ -22,6 +23,9 @@ pub struct Env {
     pub parent_excess_blob_gas: Option<U256>,
     pub parent_target_blobs_per_block: Option<U256>,
     pub current_excess_blob_gas: Option<U256>,
+
+    /// Withdrawals processed after the transaction, since Shanghai.
+    pub withdrawals: Option<Vec<Withdrawal>>,
 }
 
 #[cfg(test)]
@@ -46,4 +50,29 @@ mod tests {
         let env: Env = serde_json::from_str(json).unwrap();
         println!("{:?}", env);
     }
+
+    #[test]
+    fn deserialize_env_with_withdrawals() {
+        let json = r#"
+        {
+            "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+            "currentGasLimit": "0x016345785d8a0000",
+            "currentNumber": "0x01",
+            "currentTimestamp": "0x03e8",
+            "withdrawals": [
+                {
+                    "index": "0x00",
+                    "validatorIndex": "0x00",
+                    "address": "0x0000000000000000000000000000000000000002",
+                    "amount": "0x01"
+                }
+            ]
+        }
+        "#;
+
+        let env: Env = serde_json::from_str(json).unwrap();
+        let withdrawals = env.withdrawals.unwrap();
+        assert_eq!(withdrawals.len(), 1);
+        assert_eq!(withdrawals[0].amount, U256::from(1));
+    }
 }
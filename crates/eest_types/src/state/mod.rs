@@ -5,27 +5,35 @@ pub use env::*;
 pub use transaction::*;
 
 use serde::Deserialize;
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
 
 use crate::{AccountInfo, Config, SpecName};
 use cfx_rpc_primitives::Bytes;
-use cfx_types::{Address, H256};
+use cfx_types::{Address, H256, U256};
 
 /// State test indexed state result deserialization.
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct StateTest {
-    pub expect_exception: Option<String>,
+    pub expect_exception: Option<ExpectException>,
 
     /// Indexes
-    pub indexes: TxPartIndices,
+    pub indexes: PostIndexes,
     /// Post state hash
-    pub hash: H256,
+    #[serde(default)]
+    pub hash: Option<H256>,
     // /// Post state
     // #[serde(default)]
     // pub post_state: HashMap<Address, AccountInfo>,
     /// Logs root
     pub logs: H256,
+    /// Gas used by the transaction, only pinned by some fixtures
+    #[serde(default)]
+    pub gas_used: Option<ExpectGasUsed>,
 
     /// Output state.
     ///
@@ -37,8 +45,100 @@ pub struct StateTest {
     pub txbytes: Option<Bytes>,
 }
 
+impl StateTest {
+    /// The exception expected for the transaction at `indexes`, `None` for a
+    /// success.
+    pub fn expected_exception(
+        &self, indexes: &TxPartIndices,
+    ) -> Result<Option<&String>, String> {
+        match &self.expect_exception {
+            None => Ok(None),
+            Some(ExpectException::Single(reason)) => Ok(Some(reason)),
+            Some(ExpectException::ByIndexes(reasons)) => {
+                let key = format!(
+                    "d{}g{}v{}",
+                    indexes.data, indexes.gas, indexes.value
+                );
+                reasons
+                    .get(&key)
+                    .map(Option::as_ref)
+                    .ok_or_else(|| format!("no expected exception for {}", key))
+            }
+        }
+    }
+}
+
+/// The exception of a post state, either the same for every transaction or
+/// one per combination of indexes, keyed as `d0g1v0`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ExpectException {
+    Single(String),
+    /// `null` for a combination expected to succeed.
+    ByIndexes(BTreeMap<String, Option<String>>),
+}
+
+/// The gas used by a transaction, either exact or a `[min, max]` range for
+/// the fixtures that leave some of it to the implementation. A range whose
+/// min is above its max is refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "GasUsedRepr")]
+pub enum ExpectGasUsed {
+    Exact(U256),
+    Range([U256; 2]),
+}
+
+/// `ExpectGasUsed` as written in the fixtures, before its range is checked.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GasUsedRepr {
+    Exact(U256),
+    Range([U256; 2]),
+}
+
+impl TryFrom<GasUsedRepr> for ExpectGasUsed {
+    type Error = String;
+
+    fn try_from(repr: GasUsedRepr) -> Result<Self, Self::Error> {
+        match repr {
+            GasUsedRepr::Exact(gas) => Ok(ExpectGasUsed::Exact(gas)),
+            GasUsedRepr::Range([min, max]) if min > max => {
+                Err(format!("inverted gas used range [{:#x}, {:#x}]", min, max))
+            }
+            GasUsedRepr::Range(range) => Ok(ExpectGasUsed::Range(range)),
+        }
+    }
+}
+
+impl ExpectGasUsed {
+    /// Where `got` falls relative to the expected gas: `Less` under it,
+    /// `Greater` over it and `Equal` within it.
+    pub fn compare(&self, got: U256) -> Ordering {
+        let (min, max) = match *self {
+            ExpectGasUsed::Exact(gas) => (gas, gas),
+            ExpectGasUsed::Range([min, max]) => (min, max),
+        };
+        if got < min {
+            Ordering::Less
+        } else if got > max {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+impl fmt::Display for ExpectGasUsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpectGasUsed::Exact(gas) => write!(f, "{}", gas),
+            ExpectGasUsed::Range([min, max]) => write!(f, "[{}, {}]", min, max),
+        }
+    }
+}
+
 /// Single test unit struct
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 //#[serde(deny_unknown_fields)]
 // field config
 pub struct StateTestUnit {
@@ -50,6 +150,12 @@ pub struct StateTestUnit {
     pub pre: HashMap<Address, AccountInfo>,
     pub post: BTreeMap<SpecName, Vec<StateTest>>,
     pub transaction: TransactionParts,
+    /// Transactions applied in order to the pre-state before `transaction`,
+    /// each with its first data, gas limit and value. The post states are
+    /// only compared after `transaction`. An extension of the tester, the
+    /// fixtures of Ethereum have no such field.
+    #[serde(default, rename = "precedingTransactions")]
+    pub preceding_transactions: Vec<TransactionParts>,
     #[serde(default)]
     pub out: Option<Bytes>,
     pub config: Config,
@@ -174,4 +280,53 @@ mod tests {
         let test_unit: StateTestUnit = serde_json::from_str(json).unwrap();
         println!("{:?}", test_unit);
     }
+
+    #[test]
+    fn expected_exception_by_indexes() {
+        let test: StateTest = serde_json::from_str(
+            r#"{
+                "expectException": {
+                    "d0g0v0": "TransactionException.INTRINSIC_GAS_TOO_LOW",
+                    "d0g1v0": null
+                },
+                "indexes": { "data": 0, "gas": 1, "value": 0 },
+                "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"
+            }"#,
+        )
+        .unwrap();
+        let at = |data, gas, value| TxPartIndices { data, gas, value };
+
+        assert_eq!(
+            test.expected_exception(&at(0, 0, 0)),
+            Ok(Some(
+                &"TransactionException.INTRINSIC_GAS_TOO_LOW".to_string()
+            ))
+        );
+        assert_eq!(test.expected_exception(&at(0, 1, 0)), Ok(None));
+        assert!(test.expected_exception(&at(1, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn gas_used_is_exact_or_a_range() {
+        let exact: ExpectGasUsed = serde_json::from_str(r#""0x5208""#).unwrap();
+        assert_eq!(exact.compare(21000.into()), Ordering::Equal);
+        assert_eq!(exact.compare(21001.into()), Ordering::Greater);
+
+        let range: ExpectGasUsed =
+            serde_json::from_str(r#"["0x5208", "0x5300"]"#).unwrap();
+        assert_eq!(range.compare(20999.into()), Ordering::Less);
+        assert_eq!(range.compare(21100.into()), Ordering::Equal);
+        assert_eq!(range.compare(0x5301.into()), Ordering::Greater);
+        assert_eq!(range.to_string(), "[21000, 21248]");
+
+        let single: ExpectGasUsed =
+            serde_json::from_str(r#"["0x5208", "0x5208"]"#).unwrap();
+        assert_eq!(single.compare(21000.into()), Ordering::Equal);
+        let inverted =
+            serde_json::from_str::<ExpectGasUsed>(r#"["0x5300", "0x5208"]"#)
+                .unwrap_err();
+        assert!(inverted
+            .to_string()
+            .contains("inverted gas used range [0x5300, 0x5208]"));
+    }
 }
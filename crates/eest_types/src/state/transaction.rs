@@ -5,9 +5,10 @@ use cfx_rpc_primitives::Bytes;
 use cfx_types::{Address, H256, U256};
 use primitives::transaction::AccessList;
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
 
 /// Transaction parts.
-#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionParts {
     pub data: Vec<Bytes>,
@@ -30,6 +31,11 @@ pub struct TransactionParts {
     #[serde(default)]
     pub blob_versioned_hashes: Vec<H256>,
     pub max_fee_per_blob_gas: Option<U256>,
+
+    /// Labels of the `data` entries, by index, that post states may use
+    /// instead of the index.
+    #[serde(default)]
+    pub labels: HashMap<usize, String>,
 }
 
 impl TransactionParts {
@@ -75,7 +81,7 @@ impl TransactionParts {
 }
 
 /// Transaction part indices.
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct TxPartIndices {
     pub data: usize,
@@ -83,6 +89,78 @@ pub struct TxPartIndices {
     pub value: usize,
 }
 
+/// Index of the data of a post state, either a position in
+/// `TransactionParts::data` or the label of an entry, as `:label name`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DataIndex {
+    Index(usize),
+    Label(String),
+}
+
+impl fmt::Display for DataIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataIndex::Index(index) => write!(f, "{}", index),
+            DataIndex::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+/// Transaction part indices of a post state, as given by the fixture.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PostIndexes {
+    pub data: DataIndex,
+    pub gas: usize,
+    pub value: usize,
+}
+
+impl PostIndexes {
+    /// Translates a labeled data index with `TransactionParts::labels`, and
+    /// checks that every index is in bounds.
+    pub fn resolve(
+        &self, tx: &TransactionParts,
+    ) -> Result<TxPartIndices, String> {
+        let data = match &self.data {
+            DataIndex::Index(index) => *index,
+            DataIndex::Label(label) => {
+                let name = strip_label(label);
+                tx.labels
+                    .iter()
+                    .find(|(_, l)| strip_label(l) == name)
+                    .map(|(index, _)| *index)
+                    .ok_or_else(|| format!("unknown data label {}", label))?
+            }
+        };
+
+        for (part, index, len) in [
+            ("data", data, tx.data.len()),
+            ("gas", self.gas, tx.gas_limit.len()),
+            ("value", self.value, tx.value.len()),
+        ] {
+            if index >= len {
+                return Err(format!(
+                    "{} index {} out of bounds, {} entries",
+                    part, index, len
+                ));
+            }
+        }
+        Ok(TxPartIndices {
+            data,
+            gas: self.gas,
+            value: self.value,
+        })
+    }
+}
+
+fn strip_label(label: &str) -> &str {
+    label
+        .trim()
+        .strip_prefix(":label")
+        .map_or(label.trim(), str::trim)
+}
+
 #[cfg(test)]
 mod test {
 
@@ -132,4 +210,39 @@ mod test {
         let tx: TransactionParts = serde_json::from_str(tx).unwrap();
         println!("{:?}", tx);
     }
+
+    #[test]
+    fn resolve_labeled_data_index() {
+        let tx: TransactionParts = serde_json::from_str(
+            r#"{
+                "nonce": "0x00",
+                "gasPrice": "0x0a",
+                "gasLimit": ["0x0423ff"],
+                "to": "0x0000000000000000000000000000000000001000",
+                "value": ["0x00"],
+                "data": ["0x", "0x01"],
+                "labels": { "1": ":label declaredKeyWrite" },
+                "secretKey": "0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8"
+            }"#,
+        )
+        .unwrap();
+        let indexes = |data: &str| -> PostIndexes {
+            serde_json::from_str(&format!(
+                r#"{{ "data": {}, "gas": 0, "value": 0 }}"#,
+                data
+            ))
+            .unwrap()
+        };
+
+        let resolved = indexes(r#"":label declaredKeyWrite""#)
+            .resolve(&tx)
+            .unwrap();
+        assert_eq!(resolved.data, 1);
+        assert_eq!(indexes("0").resolve(&tx).unwrap().data, 0);
+        assert!(indexes(r#"":label unknown""#)
+            .resolve(&tx)
+            .unwrap_err()
+            .contains(":label unknown"));
+        assert!(indexes("2").resolve(&tx).is_err());
+    }
 }
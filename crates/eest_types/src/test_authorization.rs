@@ -1,4 +1,5 @@
 use super::SignedAuthorization;
+use cfx_types::Address;
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
 
 /// Struct for test authorization
@@ -7,6 +8,17 @@ use serde::{de::Error, Deserialize, Deserializer, Serialize};
 pub struct TestAuthorization {
     #[serde(flatten)]
     inner: SignedAuthorization,
+    /// The authority expected to be recovered from the signature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signer: Option<Address>,
+}
+
+impl TestAuthorization {
+    /// Returns the inner [`SignedAuthorization`].
+    pub const fn signed(&self) -> &SignedAuthorization { &self.inner }
+
+    /// Returns the signer recorded by the fixture, if any.
+    pub const fn signer(&self) -> Option<Address> { self.signer }
 }
 
 impl From<TestAuthorization> for SignedAuthorization {
@@ -20,14 +32,21 @@ impl<'de> Deserialize<'de> for TestAuthorization {
         // used by the test files for cross client compat
         let mut value: serde_json::Value =
             Deserialize::deserialize(deserializer)?;
+        let mut signer = None;
         if let Some(val) = value.as_object_mut() {
             if val.contains_key("v") && val.contains_key("yParity") {
                 val.remove("v");
             }
+            if let Some(raw_signer) = val.remove("signer") {
+                signer = Some(
+                    serde_json::from_value(raw_signer)
+                        .map_err(D::Error::custom)?,
+                );
+            }
         }
         let inner: SignedAuthorization =
             serde_json::from_value(value).map_err(D::Error::custom)?;
-        Ok(Self { inner })
+        Ok(Self { inner, signer })
     }
 }
 
@@ -52,6 +71,14 @@ mod tests {
         }"#;
 
         let auth: TestAuthorization = serde_json::from_str(auth).unwrap();
+        assert_eq!(
+            auth.signer(),
+            Some(
+                "6389e7f33ce3b1e94e4325ef02829cd12297ef71"
+                    .parse::<Address>()
+                    .unwrap()
+            )
+        );
         println!("{:?}", auth);
     }
 
@@ -1,5 +1,6 @@
-use cfx_types::{address_util::hex_to_address, Address};
+use cfx_types::{address_util::hex_to_address, Address, U256};
 use serde::{de, Deserialize};
+use std::{collections::HashMap, str::FromStr};
 
 /// Deserializes a [string][String] as a [u64].
 pub fn deserialize_str_as_u64<'de, D>(
@@ -16,6 +17,48 @@ where D: de::Deserializer<'de> {
     .map_err(serde::de::Error::custom)
 }
 
+/// Parses a `0x`-prefixed hex string, of any length up to 32 bytes, or a
+/// decimal string as a [U256].
+pub fn parse_u256(string: &str) -> Result<U256, String> {
+    match string.strip_prefix("0x") {
+        Some("") => Ok(U256::zero()),
+        Some(hex) if hex.len() > 64 => {
+            Err(format!("hex longer than 32 bytes: {}", string))
+        }
+        Some(hex) => U256::from_str(hex)
+            .map_err(|e| format!("invalid hex {}: {}", string, e)),
+        None => U256::from_dec_str(string)
+            .map_err(|e| format!("invalid decimal {}: {:?}", string, e)),
+    }
+}
+
+/// Deserializes a [string][String] as a [U256], see [parse_u256].
+pub fn deserialize_str_as_u256<'de, D>(
+    deserializer: D,
+) -> Result<U256, D::Error>
+where D: de::Deserializer<'de> {
+    let string = String::deserialize(deserializer)?;
+    parse_u256(&string).map_err(de::Error::custom)
+}
+
+/// Deserializes a map of [strings][String] as a map of [U256], see
+/// [parse_u256]. Keys which only differ by their encoding are an error.
+pub fn deserialize_u256_map<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<U256, U256>, D::Error>
+where D: de::Deserializer<'de> {
+    let strings = HashMap::<String, String>::deserialize(deserializer)?;
+    let mut map = HashMap::with_capacity(strings.len());
+    for (key, value) in strings {
+        let parsed = parse_u256(&key).map_err(de::Error::custom)?;
+        let value = parse_u256(&value).map_err(de::Error::custom)?;
+        if map.insert(parsed, value).is_some() {
+            return Err(de::Error::custom(format!("duplicate key {}", key)));
+        }
+    }
+    Ok(map)
+}
+
 /// Deserializes a [string][String] as an optional [Address].
 pub fn deserialize_maybe_empty<'de, D>(
     deserializer: D,